@@ -17,6 +17,7 @@ import java.util.List;
 public class Item {
     private String uuid;
     private String name;
+    // Both dates are in microseconds since the epoch, as Toodle stores them.
     private Long dueDate;
     private Long completionDate;
 
@@ -45,7 +46,7 @@ public class Item {
         if (timestamp == null) {
             completionDate = null;
         } else {
-            completionDate = timestamp / 1000;
+            completionDate = timestamp * 1000;
         }
         return this;
     }
@@ -53,7 +54,7 @@ public class Item {
     Item dueDate(final int year, final int month, final int date) {
         final Calendar cal = Calendar.getInstance();
         cal.set(year, month, date);
-        dueDate = cal.getTimeInMillis() / 1000;
+        dueDate = cal.getTimeInMillis() * 1000;
         return this;
     }
 
@@ -62,13 +63,13 @@ public class Item {
         item.uuid = nativeItem.uuid;
         item.name = nativeItem.itemName;
         if (nativeItem.dueDate != null) {
-            item.dueDate = nativeItem.dueDate.getValue().longValue();
+            item.dueDate = nativeItem.dueDate.getValue();
             if (item.dueDate == 0) {
                 item.dueDate = null;
             }
         }
         if (nativeItem.completionDate != null) {
-            item.completionDate = nativeItem.completionDate.getValue().longValue();
+            item.completionDate = nativeItem.completionDate.getValue();
             if (item.completionDate == 0) {
                 item.completionDate = null;
             }
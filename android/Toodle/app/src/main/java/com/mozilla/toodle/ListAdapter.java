@@ -134,7 +134,7 @@ public class ListAdapter extends RecyclerView.Adapter<ListAdapter.ViewHolder> {
             ((TextView) holder.itemView.findViewById(R.id.itemDueDate)).setText(
                     context.getResources().getString(
                             R.string.due_date,
-                            SimpleDateFormat.getDateInstance(SimpleDateFormat.MEDIUM).format(dueDate * 1000)
+                            SimpleDateFormat.getDateInstance(SimpleDateFormat.MEDIUM).format(dueDate / 1000)
                     )
             );
         }
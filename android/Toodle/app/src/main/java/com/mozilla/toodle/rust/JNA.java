@@ -9,7 +9,7 @@ import com.sun.jna.Library;
 import com.sun.jna.Native;
 import com.sun.jna.NativeLibrary;
 import com.sun.jna.Pointer;
-import com.sun.jna.ptr.NativeLongByReference;
+import com.sun.jna.ptr.LongByReference;
 
 public interface JNA extends Library {
     String JNA_LIBRARY_NAME = "toodle_ffi";
@@ -20,14 +20,25 @@ public interface JNA extends Library {
     Pointer new_toodle(String dbPath);
     void toodle_destroy(Pointer toodle);
 
-    void toodle_create_item(Pointer listManager, String name, NativeLongByReference dueDate);
-    void toodle_update_item_by_uuid(Pointer listManager, String uuid, String name, NativeLongByReference dueDate, NativeLongByReference completionDate);
-    void toodle_all_items(Pointer listManager, NativeItemsCallback callback);
+    void toodle_create_item(Pointer listManager, String name, LongByReference dueDate);
+    void toodle_update_item_by_uuid(Pointer listManager, String uuid, String name, LongByReference dueDate, LongByReference completionDate);
+    NativeResult toodle_all_items(Pointer listManager, NativeItemsCallback callback);
     void item_c_destroy(Pointer item);
 
+    NativeResult toodle_rename_label(Pointer toodle, Pointer label, String name);
+    NativeResult toodle_update_label_color(Pointer toodle, Pointer label, String color);
+    void label_destroy(Pointer label);
+
     void store_register_observer(Pointer Store, String key, Pointer attributes, int len, NativeTxObserverCallback callback );
     void store_unregister_observer(Pointer Store, String key);
     long store_entid_for_attribute(Pointer Store, String attr);
 
+    Pointer toodle_register_change_observer(Pointer toodle, String key, NativeItemsChangedCallback callback);
+    NativeResult toodle_take_change_events(Pointer toodle, Pointer observer);
+    int change_event_list_count(Pointer eventList);
+    NativeChangeEvent.ByReference change_event_list_entry_at(Pointer eventList, int index);
+    void change_event_list_destroy(Pointer eventList);
+    void toodle_unregister_change_observer(Pointer toodle, Pointer observer);
+
     NativeResult toodle_sync(Pointer toodle, String userUuid, String serverUri);
 }
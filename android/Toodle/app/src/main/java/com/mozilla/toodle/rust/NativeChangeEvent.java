@@ -0,0 +1,44 @@
+/* -*- Mode: Java; c-basic-offset: 4; tab-width: 20; indent-tabs-mode: nil; -*-
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+package com.mozilla.toodle.rust;
+
+import android.support.annotation.Nullable;
+
+import com.sun.jna.Structure;
+
+import java.util.Arrays;
+import java.util.List;
+
+public class NativeChangeEvent extends Structure {
+    public static class ByReference extends NativeChangeEvent implements Structure.ByReference {
+    }
+
+    public static class ByValue extends NativeChangeEvent implements Structure.ByValue {
+    }
+
+    public static final int ITEM_CREATED = 0;
+    public static final int ITEM_UPDATED = 1;
+    public static final int ITEM_DELETED = 2;
+    public static final int LABEL_CREATED = 3;
+    public static final int LABEL_RENAMED = 4;
+    public static final int LABEL_CHANGED = 5;
+    public static final int LABEL_DELETED = 6;
+    public static final int LIST_CREATED = 7;
+    public static final int LIST_CHANGED = 8;
+    public static final int LIST_DELETED = 9;
+
+    public int kind;
+    // The item's or list's UUID, or the label's name.
+    public String id;
+    @Nullable public String previousName;
+    // An updated item's changed attributes, separated by spaces.
+    @Nullable public String fields;
+
+    @Override
+    protected List<String> getFieldOrder() {
+        return Arrays.asList("kind", "id", "previousName", "fields");
+    }
+}
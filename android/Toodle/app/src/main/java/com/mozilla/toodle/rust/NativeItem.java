@@ -9,7 +9,7 @@ import android.support.annotation.Nullable;
 import android.util.Log;
 
 import com.sun.jna.Structure;
-import com.sun.jna.ptr.NativeLongByReference;
+import com.sun.jna.ptr.LongByReference;
 
 import java.io.Closeable;
 import java.util.Arrays;
@@ -24,12 +24,20 @@ public class NativeItem extends Structure implements Closeable {
 
     public String uuid;
     public String itemName;
-    @Nullable public NativeLongByReference dueDate;
-    @Nullable public NativeLongByReference completionDate;
+    @Nullable public String notes;
+    @Nullable public LongByReference dueDate;
+    @Nullable public LongByReference completionDate;
+    @Nullable public String recurrence;
+    @Nullable public String list;
+    @Nullable public String parent;
+    public long childCount;
+    @Nullable public LongByReference deletedAt;
+    @Nullable public LongByReference createdAt;
+    @Nullable public LongByReference modifiedAt;
 
     @Override
     protected List<String> getFieldOrder() {
-        return Arrays.asList("uuid", "itemName", "dueDate", "completionDate");
+        return Arrays.asList("uuid", "itemName", "notes", "dueDate", "completionDate", "recurrence", "list", "parent", "childCount", "deletedAt", "createdAt", "modifiedAt");
     }
 
     @Override
@@ -6,7 +6,12 @@
 package com.mozilla.toodle.rust;
 
 import com.sun.jna.Callback;
+import com.sun.jna.Pointer;
 
+/**
+ * Called from a native thread when items, labels or lists change; the changes themselves are
+ * read with {@link Toodle#takeChangeEvents(Pointer)}.
+ */
 public interface NativeItemsChangedCallback extends Callback {
-    void items();
+    void changed(String key);
 }
@@ -6,13 +6,16 @@
 package com.mozilla.toodle.rust;
 
 import android.content.Context;
+import android.text.TextUtils;
 import android.util.Log;
 
 import com.mozilla.toodle.Item;
 import com.sun.jna.Memory;
-import com.sun.jna.NativeLong;
 import com.sun.jna.Pointer;
-import com.sun.jna.ptr.NativeLongByReference;
+import com.sun.jna.ptr.LongByReference;
+
+import java.util.ArrayList;
+import java.util.List;
 
 public class Toodle extends RustObject {
     static {
@@ -41,14 +44,14 @@ public class Toodle extends RustObject {
         JNA.INSTANCE.toodle_create_item(
                 rawPointer,
                 item.name(),
-                new NativeLongByReference(new NativeLong(item.dueDate()))
+                new LongByReference(item.dueDate())
         );
     }
 
     public void updateItem(Item item) {
-        final NativeLongByReference completionDateRef;
+        final LongByReference completionDateRef;
         if (item.completionDate() != null) {
-            completionDateRef = new NativeLongByReference(new NativeLong(item.completionDate()));
+            completionDateRef = new LongByReference(item.completionDate());
         } else {
             completionDateRef = null;
         }
@@ -57,13 +60,32 @@ public class Toodle extends RustObject {
                 rawPointer,
                 item.uuid(),
                 item.name(),
-                new NativeLongByReference(new NativeLong(item.dueDate())),
+                new LongByReference(item.dueDate()),
                 completionDateRef
         );
     }
 
     public void getAllItems(NativeItemsCallback callback) {
-        JNA.INSTANCE.toodle_all_items(rawPointer, callback);
+        final NativeResult result = JNA.INSTANCE.toodle_all_items(rawPointer, callback);
+        if (!TextUtils.isEmpty(result.error)) {
+            throw new IllegalStateException("Couldn't fetch items: " + result.error);
+        }
+    }
+
+    public Pointer renameLabel(Pointer label, String name) {
+        final NativeResult result = JNA.INSTANCE.toodle_rename_label(rawPointer, label, name);
+        if (!TextUtils.isEmpty(result.error)) {
+            throw new IllegalStateException("Couldn't rename label: " + result.error);
+        }
+        return result.okRawPtr;
+    }
+
+    public Pointer updateLabelColor(Pointer label, String color) {
+        final NativeResult result = JNA.INSTANCE.toodle_update_label_color(rawPointer, label, color);
+        if (!TextUtils.isEmpty(result.error)) {
+            throw new IllegalStateException("Couldn't update label color: " + result.error);
+        }
+        return result.okRawPtr;
     }
 
     public NativeResult sync() {
@@ -90,6 +112,30 @@ public class Toodle extends RustObject {
         JNA.INSTANCE.store_unregister_observer(rawPointer, key);
     }
 
+    public Pointer registerChangeObserver(String key, NativeItemsChangedCallback callback) {
+        return JNA.INSTANCE.toodle_register_change_observer(rawPointer, key, callback);
+    }
+
+    public List<NativeChangeEvent> takeChangeEvents(Pointer observer) {
+        final NativeResult result = JNA.INSTANCE.toodle_take_change_events(rawPointer, observer);
+        if (!TextUtils.isEmpty(result.error)) {
+            throw new IllegalStateException("Couldn't take change events: " + result.error);
+        }
+        final Pointer eventList = result.okRawPtr;
+        final int count = JNA.INSTANCE.change_event_list_count(eventList);
+        final List<NativeChangeEvent> events = new ArrayList<>(count);
+        for (int i = 0; i < count; i++) {
+            events.add(JNA.INSTANCE.change_event_list_entry_at(eventList, i));
+        }
+        // The events' strings have been copied into Java by now.
+        JNA.INSTANCE.change_event_list_destroy(eventList);
+        return events;
+    }
+
+    public void unregisterChangeObserver(Pointer observer) {
+        JNA.INSTANCE.toodle_unregister_change_observer(rawPointer, observer);
+    }
+
     @Override
     public void close() {
         Log.i("Toodle", "close");
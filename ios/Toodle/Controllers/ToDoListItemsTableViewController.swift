@@ -22,7 +22,12 @@ class ToDoListItemsTableViewController: UITableViewController {
 
         self.tableView.addSubview(self.syncToRefresh)
 
-        self.items = ToodleLib.sharedInstance.allItems()
+        do {
+            self.items = try ToodleLib.sharedInstance.allItems()
+        } catch {
+            print("Couldn't fetch items: \(error)")
+            self.items = []
+        }
         let attrs = [":todo/uuid", ":todo/name", ":todo/due_date", ":todo/completion_date"]
         ToodleLib.sharedInstance.register(key: "ToDoListItemsTableViewController", observer: self, attributes: attrs)
 
@@ -94,7 +99,11 @@ extension ToDoListItemsTableViewController: Observing {
     func transactionDidOccur(key: String, reports: [TxChange]) {
         print("transaction did occur \(key)")
         DispatchQueue.main.async {
-            self.items = ToodleLib.sharedInstance.allItems()
+            do {
+                self.items = try ToodleLib.sharedInstance.allItems()
+            } catch {
+                print("Couldn't fetch items: \(error)")
+            }
             self.tableView.reloadData()
         }
     }
@@ -5,7 +5,12 @@
 import Foundation
 
 extension Date {
+    // Toodle timestamps are microseconds since the epoch.
     func asInt64Timestamp() -> Int64 {
-        return Int64(self.timeIntervalSince1970)
+        return Int64((self.timeIntervalSince1970 * 1_000_000).rounded())
+    }
+
+    init(int64Timestamp timestamp: Int64) {
+        self.init(timeIntervalSince1970: Double(timestamp) / 1_000_000)
     }
 }
@@ -40,12 +40,11 @@ class Item {
             guard let date = raw.pointee.dueDate else {
                 return nil
             }
-            return Date(timeIntervalSince1970: Double(date.pointee))
+            return Date(int64Timestamp: date.pointee)
         }
         set {
             if let d = newValue {
-                let timestamp = d.timeIntervalSince1970
-                var date = Int64(timestamp)
+                var date = d.asInt64Timestamp()
                 item_set_due_date(UnsafeMutablePointer<CItem>(mutating: raw), AutoreleasingUnsafeMutablePointer<Int64>(&date))
             }
         }
@@ -56,12 +55,11 @@ class Item {
             guard let date = raw.pointee.completionDate else {
                 return nil
             }
-            return Date(timeIntervalSince1970: Double(date.pointee))
+            return Date(int64Timestamp: date.pointee)
         }
         set {
             if let d = newValue {
-                let timestamp = d.timeIntervalSince1970
-                var date = Int64(timestamp)
+                var date = d.asInt64Timestamp()
                 item_set_completion_date(UnsafeMutablePointer<CItem>(mutating: raw), AutoreleasingUnsafeMutablePointer<Int64>(&date))
             }
         }
@@ -7,6 +7,10 @@ import UIKit
 
 import Mentat
 
+enum ToodleError: Error {
+    case failed(message: String)
+}
+
 class ToodleLib: Mentat {
 
     class var sharedInstance: ToodleLib {
@@ -29,11 +33,15 @@ class ToodleLib: Mentat {
         return OpaquePointer(AutoreleasingUnsafeMutablePointer<[OpaquePointer]>(&pointerArray))
     }
 
-    func allItems() -> [Item] {
-        let items = toodle_get_all_items(self.getRaw())
+    func allItems() throws -> [Item] {
+        let result = toodle_get_all_items(self.getRaw())
+        if let error = result.pointee.err {
+            throw ToodleError.failed(message: String(cString: error))
+        }
+        let items = OpaquePointer(result.pointee.ok!)
         var allItems: [Item] = []
         for index in 0..<item_list_count(items) {
-            let item = Item(raw: item_list_entry_at(items, Int(index))!)
+            let item = Item(raw: item_list_entry_at(items, index)!)
             allItems.append(item)
         }
         return allItems
@@ -43,6 +51,22 @@ class ToodleLib: Mentat {
         return Label(raw: toodle_create_label(self.getRaw(), name, color.toHex()!))
     }
 
+    func rename(label: Label, to name: String) throws -> Label {
+        let result = toodle_rename_label(self.getRaw(), label.getRaw(), name)
+        if let error = result.pointee.err {
+            throw ToodleError.failed(message: String(cString: error))
+        }
+        return Label(raw: OpaquePointer(result.pointee.ok!))
+    }
+
+    func update(label: Label, color: UIColor) throws -> Label {
+        let result = toodle_update_label_color(self.getRaw(), label.getRaw(), color.toHex()!)
+        if let error = result.pointee.err {
+            throw ToodleError.failed(message: String(cString: error))
+        }
+        return Label(raw: OpaquePointer(result.pointee.ok!))
+    }
+
     func createItem(withName name: String, dueDate: Date?, completionDate: Date?, labels: [Label]) -> Item? {
         var dd: UnsafeMutablePointer<Int64>? = nil
         if let due = dueDate {
@@ -0,0 +1,45 @@
+#include <stdint.h>
+
+#ifndef events_h
+#define events_h
+
+struct Toodle;
+struct ChangeObserver;
+
+enum CChangeKind {
+    CChangeItemCreated = 0,
+    CChangeItemUpdated = 1,
+    CChangeItemDeleted = 2,
+    CChangeLabelCreated = 3,
+    CChangeLabelRenamed = 4,
+    CChangeLabelChanged = 5,
+    CChangeLabelDeleted = 6,
+    CChangeListCreated = 7,
+    CChangeListChanged = 8,
+    CChangeListDeleted = 9,
+};
+
+struct CChangeEvent {
+    int32_t kind;
+    char* _Nonnull id;
+    char* _Nullable previousName;
+    char* _Nullable fields;
+};
+
+typedef struct CChangeEvent CChangeEvent;
+
+struct CChangeEventList {
+    struct CChangeEvent*_Nonnull* _Nonnull events;
+    uint64_t* _Nonnull len;
+};
+
+typedef struct CChangeEventList CChangeEventList;
+
+struct ChangeObserver* _Nullable toodle_register_change_observer(const struct Toodle* _Nonnull manager, const char* _Nonnull key, void (*_Nonnull callback_fn)(const char* _Nonnull key));
+struct Result*_Nonnull toodle_take_change_events(const struct Toodle* _Nonnull manager, const struct ChangeObserver* _Nonnull observer);
+const int32_t change_event_list_count(const struct CChangeEventList* _Nonnull list);
+const struct CChangeEvent* _Nullable change_event_list_entry_at(const struct CChangeEventList* _Nonnull list, const int32_t index);
+const void change_event_list_destroy(const struct CChangeEventList* _Nonnull list);
+void toodle_unregister_change_observer(const struct Toodle* _Nonnull manager, struct ChangeObserver* _Nonnull observer);
+
+#endif /* events_h */
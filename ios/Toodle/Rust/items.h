@@ -3,12 +3,22 @@
 #ifndef items_h
 #define items_h
 
+// Timestamps are microseconds since the epoch.
 struct Toodle;
+struct ItemQuery;
 struct CItem {
     char* _Nullable uuid;
     char* _Nonnull name;
+    char* _Nullable notes;
     int64_t* _Nullable dueDate;
     int64_t* _Nullable completionDate;
+    char* _Nullable recurrence;
+    char* _Nullable list;
+    char* _Nullable parent;
+    int64_t childCount;
+    int64_t* _Nullable deletedAt;
+    int64_t* _Nullable createdAt;
+    int64_t* _Nullable modifiedAt;
 };
 
 typedef struct CItem CItem;
@@ -18,19 +28,109 @@ struct CItemList {
     uint64_t* _Nonnull len;
 };
 
+struct CSearchResult {
+    struct CItem item;
+    double score;
+    char* _Nonnull snippet;
+};
+
+typedef struct CSearchResult CSearchResult;
+
+struct CSearchResultList {
+    struct CSearchResult*_Nonnull* _Nonnull list;
+    uint64_t* _Nonnull len;
+};
+
+struct COccurrence {
+    struct CItem item;
+    int64_t dueDate;
+};
+
+typedef struct COccurrence COccurrence;
+
+struct COccurrenceList {
+    struct COccurrence*_Nonnull* _Nonnull occurrences;
+    uint64_t* _Nonnull len;
+};
+
+typedef struct COccurrenceList COccurrenceList;
+
+struct CItemChange {
+    char* _Nonnull attribute;
+    char* _Nullable oldValue;
+    char* _Nullable newValue;
+    int64_t txInstant;
+    int32_t origin;
+};
+
+typedef struct CItemChange CItemChange;
+
+struct CItemChangeList {
+    struct CItemChange*_Nonnull* _Nonnull changes;
+    uint64_t* _Nonnull len;
+};
+
+typedef struct CItemChangeList CItemChangeList;
+
 struct Label;
 
 const struct CItem* _Nullable toodle_create_item(const struct Toodle* _Nonnull manager, const char* _Nonnull name, const int64_t* _Nullable due_date);
 const void toodle_update_item(const struct Toodle* _Nonnull manager, const struct CItem* _Nonnull item, const char* _Nonnull name, const int64_t* _Nullable due_date, const int64_t* _Nullable completion_date, struct label*_Nonnull* _Nullable list);
 const void toodle_update_item_by_uuid (const struct Toodle* _Nonnull manager, const char* _Nonnull uuid, const char* _Nonnull name, const int64_t* _Nullable due_date, const int64_t* _Nullable completion_date);
-const struct CItemList*_Nonnull toodle_get_all_items(const struct Toodle* _Nonnull manager);
-const uint64_t item_list_count(const struct CItemList* _Nonnull list);
-const struct CItem* _Nullable item_list_entry_at(const struct CItemList* _Nonnull list, size_t index);
+struct Result*_Nonnull toodle_get_all_items(const struct Toodle* _Nonnull manager);
+struct Result*_Nonnull toodle_query_items(const struct Toodle* _Nonnull manager, const struct ItemQuery* _Nonnull query);
+const int32_t item_list_count(const struct CItemList* _Nonnull list);
+const struct CItem* _Nullable item_list_entry_at(const struct CItemList* _Nonnull list, const int32_t index);
 const struct CItem* _Nullable toodle_item_for_uuid(const struct Toodle* _Nonnull manager, const char* _Nonnull uuid);
+struct Result*_Nonnull toodle_search_items(const struct Toodle* _Nonnull manager, const char* _Nonnull text);
+const int32_t search_result_list_count(const struct CSearchResultList* _Nonnull list);
+const struct CSearchResult* _Nullable search_result_list_entry_at(const struct CSearchResultList* _Nonnull list, const int32_t index);
+const void search_result_list_destroy(const struct CSearchResultList* _Nonnull list);
+struct Result*_Nonnull toodle_update_item_notes(const struct Toodle* _Nonnull manager, const char* _Nonnull uuid, const char* _Nullable notes);
+struct CItem* _Nullable toodle_create_subitem(const struct Toodle* _Nonnull manager, const char* _Nonnull parent, const char* _Nonnull name, const int64_t* _Nullable due_date);
+struct Result*_Nonnull toodle_fetch_children(const struct Toodle* _Nonnull manager, const char* _Nonnull uuid);
+struct Result*_Nonnull toodle_move_item(const struct Toodle* _Nonnull manager, const char* _Nonnull uuid, const char* _Nullable parent);
+struct Result*_Nonnull toodle_reorder_item(const struct Toodle* _Nonnull manager, const char* _Nonnull uuid, const char* _Nullable previous, const char* _Nullable next);
+struct Result*_Nonnull toodle_complete_item(const struct Toodle* _Nonnull manager, const char* _Nonnull uuid, const int64_t completion_date, const int32_t cascade);
+struct Result*_Nonnull toodle_update_item_recurrence(const struct Toodle* _Nonnull manager, const char* _Nonnull uuid, const char* _Nullable rule);
+struct Result*_Nonnull toodle_fetch_occurrences(const struct Toodle* _Nonnull manager, const int64_t start, const int64_t end);
+const int32_t occurrence_list_count(const struct COccurrenceList* _Nonnull list);
+const struct COccurrence* _Nullable occurrence_list_entry_at(const struct COccurrenceList* _Nonnull list, const int32_t index);
+const void occurrence_list_destroy(const struct COccurrenceList* _Nonnull list);
+struct Result*_Nonnull toodle_fetch_item_history(const struct Toodle* _Nonnull manager, const char* _Nonnull uuid);
+const int32_t item_change_list_count(const struct CItemChangeList* _Nonnull list);
+const struct CItemChange* _Nullable item_change_list_entry_at(const struct CItemChangeList* _Nonnull list, const int32_t index);
+const void item_change_list_destroy(const struct CItemChangeList* _Nonnull list);
+struct Result*_Nonnull toodle_delete_item(const struct Toodle* _Nonnull manager, const char* _Nonnull uuid);
+struct Result*_Nonnull toodle_trash_item(const struct Toodle* _Nonnull manager, const char* _Nonnull uuid);
+struct Result*_Nonnull toodle_restore_item(const struct Toodle* _Nonnull manager, const char* _Nonnull uuid);
+struct Result*_Nonnull toodle_empty_trash(const struct Toodle* _Nonnull manager, const int64_t older_than);
+struct Result*_Nonnull toodle_complete_items(const struct Toodle* _Nonnull manager, const char* _Nonnull const* _Nonnull uuids, size_t count, const int64_t completion_date);
+struct Result*_Nonnull toodle_uncomplete_items(const struct Toodle* _Nonnull manager, const char* _Nonnull const* _Nonnull uuids, size_t count);
+struct Result*_Nonnull toodle_set_due_date_for_items(const struct Toodle* _Nonnull manager, const char* _Nonnull const* _Nonnull uuids, size_t count, const int64_t* _Nullable due_date);
+struct Result*_Nonnull toodle_add_label_to_items(const struct Toodle* _Nonnull manager, const char* _Nonnull const* _Nonnull uuids, size_t count, const struct Label* _Nonnull label);
+struct Result*_Nonnull toodle_remove_label_from_items(const struct Toodle* _Nonnull manager, const char* _Nonnull const* _Nonnull uuids, size_t count, const struct Label* _Nonnull label);
+struct Result*_Nonnull toodle_delete_items(const struct Toodle* _Nonnull manager, const char* _Nonnull const* _Nonnull uuids, size_t count);
 
 const void item_c_destroy(const struct CItem* _Nonnull item);
 
+struct ItemQuery* _Nonnull item_query_new();
+void item_query_destroy(struct ItemQuery* _Nonnull query);
+void item_query_set_completed(struct ItemQuery* _Nonnull query, const int32_t completed);
+void item_query_set_due_after(struct ItemQuery* _Nonnull query, const int64_t* _Nullable due_after);
+void item_query_set_due_before(struct ItemQuery* _Nonnull query, const int64_t* _Nullable due_before);
+void item_query_add_label(struct ItemQuery* _Nonnull query, const char* _Nonnull name);
+void item_query_set_match_all_labels(struct ItemQuery* _Nonnull query, const int32_t all);
+void item_query_set_name_contains(struct ItemQuery* _Nonnull query, const char* _Nonnull text);
+void item_query_set_list(struct ItemQuery* _Nonnull query, const char* _Nonnull uuid);
+void item_query_set_parent(struct ItemQuery* _Nonnull query, const char* _Nonnull uuid);
+void item_query_set_top_level_only(struct ItemQuery* _Nonnull query, const int32_t top_level_only);
+void item_query_set_trash(struct ItemQuery* _Nonnull query, const int32_t trash);
+void item_query_set_order(struct ItemQuery* _Nonnull query, const int32_t order, const int32_t descending);
+void item_query_set_limit(struct ItemQuery* _Nonnull query, size_t limit);
+
 const void item_set_name(struct CItem* _Nonnull item, const char* _Nonnull description);
+const void item_set_notes(struct CItem* _Nonnull item, const char* _Nullable notes);
 const void item_set_due_date(struct CItem* _Nonnull item, const int64_t* _Nullable due_date);
 const void item_set_completion_date(struct CItem* _Nonnull item, const int64_t* _Nullable completion_date);
 
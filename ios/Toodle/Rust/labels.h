@@ -9,6 +9,9 @@ struct Label;
 void label_destroy(struct Label* _Nullable obj);
 
 struct Label* _Nonnull toodle_create_label(const struct Toodle* _Nonnull manager, const char* _Nonnull name, const char* _Nonnull color);
+struct Result* _Nonnull toodle_rename_label(const struct Toodle* _Nonnull manager, const struct Label* _Nonnull label, const char* _Nonnull name);
+struct Result* _Nonnull toodle_update_label_color(const struct Toodle* _Nonnull manager, const struct Label* _Nonnull label, const char* _Nonnull color);
+struct Result* _Nonnull toodle_delete_label(const struct Toodle* _Nonnull manager, const char* _Nonnull name);
 const struct Label* _Nonnull* _Nonnull toodle_get_all_labels(const struct Toodle* _Nonnull manager);
 const size_t label_list_count(const struct Label* _Nonnull* _Nonnull list);
 const void label_list_destroy(const struct Label* _Nonnull* _Nonnull list);
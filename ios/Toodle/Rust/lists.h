@@ -0,0 +1,33 @@
+#include <stdint.h>
+
+#ifndef lists_h
+#define lists_h
+
+struct Toodle;
+struct CList {
+    char* _Nonnull uuid;
+    char* _Nonnull name;
+    char* _Nonnull color;
+    int64_t sortOrder;
+};
+
+typedef struct CList CList;
+
+struct CListList {
+    struct CList*_Nonnull* _Nonnull lists;
+    uint64_t* _Nonnull len;
+};
+
+typedef struct CListList CListList;
+
+struct CList* _Nullable toodle_create_list(const struct Toodle* _Nonnull manager, const char* _Nonnull name, const char* _Nonnull color);
+struct Result*_Nonnull toodle_get_all_lists(const struct Toodle* _Nonnull manager);
+const int32_t list_c_list_count(const struct CListList* _Nonnull list);
+const struct CList* _Nullable list_c_list_entry_at(const struct CListList* _Nonnull list, const int32_t index);
+const void list_c_list_destroy(const struct CListList* _Nonnull list);
+void list_c_destroy(struct CList* _Nonnull list);
+struct Result*_Nonnull toodle_update_list(const struct Toodle* _Nonnull manager, const char* _Nonnull uuid, const char* _Nullable name, const char* _Nullable color, const int64_t* _Nullable sort_order);
+struct Result*_Nonnull toodle_delete_list(const struct Toodle* _Nonnull manager, const char* _Nonnull uuid);
+struct Result*_Nonnull toodle_move_item_to_list(const struct Toodle* _Nonnull manager, const char* _Nonnull uuid, const char* _Nonnull list);
+
+#endif /* lists_h */
@@ -0,0 +1,45 @@
+#include <stdint.h>
+#include "items.h"
+
+#ifndef reminders_h
+#define reminders_h
+
+// Times are microseconds since the epoch, and offsets and windows are in seconds.
+struct Toodle;
+struct CReminder {
+    char* _Nonnull uuid;
+    char* _Nonnull item;
+    int64_t* _Nullable at;
+    int64_t* _Nullable offset;
+    int64_t* _Nullable snoozedUntil;
+};
+
+typedef struct CReminder CReminder;
+
+struct CPendingReminder {
+    struct CReminder reminder;
+    struct CItem item;
+    int64_t fireAt;
+};
+
+typedef struct CPendingReminder CPendingReminder;
+
+struct CPendingReminderList {
+    struct CPendingReminder*_Nonnull* _Nonnull reminders;
+    uint64_t* _Nonnull len;
+};
+
+typedef struct CPendingReminderList CPendingReminderList;
+
+struct CReminder* _Nullable toodle_add_reminder_at(const struct Toodle* _Nonnull manager, const char* _Nonnull item, const int64_t at);
+struct CReminder* _Nullable toodle_add_reminder_before_due(const struct Toodle* _Nonnull manager, const char* _Nonnull item, const int64_t offset);
+void reminder_c_destroy(struct CReminder* _Nonnull reminder);
+struct Result*_Nonnull toodle_pending_reminders(const struct Toodle* _Nonnull manager, const int64_t now, const int64_t window);
+const int32_t pending_reminder_list_count(const struct CPendingReminderList* _Nonnull list);
+const struct CPendingReminder* _Nullable pending_reminder_list_entry_at(const struct CPendingReminderList* _Nonnull list, const int32_t index);
+const void pending_reminder_list_destroy(const struct CPendingReminderList* _Nonnull list);
+struct Result*_Nonnull toodle_acknowledge_reminder(const struct Toodle* _Nonnull manager, const char* _Nonnull uuid);
+struct Result*_Nonnull toodle_snooze_reminder(const struct Toodle* _Nonnull manager, const char* _Nonnull uuid, const int64_t until);
+struct Result*_Nonnull toodle_delete_reminder(const struct Toodle* _Nonnull manager, const char* _Nonnull uuid);
+
+#endif /* reminders_h */
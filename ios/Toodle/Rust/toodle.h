@@ -1,6 +1,9 @@
 #include <stdint.h>
 #include "labels.h"
+#include "lists.h"
 #import "items.h"
+#include "events.h"
+#include "reminders.h"
 #include "store.h"
 
 struct Store*_Nonnull new_toodle(const char*_Nonnull uri);
@@ -8,3 +11,16 @@ struct Store*_Nonnull new_label(const char*_Nonnull uri);
 void toodle_destroy(struct Store*_Nonnull toodle);
 struct Result*_Nonnull toodle_sync(struct Store*_Nonnull toodle, const char*_Nonnull user_uuid, const char*_Nonnull server_uri);
 
+const int32_t toodle_can_undo(struct Store*_Nonnull toodle);
+const int32_t toodle_can_redo(struct Store*_Nonnull toodle);
+struct Result*_Nonnull toodle_undo(struct Store*_Nonnull toodle);
+struct Result*_Nonnull toodle_redo(struct Store*_Nonnull toodle);
+
+char* _Nullable toodle_export_json(struct Store*_Nonnull toodle);
+struct Result*_Nonnull toodle_import_json(struct Store*_Nonnull toodle, const char*_Nonnull json);
+char* _Nullable toodle_export_ics(struct Store*_Nonnull toodle);
+struct Result*_Nonnull toodle_import_ics(struct Store*_Nonnull toodle, const char*_Nonnull ics);
+char* _Nullable toodle_export_todo_txt(struct Store*_Nonnull toodle);
+struct Result*_Nonnull toodle_import_todo_txt(struct Store*_Nonnull toodle, const char*_Nonnull text);
+char* _Nullable toodle_export_taskwarrior(struct Store*_Nonnull toodle);
+struct Result*_Nonnull toodle_import_taskwarrior(struct Store*_Nonnull toodle, const char*_Nonnull json);
@@ -37,13 +37,72 @@ error_chain! {
             description("Item not found")
             display("Item {} not found", uuid)
         }
+        ItemCycle(uuid: String) {
+            description("Item would become its own ancestor")
+            display("Item {} can't be moved under itself or one of its subtasks", uuid)
+        }
+        ListNotFound(uuid: String) {
+            description("List not found")
+            display("List {} not found", uuid)
+        }
+        InvalidRecurrence(rule: String) {
+            description("Invalid recurrence rule")
+            display("Invalid recurrence rule {}", rule)
+        }
+        ReminderNotFound(uuid: String) {
+            description("Reminder not found")
+            display("Reminder {} not found", uuid)
+        }
         LabelNotFound(name: String) {
             description("Label not found")
             display("Label {} not found", name)
         }
+        LabelAlreadyExists(name: String) {
+            description("Label already exists")
+            display("Label {} already exists", name)
+        }
+        InvalidExport(message: String) {
+            description("Invalid export")
+            display("Invalid export: {}", message)
+        }
+        UnsupportedExportVersion(version: i64) {
+            description("Unsupported export version")
+            display("Export version {} is newer than this version of Toodle supports", version)
+        }
+        InvalidCalendar(message: String) {
+            description("Invalid calendar")
+            display("Invalid calendar: {}", message)
+        }
+        InvalidTaskwarrior(message: String) {
+            description("Invalid Taskwarrior tasks")
+            display("Invalid Taskwarrior tasks: {}", message)
+        }
+        MalformedRow(message: String) {
+            description("Malformed row")
+            display("Malformed row: {}", message)
+        }
+        LabelWithoutId(name: String) {
+            description("Label has no entity id")
+            display("Label {} has no entity id; only labels fetched from the store can be used", name)
+        }
+        TransactionAborted {
+            description("Transaction aborted")
+            display("An earlier change in this transaction failed, so it has been rolled back")
+        }
         UnexpectedResultType(message: String) {
             description("An unexpected Result type was encountered")
             display("{}", message)
         }
     }
 }
+
+/// Lets the schema's migration hooks, which must return Mentat's errors, use Toodle's helpers.
+impl From<Error> for mentat::Error {
+    fn from(error: Error) -> mentat::Error {
+        match error {
+            Error(ErrorKind::MentatError(kind), _) => kind.into(),
+            Error(ErrorKind::UnknownAttribute(keyword), _) => mentat::ErrorKind::UnknownAttribute(keyword.to_string()).into(),
+            error => error.to_string().into(),
+        }
+    }
+}
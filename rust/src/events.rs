@@ -0,0 +1,316 @@
+// Copyright 2018 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! Typed change notifications.
+//!
+//! Mentat tells its observers which transactions touched which attributes, from a thread of its
+//! own, while the store may be in use elsewhere. So a `ChangeObserver` just queues up the
+//! transactions it's told about and pokes its callback; the owner of the store then takes the
+//! queued transactions and reads them back out of the log as `ChangeEvent`s.
+
+use std::collections::{
+    BTreeSet,
+    HashMap,
+};
+use std::sync::{
+    Arc,
+    Mutex,
+};
+
+use mentat::{
+    Entid,
+    HasSchema,
+    IntoResult,
+    Keyword,
+    Queryable,
+    QueryInputs,
+    TxObserver,
+    TypedValue,
+    Uuid,
+};
+
+use errors::{
+    Result,
+};
+
+use utils::{
+    attribute_entid,
+    Entity,
+    ToInner,
+};
+
+use Store;
+
+/// Something that changed in a single transaction. Items and lists are identified by UUID and
+/// labels by name; deleted things are identified as they were before they were deleted.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ChangeEvent {
+    ItemCreated(Uuid),
+    /// An item and the attributes of it that changed. Changes to its reminders are given as
+    /// the reminder attributes that changed.
+    ItemUpdated(Uuid, Vec<Keyword>),
+    ItemDeleted(Uuid),
+    LabelCreated(String),
+    LabelRenamed(String, String),
+    LabelChanged(String),
+    LabelDeleted(String),
+    ListCreated(Uuid),
+    ListChanged(Uuid),
+    ListDeleted(Uuid),
+}
+
+/// A registration for change notifications, handed back by `Toodle::register_change_observer`.
+pub struct ChangeObserver {
+    key: String,
+    pending: Arc<Mutex<Vec<Entid>>>,
+}
+
+impl ChangeObserver {
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    fn take_transactions(&self) -> Vec<Entid> {
+        match self.pending.lock() {
+            Ok(mut pending) => pending.drain(..).collect(),
+            Err(_) => vec![],
+        }
+    }
+}
+
+/// The attributes whose changes are turned into events.
+const OBSERVED_NAMESPACES: &'static [&'static str] = &["todo", "label", "list", "reminder"];
+
+pub fn register_observer<F>(store: &mut Store, key: String, notify: F) -> Result<ChangeObserver>
+where F: Fn(&str) + Send + Sync + 'static {
+    let query = r#"[:find [?a ...]
+                    :where
+                    [?a :db/valueType _]
+    ]"#;
+    let attributes: BTreeSet<Entid> = {
+        let in_progress_read = store.begin_read()?;
+        let attributes = in_progress_read.q_once(query, None).into_coll_result()?;
+        attributes.into_iter()
+                  .filter_map(|binding| {
+                      let attribute: Option<Entity> = binding.val().and_then(|v| v.to_inner());
+                      attribute.map(|a| a.id)
+                  })
+                  .filter(|&a| in_progress_read.get_ident(a).map_or(false, is_observed))
+                  .collect()
+    };
+    let pending = Arc::new(Mutex::new(vec![]));
+    let queue = pending.clone();
+    let observer = TxObserver::new(attributes, move |key, batch| {
+        if let Ok(mut queue) = queue.lock() {
+            queue.extend(batch.keys().map(|&&tx| tx));
+        }
+        notify(key);
+    });
+    store.register_observer(key.clone(), Arc::new(observer));
+    Ok(ChangeObserver {
+        key: key,
+        pending: pending,
+    })
+}
+
+pub fn unregister_observer(store: &mut Store, observer: ChangeObserver) {
+    store.unregister_observer(&observer.key);
+}
+
+/// Reads back the transactions `observer` has been told about since this was last called, in
+/// the order they were made.
+pub fn take_events(store: &mut Store, observer: &ChangeObserver) -> Result<Vec<ChangeEvent>> {
+    let mut transactions = observer.take_transactions();
+    transactions.sort();
+    transactions.dedup();
+    let in_progress_read = store.begin_read()?;
+    let mut events = vec![];
+    for tx in transactions {
+        events.extend(tx_events(&in_progress_read, tx)?);
+    }
+    Ok(events)
+}
+
+fn is_observed(keyword: &Keyword) -> bool {
+    keyword.namespace().map_or(false, |namespace| OBSERVED_NAMESPACES.contains(&namespace))
+}
+
+/// The datoms of one entity in a transaction, by attribute.
+struct EntityChanges {
+    entid: Entid,
+    datoms: Vec<(Keyword, TypedValue, bool)>,
+}
+
+impl EntityChanges {
+    fn namespace(&self) -> Option<&str> {
+        self.datoms.first().and_then(|&(ref a, _, _)| a.namespace())
+    }
+
+    fn value(&self, attribute: &Keyword, added: bool) -> Option<TypedValue> {
+        self.datoms
+            .iter()
+            .find(|&&(ref a, _, ref is_added)| a == attribute && *is_added == added)
+            .map(|&(_, ref v, _)| v.clone())
+    }
+
+    fn attributes(&self) -> Vec<Keyword> {
+        let mut attributes: Vec<Keyword> = vec![];
+        for &(ref attribute, _, _) in self.datoms.iter() {
+            if !attributes.contains(attribute) {
+                attributes.push(attribute.clone());
+            }
+        }
+        attributes
+    }
+}
+
+fn tx_events<Q: Queryable + HasSchema>(queryable: &Q, tx: Entid) -> Result<Vec<ChangeEvent>> {
+    let query = r#"[:find ?e ?a ?v ?added
+                    :in ?tx
+                    :where
+                    [(tx-data $ ?tx) [[?e ?a ?v _ ?added]]]
+                    :order (asc ?e)
+    ]"#;
+    let args = QueryInputs::with_value_sequence(vec![(var!(?tx), TypedValue::Ref(tx))]);
+    let rows = queryable.q_once(query, args).into_rel_result()?;
+
+    let mut entities: Vec<EntityChanges> = vec![];
+    for row in rows.into_iter() {
+        let e: Option<Entity> = row[0].clone().val().and_then(|v| v.to_inner());
+        let a: Option<Entity> = row[1].clone().val().and_then(|v| v.to_inner());
+        let added = match row[3].clone().val() {
+            Some(TypedValue::Boolean(added)) => added,
+            _ => continue,
+        };
+        let (e, a, v) = match (e, a, row[2].clone().val()) {
+            (Some(e), Some(a), Some(v)) => (e.id, a.id, v),
+            _ => continue,
+        };
+        let attribute = match queryable.get_ident(a) {
+            Some(attribute) if is_observed(attribute) => attribute.clone(),
+            _ => continue,
+        };
+        if entities.last().map_or(true, |entity| entity.entid != e) {
+            entities.push(EntityChanges { entid: e, datoms: vec![] });
+        }
+        if let Some(entity) = entities.last_mut() {
+            entity.datoms.push((attribute, v, added));
+        }
+    }
+
+    // Changes to reminders are reported against the item they belong to.
+    let mut reminder_fields: HashMap<Entid, Vec<Keyword>> = HashMap::new();
+    for entity in entities.iter().filter(|entity| entity.namespace() == Some("reminder")) {
+        let reminder_item = kw!(:reminder/item);
+        let item = match entity.value(&reminder_item, true).or_else(|| entity.value(&reminder_item, false)) {
+            Some(item) => Some(item),
+            None => current_value(queryable, entity.entid, &reminder_item)?,
+        };
+        if let Some(TypedValue::Ref(item)) = item {
+            let fields = reminder_fields.entry(item).or_insert_with(Vec::new);
+            for attribute in entity.attributes() {
+                if attribute != reminder_item && !fields.contains(&attribute) {
+                    fields.push(attribute);
+                }
+            }
+        }
+    }
+
+    let mut events = vec![];
+    for entity in entities.iter() {
+        match entity.namespace() {
+            Some("todo") => {
+                let fields = reminder_fields.remove(&entity.entid).unwrap_or_default();
+                events.extend(item_event(queryable, entity, fields)?);
+            },
+            Some("label") => events.extend(label_event(queryable, entity)?),
+            Some("list") => events.extend(list_event(queryable, entity)?),
+            _ => {},
+        }
+    }
+    for (item, fields) in reminder_fields {
+        if let Some(TypedValue::Uuid(uuid)) = current_value(queryable, item, &kw!(:todo/uuid))? {
+            events.push(ChangeEvent::ItemUpdated(uuid, fields));
+        }
+    }
+    Ok(events)
+}
+
+fn item_event<Q: Queryable + HasSchema>(queryable: &Q, entity: &EntityChanges, reminder_fields: Vec<Keyword>) -> Result<Option<ChangeEvent>> {
+    let uuid_attribute = kw!(:todo/uuid);
+    match (entity.value(&uuid_attribute, true), entity.value(&uuid_attribute, false)) {
+        (Some(TypedValue::Uuid(uuid)), None) => return Ok(Some(ChangeEvent::ItemCreated(uuid))),
+        (None, Some(TypedValue::Uuid(uuid))) => return Ok(Some(ChangeEvent::ItemDeleted(uuid))),
+        _ => {},
+    }
+    let uuid = match current_value(queryable, entity.entid, &uuid_attribute)? {
+        Some(TypedValue::Uuid(uuid)) => uuid,
+        _ => return Ok(None),
+    };
+    // The fulltext copies of the name change whenever the name does, and the stamps whenever
+    // anything else does, so they'd only repeat the other fields.
+    let implicit = [kw!(:todo/name_fulltext), kw!(:todo/name_trigrams), kw!(:todo/created_at), kw!(:todo/modified_at)];
+    let mut fields: Vec<Keyword> = entity.attributes()
+                                         .into_iter()
+                                         .filter(|a| !implicit.contains(a))
+                                         .collect();
+    for field in reminder_fields {
+        if !fields.contains(&field) {
+            fields.push(field);
+        }
+    }
+    if fields.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(ChangeEvent::ItemUpdated(uuid, fields)))
+}
+
+fn label_event<Q: Queryable + HasSchema>(queryable: &Q, entity: &EntityChanges) -> Result<Option<ChangeEvent>> {
+    let name_attribute = kw!(:label/name);
+    let name = |value: TypedValue| -> Option<String> { value.to_inner() };
+    Ok(match (entity.value(&name_attribute, false), entity.value(&name_attribute, true)) {
+        (None, Some(new)) => name(new).map(ChangeEvent::LabelCreated),
+        (Some(old), None) => name(old).map(ChangeEvent::LabelDeleted),
+        (Some(old), Some(new)) => match (name(old), name(new)) {
+            (Some(old), Some(new)) => Some(ChangeEvent::LabelRenamed(old, new)),
+            _ => None,
+        },
+        (None, None) => current_value(queryable, entity.entid, &name_attribute)?
+            .and_then(name)
+            .map(ChangeEvent::LabelChanged),
+    })
+}
+
+fn list_event<Q: Queryable + HasSchema>(queryable: &Q, entity: &EntityChanges) -> Result<Option<ChangeEvent>> {
+    let uuid_attribute = kw!(:list/uuid);
+    Ok(match (entity.value(&uuid_attribute, true), entity.value(&uuid_attribute, false)) {
+        (Some(TypedValue::Uuid(uuid)), None) => Some(ChangeEvent::ListCreated(uuid)),
+        (None, Some(TypedValue::Uuid(uuid))) => Some(ChangeEvent::ListDeleted(uuid)),
+        _ => match current_value(queryable, entity.entid, &uuid_attribute)? {
+            Some(TypedValue::Uuid(uuid)) => Some(ChangeEvent::ListChanged(uuid)),
+            _ => None,
+        },
+    })
+}
+
+fn current_value<Q: Queryable + HasSchema>(queryable: &Q, entid: Entid, attribute: &Keyword) -> Result<Option<TypedValue>> {
+    let attribute = attribute_entid(queryable, attribute.clone())?;
+    let query = r#"[:find ?v .
+                    :in ?e ?a
+                    :where
+                    [?e ?a ?v]
+    ]"#;
+    let args = QueryInputs::with_value_sequence(vec![(var!(?e), TypedValue::Ref(entid)),
+                                                     (var!(?a), TypedValue::Ref(attribute.0))]);
+    Ok(queryable
+        .q_once(query, args)
+        .into_scalar_result()?
+        .and_then(|binding| binding.val()))
+}
@@ -0,0 +1,280 @@
+// Copyright 2018 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+use std::collections::{
+    BTreeMap,
+    HashSet,
+};
+
+use time::Timespec;
+
+use rusqlite::{
+    self,
+    Connection,
+};
+
+use mentat::{
+    Entid,
+    HasSchema,
+    InProgress,
+    IntoResult,
+    Keyword,
+    Queryable,
+    QueryInputs,
+    TypedValue,
+    Uuid,
+};
+
+use mentat::entity_builder::{
+    BuildTerms,
+    TermBuilder,
+};
+
+use mentat_core::{
+    KnownEntid,
+};
+
+use errors::{
+    Result,
+};
+
+use utils::{
+    attribute_entid,
+    Entity,
+    ToInner,
+    ToTypedValue,
+};
+
+use Store;
+
+/// Where a change was made. Changes from before this device kept track, such as those made
+/// before it was upgraded to a version of Toodle that does, can't be told apart.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChangeOrigin {
+    Local,
+    Sync,
+    Unknown,
+}
+
+/// A change to one attribute of an item. A single-valued attribute that's given a new value
+/// has both an old and a new value; adding or removing one of a multi-valued attribute's
+/// values, such as a label, only has one or the other.
+///
+/// References to labels, lists and parent items are given as the label's name, or the list's
+/// or parent's UUID, if they still exist.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ItemChange {
+    pub attribute: Keyword,
+    pub old_value: Option<TypedValue>,
+    pub new_value: Option<TypedValue>,
+    pub tx_instant: Timespec,
+    pub origin: ChangeOrigin,
+}
+
+/// Records that `txs` were made on this device. This is kept in the `toodle_local_txs` table
+/// (see `local`), since a mark in the store would sync, and claim the transactions for every
+/// other device too.
+pub fn mark_local(conn: &Connection, txs: &[Entid]) -> Result<()> {
+    for tx in txs.iter() {
+        conn.execute("INSERT OR IGNORE INTO toodle_local_txs (tx) VALUES (?)", &[tx])?;
+    }
+    Ok(())
+}
+
+/// Starts keeping track of where transactions come from, if this device hasn't already, with
+/// every transaction up to `latest` left unknown.
+pub fn track_origins(conn: &Connection, latest: Entid) -> Result<()> {
+    conn.execute("INSERT INTO toodle_tracked_since (tx) SELECT ? WHERE NOT EXISTS (SELECT 1 FROM toodle_tracked_since)", &[&latest])?;
+    Ok(())
+}
+
+/// Every transaction in the log, oldest first.
+pub fn transactions<Q: Queryable>(queryable: &Q) -> Result<Vec<Entid>> {
+    let query = r#"[:find [?tx ...]
+                    :where
+                    [?tx :db/txInstant _]
+                    :order (asc ?tx)
+    ]"#;
+    Ok(queryable
+        .q_once(query, None)
+        .into_coll_result()?
+        .into_iter()
+        .filter_map(|binding| {
+            let tx: Option<Entity> = binding.val().and_then(|v| v.to_inner());
+            tx.map(|tx| tx.id)
+        })
+        .collect())
+}
+
+/// Stamps the items touched by `txs` with when they were changed, giving any that are new
+/// their creation date too. Returns false if none of them touched an item.
+pub fn stamp_items(in_progress: &InProgress, builder: &mut TermBuilder, txs: &[Entid]) -> Result<bool> {
+    let query = r#"[:find ?e ?instant
+                    :in ?tx
+                    :where
+                    [(tx-data $ ?tx) [[?e _ _ _ _]]]
+                    [?e :todo/uuid _]
+                    [?tx :db/txInstant ?instant]
+    ]"#;
+    let created_query = r#"[:find ?created_at .
+                            :in ?e
+                            :where
+                            [?e :todo/created_at ?created_at]
+    ]"#;
+    let mut stamps: BTreeMap<Entid, (Timespec, Timespec)> = BTreeMap::new();
+    for &tx in txs.iter() {
+        let args = QueryInputs::with_value_sequence(vec![(var!(?tx), TypedValue::Ref(tx))]);
+        for row in in_progress.q_once(query, args).into_rel_result()?.into_iter() {
+            let entity: Option<Entity> = row[0].clone().val().and_then(|v| v.to_inner());
+            let instant: Option<Timespec> = row[1].clone().val().and_then(|v| v.to_inner());
+            if let (Some(entity), Some(instant)) = (entity, instant) {
+                let stamp = stamps.entry(entity.id).or_insert((instant, instant));
+                stamp.0 = stamp.0.min(instant);
+                stamp.1 = stamp.1.max(instant);
+            }
+        }
+    }
+    let created_at = attribute_entid(in_progress, kw!(:todo/created_at))?;
+    let modified_at = attribute_entid(in_progress, kw!(:todo/modified_at))?;
+    for (&entid, &(first, last)) in stamps.iter() {
+        builder.add(KnownEntid(entid), modified_at, last.to_typed_value())?;
+        let args = QueryInputs::with_value_sequence(vec![(var!(?e), TypedValue::Ref(entid))]);
+        if in_progress.q_once(created_query, args).into_scalar_result()?.is_none() {
+            builder.add(KnownEntid(entid), created_at, first.to_typed_value())?;
+        }
+    }
+    Ok(!stamps.is_empty())
+}
+
+/// Reads every change made to the item with `uuid` out of the transaction log, oldest first.
+/// Changes made in the same transaction are ordered by attribute.
+pub fn fetch_item_history(store: &mut Store, uuid: &Uuid) -> Result<Vec<ItemChange>> {
+    let query = r#"[:find ?tx ?instant ?a ?v ?added
+                    :in ?e
+                    :where
+                    [?tx :db/txInstant ?instant]
+                    [(tx-data $ ?tx) [[?e ?a ?v _ ?added]]]
+                    :order (asc ?tx) (asc ?a)
+    ]"#;
+    let local: HashSet<Entid> = {
+        let mut statement = store.local.conn.prepare("SELECT tx FROM toodle_local_txs")?;
+        let txs = statement.query_map(&[], |row| {
+            let tx: Entid = row.get(0);
+            tx
+        })?;
+        let txs = txs.collect::<rusqlite::Result<HashSet<Entid>>>()?;
+        txs
+    };
+    let tracked_since: Option<Entid> = store.local.conn.query_row("SELECT MAX(tx) FROM toodle_tracked_since", &[], |row| row.get(0))?;
+    let in_progress_read = store.begin_read()?;
+    let entid = ::item_entid(&in_progress_read, uuid)?;
+    let args = QueryInputs::with_value_sequence(vec![(var!(?e), TypedValue::Ref(entid.0))]);
+    let rows = in_progress_read.q_once(query, args).into_rel_result()?;
+
+    // Gather each transaction's retractions and assertions for each attribute, so that a
+    // single-valued attribute's new value can be paired up with the value it replaced.
+    let mut groups: Vec<(Entid, Timespec, Entid, Vec<TypedValue>, Vec<TypedValue>)> = vec![];
+    for row in rows.into_iter() {
+        let tx: Option<Entity> = row[0].clone().val().and_then(|v| v.to_inner());
+        let instant: Option<Timespec> = row[1].clone().val().and_then(|v| v.to_inner());
+        let attribute: Option<Entity> = row[2].clone().val().and_then(|v| v.to_inner());
+        let (tx, instant, attribute, value) = match (tx, instant, attribute, row[3].clone().val()) {
+            (Some(tx), Some(instant), Some(attribute), Some(value)) => (tx.id, instant, attribute.id, value),
+            _ => continue,
+        };
+        let added = match row[4].clone().val() {
+            Some(TypedValue::Boolean(added)) => added,
+            _ => continue,
+        };
+        let is_same_group = groups.last().map_or(false, |group| group.0 == tx && group.2 == attribute);
+        if !is_same_group {
+            groups.push((tx, instant, attribute, vec![], vec![]));
+        }
+        if let Some(group) = groups.last_mut() {
+            if added {
+                group.4.push(value);
+            } else {
+                group.3.push(value);
+            }
+        }
+    }
+
+    let implicit = [kw!(:todo/name_fulltext), kw!(:todo/name_trigrams), kw!(:todo/created_at), kw!(:todo/modified_at)];
+    let mut changes: Vec<(Entid, ItemChange)> = vec![];
+    for (tx, instant, attribute, retracted, added) in groups {
+        let keyword = match in_progress_read.get_ident(attribute) {
+            // The fulltext copies of the name only exist for searching, and the stamps only
+            // repeat when the other changes were made.
+            Some(keyword) if !implicit.contains(keyword) => keyword.clone(),
+            _ => continue,
+        };
+        let multival = in_progress_read.attribute_for_entid(attribute).map_or(false, |a| a.multival);
+        let origin = match tracked_since {
+            _ if local.contains(&tx) => ChangeOrigin::Local,
+            Some(since) if tx > since => ChangeOrigin::Sync,
+            _ => ChangeOrigin::Unknown,
+        };
+        let resolve = |value: TypedValue| resolve_ref(&in_progress_read, &keyword, value);
+        let change = |old_value: Option<TypedValue>, new_value: Option<TypedValue>| (tx, ItemChange {
+            attribute: keyword.clone(),
+            old_value: old_value,
+            new_value: new_value,
+            tx_instant: instant,
+            origin: origin,
+        });
+        if !multival && retracted.len() <= 1 && added.len() <= 1 {
+            let old_value = retracted.into_iter().next().map(&resolve);
+            let new_value = added.into_iter().next().map(&resolve);
+            changes.push(change(old_value, new_value));
+        } else {
+            changes.extend(retracted.into_iter().map(|value| change(Some(resolve(value)), None)));
+            changes.extend(added.into_iter().map(|value| change(None, Some(resolve(value)))));
+        }
+    }
+    changes.sort_by_key(|&(tx, ref change)| (tx, change.attribute.to_string()));
+    Ok(changes.into_iter().map(|(_, change)| change).collect())
+}
+
+/// Swaps a reference for something a person can recognise: a label's name, or a list's or an
+/// item's UUID. Anything that's since been deleted is left as it is.
+fn resolve_ref<Q: Queryable + HasSchema>(queryable: &Q, attribute: &Keyword, value: TypedValue) -> TypedValue {
+    let entid = match value {
+        TypedValue::Ref(entid) => entid,
+        _ => return value,
+    };
+    let identifying = if *attribute == kw!(:todo/label) {
+        kw!(:label/name)
+    } else if *attribute == kw!(:todo/list) {
+        kw!(:list/uuid)
+    } else if *attribute == kw!(:todo/parent) {
+        kw!(:todo/uuid)
+    } else {
+        return value;
+    };
+    let identifying = match queryable.get_entid(&identifying) {
+        Some(identifying) => identifying,
+        None => return value,
+    };
+    let query = r#"[:find ?v .
+                    :in ?e ?a
+                    :where
+                    [?e ?a ?v]
+    ]"#;
+    let args = QueryInputs::with_value_sequence(vec![(var!(?e), TypedValue::Ref(entid)),
+                                                     (var!(?a), TypedValue::Ref(identifying.0))]);
+    queryable
+        .q_once(query, args)
+        .into_scalar_result()
+        .ok()
+        .and_then(|binding| binding)
+        .and_then(|binding| binding.val())
+        .unwrap_or(value)
+}
+
@@ -0,0 +1,461 @@
+// Copyright 2018 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! iCalendar (RFC 5545) import and export.
+//!
+//! Each item is a `VTODO`: `UID` is its UUID, `SUMMARY` its name, `DESCRIPTION` its notes,
+//! `CATEGORIES` its labels, `RRULE` its recurrence and `RELATED-TO` its parent. `COMPLETED`
+//! goes along with `STATUS:COMPLETED`. `CREATED` and `LAST-MODIFIED` are written out, but
+//! left to Toodle on import.
+//!
+//! iCalendar times only go down to the second, and are written in UTC. When reading, dates
+//! without a time are taken as midnight UTC, and times with a `TZID` or no zone at all are
+//! taken as UTC.
+//!
+//! Importing merges by `UID` the way `json::import_json` merges by UUID. Other calendars
+//! don't always use UUIDs as UIDs, so those are given a UUID derived from the UID, which stays
+//! the same when the file is imported again. Lists have no equivalent in iCalendar, so the
+//! list an item is in is left alone. Recurrence rules Toodle can't follow are dropped, and so
+//! are `VTODO`s that override a single occurrence of another.
+
+use std::collections::{
+    BTreeMap,
+    HashMap,
+};
+
+use time::{
+    self,
+    Timespec,
+};
+
+use uuid;
+
+use mentat::{
+    Uuid,
+};
+
+use errors::{
+    ErrorKind,
+    Result,
+};
+
+use json::{
+    self,
+    Export,
+    ItemInfo,
+    LabelInfo,
+    EXPORT_VERSION,
+};
+
+use query::{
+    ItemQuery,
+};
+
+use recurrence::{
+    Recurrence,
+};
+
+use Store;
+
+use Toodle;
+
+const DATE_TIME_FORMAT: &str = "%Y%m%dT%H%M%S";
+const DATE_FORMAT: &str = "%Y%m%d";
+
+/// Content lines longer than this many octets are folded.
+const MAX_LINE_LENGTH: usize = 75;
+
+pub fn export_ics(store: &mut Store) -> Result<String> {
+    let stamp = format_time(&time::now_utc().to_timespec())?;
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//Mozilla//Toodle//EN".to_string(),
+    ];
+    for item in store.fetch_items(&ItemQuery::new())?.vec.iter() {
+        lines.push("BEGIN:VTODO".to_string());
+        lines.push(format!("UID:{}", item.uuid.hyphenated()));
+        lines.push(format!("DTSTAMP:{}", stamp));
+        if let Some(ref created_at) = item.created_at {
+            lines.push(format!("CREATED:{}", format_time(created_at)?));
+        }
+        if let Some(ref modified_at) = item.modified_at {
+            lines.push(format!("LAST-MODIFIED:{}", format_time(modified_at)?));
+        }
+        lines.push(format!("SUMMARY:{}", escape(&item.name)));
+        if let Some(ref notes) = item.notes {
+            lines.push(format!("DESCRIPTION:{}", escape(notes)));
+        }
+        if let Some(ref due_date) = item.due_date {
+            lines.push(format!("DUE:{}", format_time(due_date)?));
+        }
+        match item.completion_date {
+            Some(ref completion_date) => {
+                lines.push(format!("COMPLETED:{}", format_time(completion_date)?));
+                lines.push("STATUS:COMPLETED".to_string());
+            },
+            None => lines.push("STATUS:NEEDS-ACTION".to_string()),
+        }
+        if !item.labels.is_empty() {
+            let categories: Vec<String> = item.labels.iter().map(|label| escape(&label.name)).collect();
+            lines.push(format!("CATEGORIES:{}", categories.join(",")));
+        }
+        if let Some(ref recurrence) = item.recurrence {
+            lines.push(format!("RRULE:{}", recurrence));
+        }
+        if let Some(parent) = item.parent {
+            lines.push(format!("RELATED-TO;RELTYPE=PARENT:{}", parent.hyphenated()));
+        }
+        lines.push("END:VTODO".to_string());
+    }
+    lines.push("END:VCALENDAR".to_string());
+
+    let mut ics = String::new();
+    for line in lines {
+        ics.push_str(&fold(&line));
+        ics.push_str("\r\n");
+    }
+    Ok(ics)
+}
+
+pub fn import_ics(store: &mut Store, ics: &str) -> Result<()> {
+    let mut todos: HashMap<Uuid, ItemInfo> = HashMap::new();
+    let mut parents = vec![];
+    for properties in todo_components(ics)? {
+        if properties.iter().any(|property| property.name == "RECURRENCE-ID") {
+            continue;
+        }
+        let (uuid, info, parent) = item_info(&properties)?;
+        if let Some(parent) = parent {
+            parents.push((uuid, parent));
+        }
+        todos.insert(uuid, info);
+    }
+    // A parent that's in neither the file nor the store was most likely left out of an
+    // export from elsewhere, so the item is imported without it.
+    for (uuid, parent) in parents {
+        let known = todos.contains_key(&parent) || store.fetch_item(&parent)?.is_some();
+        if known {
+            if let Some(info) = todos.get_mut(&uuid) {
+                info.parent = Some(parent.hyphenated().to_string());
+            }
+        }
+    }
+    let export = Export {
+        version: EXPORT_VERSION,
+        labels: vec![],
+        lists: BTreeMap::new(),
+        todos: todos.into_iter().map(|(uuid, info)| (uuid.hyphenated().to_string(), info)).collect(),
+    };
+    json::import(store, export, &[kw!(:todo/list)])
+}
+
+/// A content line: `NAME;PARAM=value:value`.
+#[derive(Debug, PartialEq)]
+struct Property {
+    name: String,
+    params: Vec<(String, String)>,
+    value: String,
+}
+
+impl Property {
+    fn param(&self, name: &str) -> Option<&str> {
+        self.params.iter().find(|&&(ref n, _)| n == name).map(|&(_, ref v)| &v[..])
+    }
+}
+
+/// The properties of each `VTODO` in `ics`, leaving out those of any components inside it,
+/// such as alarms.
+fn todo_components(ics: &str) -> Result<Vec<Vec<Property>>> {
+    let mut todos = vec![];
+    let mut components: Vec<String> = vec![];
+    let mut properties = vec![];
+    for line in unfold(ics) {
+        let property = parse_property(&line)?;
+        match &property.name[..] {
+            "BEGIN" => components.push(property.value.to_uppercase()),
+            "END" => {
+                if components.pop().as_ref().map(|c| &c[..]) == Some("VTODO") {
+                    todos.push(properties);
+                    properties = vec![];
+                }
+            },
+            _ => {
+                if components.last().map(|c| &c[..]) == Some("VTODO") {
+                    properties.push(property);
+                }
+            },
+        }
+    }
+    if !components.is_empty() {
+        bail!(ErrorKind::InvalidCalendar(format!("{} is never ended", components.join(", "))));
+    }
+    Ok(todos)
+}
+
+/// The UUID of a `VTODO`, its item, and its parent's UUID.
+fn item_info(properties: &[Property]) -> Result<(Uuid, ItemInfo, Option<Uuid>)> {
+    let mut uid = None;
+    let mut info = ItemInfo {
+        uuid: None,
+        name: String::new(),
+        notes: None,
+        due_date: None,
+        completion_date: None,
+        recurrence: None,
+        list: None,
+        parent: None,
+        position: None,
+        created_at: None,
+        modified_at: None,
+        child_count: 0,
+        labels: Some(vec![]),
+    };
+    let mut parent = None;
+    let mut completed = false;
+    let mut stamp = None;
+    for property in properties {
+        match &property.name[..] {
+            "UID" => uid = Some(uuid_for_uid(&property.value)),
+            "SUMMARY" => info.name = unescape(&property.value),
+            "DESCRIPTION" => info.notes = Some(unescape(&property.value)),
+            "DUE" => info.due_date = Some(json::to_millis(&parse_time(property)?)),
+            "COMPLETED" => info.completion_date = Some(json::to_millis(&parse_time(property)?)),
+            "DTSTAMP" => stamp = Some(parse_time(property)?),
+            "STATUS" => completed = property.value.eq_ignore_ascii_case("COMPLETED"),
+            "CATEGORIES" => {
+                if let Some(ref mut labels) = info.labels {
+                    for name in split_list(&property.value) {
+                        if !name.is_empty() && !labels.iter().any(|label: &LabelInfo| label.name == name) {
+                            labels.push(LabelInfo { name: name, color: None });
+                        }
+                    }
+                }
+            },
+            "RRULE" => {
+                if property.value.parse::<Recurrence>().is_ok() {
+                    info.recurrence = Some(property.value.clone());
+                }
+            },
+            "RELATED-TO" => {
+                let reltype = property.param("RELTYPE").unwrap_or("PARENT");
+                if reltype.eq_ignore_ascii_case("PARENT") {
+                    parent = Some(uuid_for_uid(&property.value));
+                }
+            },
+            _ => {},
+        }
+    }
+    // Some calendars only give the status.
+    if completed && info.completion_date.is_none() {
+        let completion_date = stamp.unwrap_or_else(|| time::now_utc().to_timespec());
+        info.completion_date = Some(json::to_millis(&completion_date));
+    }
+    let uuid = uid.ok_or_else(|| ErrorKind::InvalidCalendar("VTODO without a UID".to_string()))?;
+    Ok((uuid, info, parent))
+}
+
+fn uuid_for_uid(uid: &str) -> Uuid {
+    Uuid::parse_str(uid).unwrap_or_else(|_| Uuid::new_v5(&uuid::NAMESPACE_URL, uid))
+}
+
+fn format_time(time: &Timespec) -> Result<String> {
+    let formatted = time::strftime(DATE_TIME_FORMAT, &time::at_utc(*time))
+        .map_err(|e| ErrorKind::InvalidCalendar(e.to_string()))?;
+    Ok(format!("{}Z", formatted))
+}
+
+fn parse_time(property: &Property) -> Result<Timespec> {
+    let value = property.value.trim_right_matches('Z');
+    let format = if property.param("VALUE").map_or(false, |v| v.eq_ignore_ascii_case("DATE")) || !value.contains('T') {
+        DATE_FORMAT
+    } else {
+        DATE_TIME_FORMAT
+    };
+    let tm = time::strptime(value, format)
+        .map_err(|_| ErrorKind::InvalidCalendar(format!("invalid {} {}", property.name, property.value)))?;
+    Ok(tm.to_timespec())
+}
+
+/// Joins folded lines back together, dropping empty ones.
+fn unfold(ics: &str) -> Vec<String> {
+    let mut lines: Vec<String> = vec![];
+    for line in ics.split('\n') {
+        let line = line.trim_right_matches('\r');
+        if line.starts_with(' ') || line.starts_with('\t') {
+            if let Some(last) = lines.last_mut() {
+                last.push_str(&line[1..]);
+                continue;
+            }
+        }
+        if !line.is_empty() {
+            lines.push(line.to_string());
+        }
+    }
+    lines
+}
+
+/// Breaks a content line into lines of at most `MAX_LINE_LENGTH` octets, each after the
+/// first starting with a space. Characters aren't split.
+fn fold(line: &str) -> String {
+    let mut folded = String::new();
+    let mut length = 0;
+    for c in line.chars() {
+        if length + c.len_utf8() > MAX_LINE_LENGTH {
+            folded.push_str("\r\n ");
+            length = 1;
+        }
+        folded.push(c);
+        length += c.len_utf8();
+    }
+    folded
+}
+
+fn parse_property(line: &str) -> Result<Property> {
+    let invalid = || ErrorKind::InvalidCalendar(format!("invalid line {}", line));
+    // The value starts after the first colon that isn't in a quoted parameter value.
+    let mut quoted = false;
+    let mut colon = None;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ':' if !quoted => {
+                colon = Some(i);
+                break;
+            },
+            _ => {},
+        }
+    }
+    let colon = colon.ok_or_else(invalid)?;
+    let (head, value) = (&line[..colon], &line[colon + 1..]);
+
+    let mut parts = vec![];
+    let mut part = String::new();
+    let mut quoted = false;
+    for c in head.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            ';' if !quoted => parts.push(::std::mem::replace(&mut part, String::new())),
+            _ => part.push(c),
+        }
+    }
+    parts.push(part);
+
+    let mut parts = parts.into_iter();
+    let name = parts.next().map(|name| name.to_uppercase()).unwrap_or_default();
+    if name.is_empty() {
+        bail!(invalid());
+    }
+    let mut params = vec![];
+    for param in parts {
+        let mut pair = param.splitn(2, '=');
+        match (pair.next(), pair.next()) {
+            (Some(n), Some(v)) => params.push((n.to_uppercase(), v.to_string())),
+            _ => bail!(invalid()),
+        }
+    }
+    Ok(Property {
+        name: name,
+        params: params,
+        value: value.to_string(),
+    })
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            ';' => escaped.push_str("\\;"),
+            ',' => escaped.push_str("\\,"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {},
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('n') | Some('N') => unescaped.push('\n'),
+                Some(c) => unescaped.push(c),
+                None => {},
+            },
+            _ => unescaped.push(c),
+        }
+    }
+    unescaped
+}
+
+/// Splits a list of text values, such as `CATEGORIES`, at its unescaped commas.
+fn split_list(text: &str) -> Vec<String> {
+    let mut values = vec![];
+    let mut value = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                value.push(c);
+                if let Some(c) = chars.next() {
+                    value.push(c);
+                }
+            },
+            ',' => values.push(unescape(&::std::mem::replace(&mut value, String::new()))),
+            _ => value.push(c),
+        }
+    }
+    values.push(unescape(&value));
+    values
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        escape,
+        fold,
+        parse_property,
+        split_list,
+        unescape,
+        unfold,
+        Property,
+    };
+
+    #[test]
+    fn test_escaping() {
+        let text = "Milk, eggs; and \\ a\nnew line";
+        assert_eq!(escape(text), "Milk\\, eggs\\; and \\\\ a\\nnew line");
+        assert_eq!(unescape(&escape(text)), text);
+        assert_eq!(split_list("work,home\\, garden,"), vec!["work", "home, garden", ""]);
+    }
+
+    #[test]
+    fn test_folding() {
+        let line = format!("SUMMARY:{}", "é".repeat(60));
+        let folded = fold(&line);
+        assert!(folded.split("\r\n").all(|l| l.len() <= 75));
+        assert_eq!(unfold(&format!("BEGIN:VTODO\r\n{}\r\n", folded)), vec!["BEGIN:VTODO".to_string(), line]);
+    }
+
+    #[test]
+    fn test_parse_property() {
+        assert_eq!(parse_property("due;VALUE=DATE;X-NOTE=\"a:b;c\":20180131").expect("expected a property"), Property {
+            name: "DUE".to_string(),
+            params: vec![("VALUE".to_string(), "DATE".to_string()), ("X-NOTE".to_string(), "a:b;c".to_string())],
+            value: "20180131".to_string(),
+        });
+        assert!(parse_property("no colon").is_err());
+        assert!(parse_property(":value").is_err());
+    }
+}
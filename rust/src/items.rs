@@ -23,6 +23,10 @@ use labels::{
     Label,
 };
 
+use recurrence::{
+    Recurrence,
+};
+
 use utils::{
     Entity,
 };
@@ -32,11 +36,31 @@ pub struct Item {
     pub id: Option<Entity>,
     pub uuid: Uuid,
     pub name: String,
+    pub notes: Option<String>,
     pub due_date: Option<Timespec>,
     pub completion_date: Option<Timespec>,
+    /// When the item was put in the trash, if it's there.
+    pub deleted_at: Option<Timespec>,
+    /// Where the item goes when items are in the order they were put in by hand.
+    pub position: Option<String>,
+    /// When the item was first transacted. Toodle stamps this, and `modified_at`, itself.
+    pub created_at: Option<Timespec>,
+    /// When the item was last changed.
+    pub modified_at: Option<Timespec>,
+    pub recurrence: Option<Recurrence>,
+    pub list: Option<Uuid>,
+    pub parent: Option<Uuid>,
+    pub child_count: usize,
     pub labels: Vec<Label>,
 }
 
+/// An item along with its subtasks, as returned by `Toodle::fetch_item_tree`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ItemNode {
+    pub item: Item,
+    pub children: Vec<ItemNode>,
+}
+
 #[derive(Debug)]
 pub struct Items {
     pub vec: Vec<Item>
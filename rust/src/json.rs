@@ -0,0 +1,727 @@
+// Copyright 2018 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! The JSON representation of items, labels and lists, shared by every platform, and the
+//! export format built on it.
+//!
+//! An export looks like this, with times in milliseconds since the epoch:
+//!
+//! ```json
+//! {
+//!   "version": 1,
+//!   "labels": [{"name": "work", "color": "#00ff00"}],
+//!   "lists": {
+//!     "<list uuid>": {"name": "Inbox", "color": "#808080", "sortOrder": 0}
+//!   },
+//!   "todos": {
+//!     "<todo uuid>": {
+//!       "name": "Write report",
+//!       "notes": "...",
+//!       "dueDate": 1517389200000.0,
+//!       "completionDate": 1517392800000.0,
+//!       "recurrence": "FREQ=WEEKLY;BYDAY=MO",
+//!       "list": "<list uuid>",
+//!       "parent": "<todo uuid>",
+//!       "position": "a0",
+//!       "createdAt": 1517385600000.0,
+//!       "modifiedAt": 1517392800000.0,
+//!       "labels": [{"name": "work"}]
+//!     }
+//!   }
+//! }
+//! ```
+//!
+//! Importing merges lists and todos by UUID and labels by name, so importing the same
+//! document twice changes nothing the second time. A todo that's in the document ends up
+//! exactly as described; anything that isn't is left alone. New todos without a position
+//! go at the end. A todo keeps the creation date it's given, but importing it is a change
+//! like any other, so `modifiedAt` is only ever written out.
+
+use std::collections::{
+    BTreeMap,
+    HashMap,
+};
+
+use serde_json;
+
+use time::Timespec;
+
+use mentat::{
+    Entid,
+    HasSchema,
+    InProgress,
+    IntoResult,
+    Keyword,
+    Queryable,
+    QueryInputs,
+    TypedValue,
+    Uuid,
+};
+
+use mentat::entity_builder::{
+    BuildTerms,
+    InProgressBuilder,
+    TempIdHandle,
+};
+
+use mentat_core::{
+    KnownEntid,
+};
+
+use errors::{
+    ErrorKind,
+    Result,
+};
+
+use items::{
+    Item,
+};
+
+use labels::{
+    Label,
+};
+
+use lists::{
+    List,
+};
+
+use position;
+
+use query::{
+    ItemQuery,
+};
+
+use search;
+
+use utils::{
+    attribute_entid,
+    Entity,
+    ToInner,
+    ToTypedValue,
+};
+
+use Store;
+
+use Toodle;
+
+/// The version of the export format written by `export_json`. Documents from later versions
+/// are refused.
+pub const EXPORT_VERSION: i64 = 1;
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct LabelInfo {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+}
+
+impl From<Label> for LabelInfo {
+    fn from(label: Label) -> Self {
+        LabelInfo {
+            name: label.name.clone(),
+            color: Some(label.color.clone()),
+        }
+    }
+}
+
+impl Into<Label> for LabelInfo {
+    fn into(self) -> Label {
+        Label {
+            id: None,
+            name: self.name.clone(),
+            color: self.color.clone().unwrap_or_default(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ListInfo {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uuid: Option<String>,
+
+    pub name: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+
+    #[serde(rename = "sortOrder", skip_serializing_if = "Option::is_none")]
+    pub sort_order: Option<i64>,
+}
+
+impl From<List> for ListInfo {
+    fn from(list: List) -> Self {
+        ListInfo {
+            uuid: Some(list.uuid.hyphenated().to_string()),
+            name: list.name.clone(),
+            color: Some(list.color.clone()),
+            sort_order: Some(list.sort_order),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ItemInfo {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uuid: Option<String>,
+
+    pub name: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+
+    #[serde(rename = "dueDate", skip_serializing_if = "Option::is_none")]
+    pub due_date: Option<f64>,
+
+    #[serde(rename = "completionDate", skip_serializing_if = "Option::is_none")]
+    pub completion_date: Option<f64>,
+
+    /// An RRULE, such as `FREQ=WEEKLY;BYDAY=MO`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recurrence: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub list: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent: Option<String>,
+
+    /// Where the todo goes in the order todos are put in by hand. See `position`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub position: Option<String>,
+
+    #[serde(rename = "createdAt", skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<f64>,
+
+    #[serde(rename = "modifiedAt", skip_serializing_if = "Option::is_none")]
+    pub modified_at: Option<f64>,
+
+    #[serde(rename = "childCount", default)]
+    pub child_count: usize,
+
+    pub labels: Option<Vec<LabelInfo>>,
+}
+
+impl From<Item> for ItemInfo {
+    fn from(item: Item) -> Self {
+        let label_infos = Some(item.labels
+                                   .clone()
+                                   .into_iter()
+                                   .map(|label| label.into())
+                                   .collect());
+        ItemInfo {
+            uuid: Some(item.uuid.hyphenated().to_string()),
+            name: item.name.clone(),
+            notes: item.notes.clone(),
+            due_date: item.due_date.as_ref().map(to_millis),
+            completion_date: item.completion_date.as_ref().map(to_millis),
+            recurrence: item.recurrence.as_ref().map(|recurrence| recurrence.to_string()),
+            list: item.list.map(|list| list.hyphenated().to_string()),
+            parent: item.parent.map(|parent| parent.hyphenated().to_string()),
+            position: item.position.clone(),
+            created_at: item.created_at.as_ref().map(to_millis),
+            modified_at: item.modified_at.as_ref().map(to_millis),
+            child_count: item.child_count,
+            labels: label_infos,
+        }
+    }
+}
+
+impl Into<Item> for ItemInfo {
+    fn into(self) -> Item {
+        let due_date = self.due_date.map(from_millis);
+        let completion_date = self.completion_date.map(from_millis);
+        let labels = match self.labels {
+            Some(labels) => labels.into_iter().map(|label| label.into()).collect(),
+            None => Vec::new(),
+        };
+        Item {
+            id: None,
+            uuid: self.uuid
+                .clone()
+                .and_then(|uuid| Uuid::parse_str(&uuid).ok())
+                .unwrap_or_else(|| Uuid::nil()),
+            name: self.name.clone(),
+            notes: self.notes.clone(),
+            due_date: due_date,
+            completion_date: completion_date,
+            deleted_at: None,
+            position: self.position.clone(),
+            created_at: self.created_at.map(from_millis),
+            modified_at: self.modified_at.map(from_millis),
+            recurrence: self.recurrence
+                .clone()
+                .and_then(|recurrence| recurrence.parse().ok()),
+            list: self.list
+                .clone()
+                .and_then(|list| Uuid::parse_str(&list).ok()),
+            parent: self.parent
+                .clone()
+                .and_then(|parent| Uuid::parse_str(&parent).ok()),
+            child_count: self.child_count,
+            labels: labels,
+        }
+    }
+}
+
+/// Times are given as milliseconds since the epoch, like JavaScript's `Date`, with the
+/// microseconds Toodle stores them to as the fraction.
+pub fn to_millis(time: &Timespec) -> f64 {
+    (time.sec * 1_000_000 + i64::from(time.nsec / 1_000)) as f64 / 1000.0
+}
+
+pub fn from_millis(millis: f64) -> Timespec {
+    let micros = (millis * 1000.0).round() as i64;
+    let (sec, micros) = (micros / 1_000_000, micros % 1_000_000);
+    if micros < 0 {
+        Timespec::new(sec - 1, ((micros + 1_000_000) * 1_000) as i32)
+    } else {
+        Timespec::new(sec, (micros * 1_000) as i32)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Export {
+    pub version: i64,
+
+    #[serde(default)]
+    pub labels: Vec<LabelInfo>,
+
+    /// Lists by UUID.
+    #[serde(default)]
+    pub lists: BTreeMap<String, ListInfo>,
+
+    /// Todos by UUID.
+    #[serde(default)]
+    pub todos: BTreeMap<String, ItemInfo>,
+}
+
+pub fn export_json(store: &mut Store) -> Result<String> {
+    let labels = store.fetch_labels()?.into_iter().map(|label| label.into()).collect();
+    let lists = store.fetch_lists()?
+        .into_iter()
+        .map(|list| {
+            let mut info: ListInfo = list.into();
+            (info.uuid.take().unwrap_or_default(), info)
+        })
+        .collect();
+    let todos = store.fetch_items(&ItemQuery::new())?
+        .vec
+        .into_iter()
+        .map(|item| {
+            let mut info: ItemInfo = item.into();
+            info.child_count = 0;
+            (info.uuid.take().unwrap_or_default(), info)
+        })
+        .collect();
+    let export = Export {
+        version: EXPORT_VERSION,
+        labels: labels,
+        lists: lists,
+        todos: todos,
+    };
+    serde_json::to_string_pretty(&export).map_err(|e| ErrorKind::InvalidExport(e.to_string()).into())
+}
+
+/// Something being imported: either an entity that's already in the store, or a new one.
+#[derive(Clone)]
+enum Target {
+    Existing(KnownEntid),
+    New(TempIdHandle),
+}
+
+/// A value to give an attribute. References to new entities can't be a `TypedValue` until
+/// they've been transacted.
+enum Value {
+    Typed(TypedValue),
+    New(TempIdHandle),
+}
+
+impl Value {
+    fn typed(&self) -> Option<&TypedValue> {
+        match *self {
+            Value::Typed(ref v) => Some(v),
+            Value::New(_) => None,
+        }
+    }
+}
+
+impl<'a> From<&'a Target> for Value {
+    fn from(target: &'a Target) -> Value {
+        match *target {
+            Target::Existing(entid) => Value::Typed(TypedValue::Ref(entid.0)),
+            Target::New(ref tempid) => Value::New(tempid.clone()),
+        }
+    }
+}
+
+pub fn import_json(store: &mut Store, json: &str) -> Result<()> {
+    let export: Export = serde_json::from_str(json).map_err(|e| ErrorKind::InvalidExport(e.to_string()))?;
+    if export.version > EXPORT_VERSION {
+        bail!(ErrorKind::UnsupportedExportVersion(export.version));
+    }
+    import(store, export, &[])
+}
+
+/// Merges `export` into the store, as `import_json` does. The todo attributes in `preserved`
+/// are left as they are on todos that are already in the store; this is for formats that
+/// have no way of saying what they should be.
+pub fn import(store: &mut Store, export: Export, preserved: &[Keyword]) -> Result<()> {
+    let parse_uuid = |uuid: &str| Uuid::parse_str(uuid).map_err(|_| ErrorKind::InvalidExport(format!("invalid UUID {}", uuid)));
+
+    // Labels named by todos but missing from the label list are created without a color.
+    let mut labels: Vec<(String, Option<String>)> = export.labels
+        .into_iter()
+        .map(|label| (label.name, label.color))
+        .collect();
+    for item in export.todos.values() {
+        for label in item.labels.iter().flat_map(|labels| labels.iter()) {
+            if !labels.iter().any(|&(ref name, _)| *name == label.name) {
+                labels.push((label.name.clone(), label.color.clone()));
+            }
+        }
+    }
+    let mut lists = vec![];
+    for (uuid, list) in export.lists {
+        lists.push((parse_uuid(&uuid)?, list));
+    }
+    let mut items = vec![];
+    for (uuid, item) in export.todos {
+        let recurrence = match item.recurrence {
+            Some(ref rule) => Some(rule.parse().map_err(|_| ErrorKind::InvalidRecurrence(rule.clone()))?),
+            None => None,
+        };
+        let list = match item.list {
+            Some(ref list) => Some(parse_uuid(list)?),
+            None => None,
+        };
+        let parent = match item.parent {
+            Some(ref parent) => Some(parse_uuid(parent)?),
+            None => None,
+        };
+        if let Some(ref position) = item.position {
+            if !position::is_valid(position) {
+                bail!(ErrorKind::InvalidExport(format!("invalid position {}", position)));
+            }
+        }
+        let label_names: Vec<String> = item.labels.iter().flat_map(|labels| labels.iter()).map(|label| label.name.clone()).collect();
+        let mut item: Item = item.into();
+        item.uuid = parse_uuid(&uuid)?;
+        item.recurrence = recurrence;
+        item.list = list;
+        item.parent = parent;
+        items.push((item, label_names));
+    }
+
+    let in_progress = store.mentat.begin_transaction()?;
+    let attributes = Attributes::new(&in_progress)?;
+    let mut preserved_attributes = vec![];
+    for attribute in preserved {
+        preserved_attributes.push(attribute_entid(&in_progress, attribute.clone())?);
+    }
+
+    // Everything that's already in the store has to be looked up before building the
+    // transaction, along with what it currently says, so that it can be brought up to date.
+    let mut current: HashMap<Entid, Vec<(KnownEntid, TypedValue)>> = HashMap::new();
+    let mut existing_labels = vec![];
+    let mut existing_lists = vec![];
+    let mut existing_items = vec![];
+    {
+        let mut resolve = |in_progress: &InProgress, attribute: Keyword, value: TypedValue| -> Result<Option<KnownEntid>> {
+            let entid = existing_entid(in_progress, &attribute, value)?;
+            if let Some(entid) = entid {
+                current.insert(entid.0, ::entity_datoms(in_progress, entid)?);
+            }
+            Ok(entid)
+        };
+        for &(ref name, _) in labels.iter() {
+            existing_labels.push(resolve(&in_progress, kw!(:label/name), name.to_typed_value())?);
+        }
+        for &(ref uuid, _) in lists.iter() {
+            existing_lists.push(resolve(&in_progress, kw!(:list/uuid), uuid.to_typed_value())?);
+        }
+        for &(ref item, _) in items.iter() {
+            existing_items.push(resolve(&in_progress, kw!(:todo/uuid), item.uuid.to_typed_value())?);
+        }
+    }
+    // Todos can also be put in lists, or under parents, that are in the store but not in
+    // the document.
+    let mut outside: HashMap<Uuid, KnownEntid> = HashMap::new();
+    for &(ref item, _) in items.iter() {
+        if let Some(list) = item.list {
+            if !lists.iter().any(|&(uuid, _)| uuid == list) {
+                outside.insert(list, ::list_entid(&in_progress, &list)?);
+            }
+        }
+        if let Some(parent) = item.parent {
+            if !items.iter().any(|&(ref other, _)| other.uuid == parent) {
+                outside.insert(parent, ::item_entid(&in_progress, &parent)?);
+            }
+        }
+    }
+
+    // New todos without a position go after everything, including the todos being imported.
+    let mut last_position = ::item_positions(&in_progress)?
+        .into_iter()
+        .map(|(_, position)| position)
+        .chain(items.iter().filter_map(|&(ref item, _)| item.position.clone()))
+        .max();
+
+    let mut builder = in_progress.builder();
+    let target = |builder: &mut InProgressBuilder, existing: Option<KnownEntid>, name: String| match existing {
+        Some(entid) => Target::Existing(entid),
+        None => Target::New(builder.named_tempid(name)),
+    };
+    let label_targets: HashMap<String, Target> = labels
+        .iter()
+        .zip(existing_labels)
+        .map(|(&(ref name, _), existing)| (name.clone(), target(&mut builder, existing, format!("label {}", name))))
+        .collect();
+    let mut uuid_targets: HashMap<Uuid, Target> = outside
+        .into_iter()
+        .map(|(uuid, entid)| (uuid, Target::Existing(entid)))
+        .collect();
+    for (&(uuid, _), existing) in lists.iter().zip(existing_lists) {
+        let list = target(&mut builder, existing, format!("list {}", uuid));
+        uuid_targets.insert(uuid, list);
+    }
+    for (&(ref item, _), existing) in items.iter().zip(existing_items) {
+        let todo = target(&mut builder, existing, format!("todo {}", item.uuid));
+        uuid_targets.insert(item.uuid, todo);
+    }
+    let lookup = |uuid: &Uuid| -> Result<Value> {
+        uuid_targets.get(uuid)
+                    .map(Value::from)
+                    .ok_or_else(|| ErrorKind::ItemNotFound(uuid.hyphenated().to_string()).into())
+    };
+
+    for &(ref name, ref color) in labels.iter() {
+        let label = &label_targets[name];
+        let mut datoms = vec![(attributes.label_name, Value::Typed(name.to_typed_value()))];
+        // Labels are only fetched along with their color, so new ones always need one.
+        let color = match (color, label) {
+            (&Some(ref color), _) => Some(color.clone()),
+            (&None, &Target::New(_)) => Some(String::new()),
+            (&None, &Target::Existing(_)) => None,
+        };
+        if let Some(color) = color {
+            datoms.push((attributes.label_color, Value::Typed(color.to_typed_value())));
+        }
+        let managed: Vec<KnownEntid> = datoms.iter().map(|&(a, _)| a).collect();
+        merge(&mut builder, label, &current, &managed, datoms)?;
+    }
+
+    for &(uuid, ref list) in lists.iter() {
+        let mut datoms = vec![
+            (attributes.list_uuid, Value::Typed(uuid.to_typed_value())),
+            (attributes.list_name, Value::Typed(list.name.to_typed_value())),
+        ];
+        if let Some(ref color) = list.color {
+            datoms.push((attributes.list_color, Value::Typed(color.to_typed_value())));
+        }
+        if let Some(sort_order) = list.sort_order {
+            datoms.push((attributes.list_sort_order, Value::Typed(sort_order.to_typed_value())));
+        }
+        let managed: Vec<KnownEntid> = datoms.iter().map(|&(a, _)| a).collect();
+        merge(&mut builder, &uuid_targets[&uuid], &current, &managed, datoms)?;
+    }
+
+    for &(ref item, ref label_names) in items.iter() {
+        let mut datoms = vec![
+            (attributes.todo_uuid, Value::Typed(item.uuid.to_typed_value())),
+            (attributes.todo_name, Value::Typed(item.name.to_typed_value())),
+            (attributes.todo_name_fulltext, Value::Typed(item.name.to_typed_value())),
+            (attributes.todo_name_trigrams, Value::Typed(search::trigrams(&item.name).to_typed_value())),
+        ];
+        if let Some(ref notes) = item.notes {
+            datoms.push((attributes.todo_notes, Value::Typed(notes.to_typed_value())));
+        }
+        if let Some(due_date) = item.due_date {
+            datoms.push((attributes.todo_due_date, Value::Typed(due_date.to_typed_value())));
+        }
+        if let Some(completion_date) = item.completion_date {
+            datoms.push((attributes.todo_completion_date, Value::Typed(completion_date.to_typed_value())));
+        }
+        if let Some(ref recurrence) = item.recurrence {
+            datoms.push((attributes.todo_recurrence, Value::Typed(recurrence.to_string().to_typed_value())));
+        }
+        if let Some(ref list) = item.list {
+            datoms.push((attributes.todo_list, lookup(list)?));
+        }
+        if let Some(ref parent) = item.parent {
+            datoms.push((attributes.todo_parent, lookup(parent)?));
+        }
+        for name in label_names {
+            datoms.push((attributes.todo_label, Value::from(&label_targets[name])));
+        }
+        let todo = &uuid_targets[&item.uuid];
+        let position = match (&item.position, todo) {
+            (&Some(ref position), _) => Some(position.clone()),
+            (&None, &Target::New(_)) => {
+                let position = position::between(last_position.as_ref().map(|p| p.as_str()), None);
+                last_position = Some(position.clone());
+                Some(position)
+            },
+            (&None, &Target::Existing(_)) => None,
+        };
+        if let Some(position) = position {
+            datoms.push((attributes.todo_position, Value::Typed(position.to_typed_value())));
+        }
+        if let Some(created_at) = item.created_at {
+            datoms.push((attributes.todo_created_at, Value::Typed(created_at.to_typed_value())));
+        }
+        let mut managed: Vec<KnownEntid> = [
+            attributes.todo_name,
+            attributes.todo_name_fulltext,
+            attributes.todo_name_trigrams,
+            attributes.todo_notes,
+            attributes.todo_due_date,
+            attributes.todo_completion_date,
+            attributes.todo_recurrence,
+            attributes.todo_list,
+            attributes.todo_parent,
+            attributes.todo_label,
+        ].iter().cloned().filter(|a| !preserved_attributes.contains(a)).collect();
+        // Todos that are already in the store keep their position unless they're given one.
+        if item.position.is_some() {
+            managed.push(attributes.todo_position);
+        }
+        // New todos without a creation date are stamped with the import's.
+        if item.created_at.is_some() {
+            managed.push(attributes.todo_created_at);
+        }
+        merge(&mut builder, todo, &current, &managed, datoms)?;
+    }
+
+    ::undo::commit(&mut store.local, builder).and(Ok(()))
+}
+
+/// Brings `target` into line with `datoms`: anything it has for one of the `managed`
+/// attributes that isn't in `datoms` is retracted, and anything in `datoms` it doesn't have
+/// yet is added.
+fn merge(builder: &mut InProgressBuilder,
+         target: &Target,
+         current: &HashMap<Entid, Vec<(KnownEntid, TypedValue)>>,
+         managed: &[KnownEntid],
+         datoms: Vec<(KnownEntid, Value)>) -> Result<()> {
+    let entid = match *target {
+        Target::Existing(entid) => entid,
+        Target::New(ref tempid) => {
+            for (a, v) in datoms {
+                match v {
+                    Value::Typed(v) => builder.add(tempid.clone(), a, v)?,
+                    Value::New(v) => builder.add(tempid.clone(), a, v)?,
+                }
+            }
+            return Ok(());
+        },
+    };
+    let existing: &[(KnownEntid, TypedValue)] = current.get(&entid.0).map(|datoms| &datoms[..]).unwrap_or(&[]);
+    for &(a, ref v) in existing.iter() {
+        let wanted = datoms.iter().any(|&(wanted_a, ref wanted_v)| wanted_a == a && wanted_v.typed() == Some(v));
+        if managed.contains(&a) && !wanted {
+            builder.retract(entid, a, v.clone())?;
+        }
+    }
+    for (a, v) in datoms {
+        match v {
+            Value::Typed(v) => {
+                if !existing.iter().any(|&(existing_a, ref existing_v)| existing_a == a && *existing_v == v) {
+                    builder.add(entid, a, v)?;
+                }
+            },
+            Value::New(v) => builder.add(entid, a, v)?,
+        }
+    }
+    Ok(())
+}
+
+fn existing_entid<Q: Queryable + HasSchema>(queryable: &Q, attribute: &Keyword, value: TypedValue) -> Result<Option<KnownEntid>> {
+    let attribute = attribute_entid(queryable, attribute.clone())?;
+    let query = r#"[:find ?e .
+                    :in ?a ?v
+                    :where
+                    [?e ?a ?v]
+    ]"#;
+    let args = QueryInputs::with_value_sequence(vec![(var!(?a), TypedValue::Ref(attribute.0)),
+                                                     (var!(?v), value)]);
+    let entity: Option<Entity> = queryable
+        .q_once(query, args)
+        .into_scalar_result()?
+        .and_then(|binding| binding.val())
+        .and_then(|v| v.to_inner());
+    Ok(entity.map(|e| KnownEntid(e.id)))
+}
+
+struct Attributes {
+    label_name: KnownEntid,
+    label_color: KnownEntid,
+    list_uuid: KnownEntid,
+    list_name: KnownEntid,
+    list_color: KnownEntid,
+    list_sort_order: KnownEntid,
+    todo_uuid: KnownEntid,
+    todo_name: KnownEntid,
+    todo_name_fulltext: KnownEntid,
+    todo_name_trigrams: KnownEntid,
+    todo_notes: KnownEntid,
+    todo_due_date: KnownEntid,
+    todo_completion_date: KnownEntid,
+    todo_recurrence: KnownEntid,
+    todo_list: KnownEntid,
+    todo_parent: KnownEntid,
+    todo_position: KnownEntid,
+    todo_created_at: KnownEntid,
+    todo_label: KnownEntid,
+}
+
+impl Attributes {
+    fn new<Q: HasSchema>(schema: &Q) -> Result<Attributes> {
+        let get = |keyword: Keyword| attribute_entid(schema, keyword);
+        Ok(Attributes {
+            label_name: get(kw!(:label/name))?,
+            label_color: get(kw!(:label/color))?,
+            list_uuid: get(kw!(:list/uuid))?,
+            list_name: get(kw!(:list/name))?,
+            list_color: get(kw!(:list/color))?,
+            list_sort_order: get(kw!(:list/sort_order))?,
+            todo_uuid: get(kw!(:todo/uuid))?,
+            todo_name: get(kw!(:todo/name))?,
+            todo_name_fulltext: get(kw!(:todo/name_fulltext))?,
+            todo_name_trigrams: get(kw!(:todo/name_trigrams))?,
+            todo_notes: get(kw!(:todo/notes))?,
+            todo_due_date: get(kw!(:todo/due_date))?,
+            todo_completion_date: get(kw!(:todo/completion_date))?,
+            todo_recurrence: get(kw!(:todo/recurrence))?,
+            todo_list: get(kw!(:todo/list))?,
+            todo_parent: get(kw!(:todo/parent))?,
+            todo_position: get(kw!(:todo/position))?,
+            todo_created_at: get(kw!(:todo/created_at))?,
+            todo_label: get(kw!(:todo/label))?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        from_millis,
+        to_millis,
+    };
+
+    use time::Timespec;
+
+    #[test]
+    fn test_millis_round_trip() {
+        for timespec in vec![Timespec::new(1_517_389_200, 123_456_000), Timespec::new(-1, 999_999_000), Timespec::new(0, 0)] {
+            assert_eq!(from_millis(to_millis(&timespec)), timespec);
+        }
+        assert_eq!(to_millis(&Timespec::new(1, 500_000_000)), 1500.0);
+    }
+}
@@ -12,9 +12,18 @@ use mentat::{
     Binding,
 };
 
+use mentat_core::{
+    KnownEntid,
+};
+
+use errors::{
+    ErrorKind,
+    Result,
+};
+
 use utils::{
+    column,
     Entity,
-    ToInner,
 };
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -25,11 +34,20 @@ pub struct Label {
 }
 
 impl Label {
-    pub fn from_row(row: &Vec<Binding>) -> Option<Label> {
-        Some(Label {
-            id: row[0].clone().val().map(|v| v.to_inner()).expect("typed value"),
-            name: row[1].clone().val().map(|v| v.to_inner()).expect("typed value"),
-            color: row[2].clone().val().map(|v| v.to_inner()).expect("typed value")
+    /// Builds a label from an `?l ?name ?color` row.
+    pub fn from_row(row: &[Binding]) -> Result<Label> {
+        Ok(Label {
+            id: Some(column(row, 0)?),
+            name: column(row, 1)?,
+            color: column(row, 2)?,
         })
     }
+
+    /// The label's entity, which only labels that came from the store have.
+    pub fn entid(&self) -> Result<KnownEntid> {
+        self.id
+            .as_ref()
+            .map(|id| KnownEntid(id.id))
+            .ok_or_else(|| ErrorKind::LabelWithoutId(self.name.clone()).into())
+    }
 }
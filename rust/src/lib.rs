@@ -18,23 +18,37 @@ extern crate libc;
 extern crate mentat_core;
 extern crate mentat_ffi;
 extern crate rusqlite;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
 extern crate time;
 extern crate uuid;
 
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::ops::{
+    Deref,
+    DerefMut,
+};
+
 use mentat::{
     Binding,
+    Entid,
+    InProgress,
     IntoResult,
     Queryable,
     QueryExecutionResult,
     QueryInputs,
-    TypedValue,
-    ValueType,
+    RelResult,
+    Variable,
 };
 
 pub use mentat::{
-    Store,
+    TypedValue,
 };
 
+
 use mentat_ffi::utils::log;
 
 use mentat_core::{
@@ -43,25 +57,35 @@ use mentat_core::{
 
 use mentat::entity_builder::{
     BuildTerms,
+    InProgressBuilder,
 };
 
-use mentat::vocabulary::{
-    AttributeBuilder,
-    Definition,
-    VersionedStore,
-};
-
-use mentat::vocabulary::attribute::{
-    Unique
+pub use time::{
+    Duration,
+    Timespec,
 };
-
-pub use time::Timespec;
 pub use mentat::Uuid;
 
 pub mod labels;
 pub mod items;
+pub mod lists;
 pub mod errors;
-
+pub mod events;
+pub mod history;
+pub mod ical;
+pub mod json;
+pub mod position;
+pub mod query;
+pub mod recurrence;
+pub mod reminders;
+pub mod schema;
+pub mod search;
+pub mod taskwarrior;
+pub mod todotxt;
+pub mod transaction;
+
+mod local;
+mod undo;
 mod utils;
 
 use errors::{
@@ -69,8 +93,23 @@ use errors::{
     Result,
 };
 
+use local::{
+    Local,
+};
+
+pub use events::{
+    ChangeEvent,
+    ChangeObserver,
+};
+
+pub use history::{
+    ChangeOrigin,
+    ItemChange,
+};
+
 pub use items::{
     Item,
+    ItemNode,
     Items,
 };
 
@@ -78,7 +117,46 @@ pub use labels::{
     Label,
 };
 
+pub use lists::{
+    List,
+};
+
+pub use query::{
+    ItemOrder,
+    ItemQuery,
+    LabelMatch,
+    SortDirection,
+    TrashFilter,
+};
+
+use query::{
+    ItemSelection,
+};
+
+pub use recurrence::{
+    Frequency,
+    Occurrence,
+    Recurrence,
+    Weekday,
+};
+
+pub use reminders::{
+    PendingReminder,
+    Reminder,
+    ReminderTrigger,
+};
+
+pub use search::{
+    SearchResult,
+};
+
+pub use transaction::{
+    Transaction,
+};
+
 use utils::{
+    column,
+    Entity,
     ToInner,
     ToTypedValue,
 };
@@ -93,19 +171,665 @@ fn return_date_field(results: QueryExecutionResult) -> Result<Option<Timespec>>
            .map_err(|e| e.into())
 }
 
+/// Groups `?e ?value` rows by entity, failing with `MalformedRow` if a value isn't a `T`.
+fn values_by_entity<T>(rows: RelResult<Binding>) -> Result<HashMap<Entid, T>> where TypedValue: ToInner<Option<T>> {
+    rows.into_iter()
+        .map(|row| {
+            let entity: Entity = column(&row, 0)?;
+            Ok((entity.id, column(&row, 1)?))
+        })
+        .collect()
+}
+
+/// Fetches every item matched by `selection`.
+///
+/// Rather than looking up each item's dates and labels separately, this issues one query
+/// per field across the whole matching set and groups the results by entity, so the number
+/// of queries doesn't grow with the number of items.
+fn fetch_items_where<Q: Queryable>(queryable: &Q, selection: ItemSelection) -> Result<Vec<Item>> {
+    let in_clause = if selection.inputs.is_empty() {
+        String::new()
+    } else {
+        format!(":in {}", selection.inputs.iter().map(|&(ref name, _)| name.as_str()).collect::<Vec<_>>().join(" "))
+    };
+    let clauses = selection.clauses.join(" ");
+    let query = |find: &str, patterns: &str, modifiers: &str| {
+        format!("[:find {} {} :where {} {} {}]", find, in_clause, clauses, patterns, modifiers)
+    };
+    let args = || {
+        QueryInputs::with_value_sequence(selection.inputs.iter()
+                                                         .map(|&(ref name, ref value)| (Variable::from_valid_name(name), value.clone()))
+                                                         .collect())
+    };
+    let mut modifiers = String::new();
+    if !selection.order.is_empty() {
+        modifiers.push_str(&format!(":order {} ", selection.order.join(" ")));
+    }
+    if let Some(limit) = selection.limit {
+        modifiers.push_str(&format!(":limit {}", limit));
+    }
+
+    let rows = queryable
+        .q_once(&query("?eid ?item_uuid ?item_name",
+                       "[?eid :todo/uuid ?item_uuid] [?eid :todo/name ?item_name]",
+                       &modifiers),
+                args())
+        .into_rel_result()?;
+    let due_dates = queryable
+        .q_once(&query("?eid ?due_date",
+                       "[?eid :todo/due_date ?due_date]", ""),
+                args())
+        .into_rel_result()?;
+    let completion_dates = queryable
+        .q_once(&query("?eid ?completion_date",
+                       "[?eid :todo/completion_date ?completion_date]", ""),
+                args())
+        .into_rel_result()?;
+    let deletion_dates = queryable
+        .q_once(&query("?eid ?deleted_at",
+                       "[?eid :todo/deleted_at ?deleted_at]", ""),
+                args())
+        .into_rel_result()?;
+    let creation_dates = queryable
+        .q_once(&query("?eid ?created_at",
+                       "[?eid :todo/created_at ?created_at]", ""),
+                args())
+        .into_rel_result()?;
+    let modification_dates = queryable
+        .q_once(&query("?eid ?modified_at",
+                       "[?eid :todo/modified_at ?modified_at]", ""),
+                args())
+        .into_rel_result()?;
+    let position_rows = queryable
+        .q_once(&query("?eid ?position",
+                       "[?eid :todo/position ?position]", ""),
+                args())
+        .into_rel_result()?;
+    let note_rows = queryable
+        .q_once(&query("?eid ?notes",
+                       "[?eid :todo/notes ?notes]", ""),
+                args())
+        .into_rel_result()?;
+    let recurrence_rows = queryable
+        .q_once(&query("?eid ?rule",
+                       "[?eid :todo/recurrence ?rule]", ""),
+                args())
+        .into_rel_result()?;
+    let list_rows = queryable
+        .q_once(&query("?eid ?list_uuid",
+                       "[?eid :todo/list ?list] [?list :list/uuid ?list_uuid]", ""),
+                args())
+        .into_rel_result()?;
+    let parent_rows = queryable
+        .q_once(&query("?eid ?parent_uuid",
+                       "[?eid :todo/parent ?parent] [?parent :todo/uuid ?parent_uuid]", ""),
+                args())
+        .into_rel_result()?;
+    // Mentat's `count` aggregate would collapse the rows of every other query sharing these
+    // clauses, so we count children ourselves. Children in the trash aren't counted.
+    let child_rows = queryable
+        .q_once(&query("?eid ?child",
+                       "[?child :todo/parent ?eid] [?child :todo/uuid _] (not [?child :todo/deleted_at _])", ""),
+                args())
+        .into_rel_result()?;
+    let label_rows = queryable
+        .q_once(&query("?eid ?item_label ?label_name ?label_color",
+                       "[?eid :todo/label ?item_label] [?item_label :label/name ?label_name] [?item_label :label/color ?label_color]", ""),
+                args())
+        .into_rel_result()?;
+
+    let due_dates: HashMap<Entid, Timespec> = values_by_entity(due_dates)?;
+    let completion_dates: HashMap<Entid, Timespec> = values_by_entity(completion_dates)?;
+    let deletion_dates: HashMap<Entid, Timespec> = values_by_entity(deletion_dates)?;
+    let creation_dates: HashMap<Entid, Timespec> = values_by_entity(creation_dates)?;
+    let modification_dates: HashMap<Entid, Timespec> = values_by_entity(modification_dates)?;
+    let mut notes: HashMap<Entid, String> = values_by_entity(note_rows)?;
+    let mut positions: HashMap<Entid, String> = values_by_entity(position_rows)?;
+    let lists: HashMap<Entid, Uuid> = values_by_entity(list_rows)?;
+    let parents: HashMap<Entid, Uuid> = values_by_entity(parent_rows)?;
+
+    // A rule we can't parse was probably written by a newer client; treat the item as a
+    // one-off rather than failing the whole fetch.
+    let rules: HashMap<Entid, String> = values_by_entity(recurrence_rows)?;
+    let mut recurrences: HashMap<Entid, Recurrence> = rules.into_iter()
+        .filter_map(|(entid, rule)| rule.parse().ok().map(|rule| (entid, rule)))
+        .collect();
+
+    let mut child_counts: HashMap<Entid, usize> = HashMap::new();
+    for row in child_rows.into_iter() {
+        let entity: Entity = column(&row, 0)?;
+        *child_counts.entry(entity.id).or_insert(0) += 1;
+    }
+
+    let mut labels: HashMap<Entid, Vec<Label>> = HashMap::new();
+    for row in label_rows.into_iter() {
+        let entity: Entity = column(&row, 0)?;
+        labels.entry(entity.id).or_insert_with(Vec::new).push(Label::from_row(&row[1..])?);
+    }
+
+    rows.into_iter().map(|row| {
+        let id: Entity = column(&row, 0)?;
+        let entid = id.id;
+        Ok(Item {
+            uuid: column(&row, 1)?,
+            name: column(&row, 2)?,
+            notes: notes.remove(&entid),
+            due_date: due_dates.get(&entid).cloned(),
+            completion_date: completion_dates.get(&entid).cloned(),
+            deleted_at: deletion_dates.get(&entid).cloned(),
+            position: positions.remove(&entid),
+            created_at: creation_dates.get(&entid).cloned(),
+            modified_at: modification_dates.get(&entid).cloned(),
+            recurrence: recurrences.remove(&entid),
+            list: lists.get(&entid).cloned(),
+            parent: parents.get(&entid).cloned(),
+            child_count: child_counts.get(&entid).cloned().unwrap_or(0),
+            labels: labels.remove(&entid).unwrap_or_default(),
+            id: Some(id),
+        })
+    }).collect()
+}
+
+/// Fetches every reminder matched by `clauses`, which bind `?r` to each reminder and may
+/// refer to `inputs` by name.
+fn fetch_reminders_where(store: &mut Store, clauses: &str, inputs: Vec<(&str, TypedValue)>) -> Result<Vec<Reminder>> {
+    let in_clause = if inputs.is_empty() {
+        String::new()
+    } else {
+        format!(":in {}", inputs.iter().map(|&(name, _)| name).collect::<Vec<_>>().join(" "))
+    };
+    let query = |find: &str, patterns: &str, modifiers: &str| {
+        format!("[:find {} {} :where {} {} {}]", find, in_clause, clauses, patterns, modifiers)
+    };
+    let args = || {
+        QueryInputs::with_value_sequence(inputs.iter()
+                                               .map(|&(name, ref value)| (Variable::from_valid_name(name), value.clone()))
+                                               .collect())
+    };
+
+    let in_progress_read = store.begin_read()?;
+    let rows = in_progress_read
+        .q_once(&query("?r ?uuid ?item_uuid",
+                       "[?r :reminder/uuid ?uuid] [?r :reminder/item ?reminder_item] [?reminder_item :todo/uuid ?item_uuid]",
+                       ":order (asc ?r)"),
+                args())
+        .into_rel_result()?;
+    let at: HashMap<Entid, Timespec> = values_by_entity(in_progress_read
+        .q_once(&query("?r ?at", "[?r :reminder/at ?at]", ""), args())
+        .into_rel_result()?)?;
+    let snoozed: HashMap<Entid, Timespec> = values_by_entity(in_progress_read
+        .q_once(&query("?r ?snoozed", "[?r :reminder/snoozed_until ?snoozed]", ""), args())
+        .into_rel_result()?)?;
+    let acknowledged: HashMap<Entid, Timespec> = values_by_entity(in_progress_read
+        .q_once(&query("?r ?acknowledged", "[?r :reminder/acknowledged ?acknowledged]", ""), args())
+        .into_rel_result()?)?;
+    let offsets: HashMap<Entid, i64> = values_by_entity(in_progress_read
+        .q_once(&query("?r ?offset", "[?r :reminder/offset ?offset]", ""), args())
+        .into_rel_result()?)?;
+
+    let mut reminders = vec![];
+    for row in rows.into_iter() {
+        let id: Entity = column(&row, 0)?;
+        let entid = id.id;
+        let trigger = match (at.get(&entid), offsets.get(&entid)) {
+            (Some(&date), _) => ReminderTrigger::At(date),
+            (None, Some(&offset)) => ReminderTrigger::BeforeDue(Duration::seconds(offset)),
+            (None, None) => continue,
+        };
+        reminders.push(Reminder {
+            uuid: column(&row, 1)?,
+            item: column(&row, 2)?,
+            trigger: trigger,
+            snoozed_until: snoozed.get(&entid).cloned(),
+            acknowledged: acknowledged.get(&entid).cloned(),
+            id: Some(id),
+        });
+    }
+    Ok(reminders)
+}
+
+/// Returns every attribute and value asserted about `entid`, so that it can be retracted
+/// whole.
+fn entity_datoms<Q: Queryable>(queryable: &Q, entid: KnownEntid) -> Result<Vec<(KnownEntid, TypedValue)>> {
+    let query = r#"[:find ?a ?v
+                    :in ?e
+                    :where
+                    [?e ?a ?v]
+    ]"#;
+    let args = QueryInputs::with_value_sequence(vec![(var!(?e), TypedValue::Ref(entid.0))]);
+    Ok(queryable
+        .q_once(query, args)
+        .into_rel_result()?
+        .into_iter()
+        .filter_map(|row| {
+            let attribute: Option<Entity> = row[0].clone().val().and_then(|v| v.to_inner());
+            attribute.and_then(|a| row[1].clone().val().map(|v| (KnownEntid(a.id), v)))
+        })
+        .collect())
+}
+
+/// Finds the reminders for the item `entid`.
+fn reminder_entids<Q: Queryable>(queryable: &Q, entid: KnownEntid) -> Result<Vec<KnownEntid>> {
+    let query = r#"[:find [?r ...]
+                    :in ?item
+                    :where
+                    [?r :reminder/item ?item]
+    ]"#;
+    let args = QueryInputs::with_value_sequence(vec![(var!(?item), TypedValue::Ref(entid.0))]);
+    queryable
+        .q_once(query, args)
+        .into_coll_result()?
+        .into_iter()
+        .map(|binding| {
+            let reminder: Entity = column(&[binding], 0)?;
+            Ok(KnownEntid(reminder.id))
+        })
+        .collect()
+}
+
+/// Looks up the entity of the reminder with `uuid`.
+fn reminder_entid<Q: Queryable>(queryable: &Q, uuid: &Uuid) -> Result<KnownEntid> {
+    let query = r#"[:find ?r .
+                    :in ?uuid
+                    :where
+                    [?r :reminder/uuid ?uuid]
+    ]"#;
+    let args = QueryInputs::with_value_sequence(vec![(var!(?uuid), uuid.to_typed_value())]);
+    let entity: Option<Entity> = queryable
+        .q_once(query, args)
+        .into_scalar_result()?
+        .and_then(|binding| binding.val())
+        .and_then(|v| v.to_inner());
+    entity.map(|e| KnownEntid(e.id))
+          .ok_or_else(|| ErrorKind::ReminderNotFound(uuid.hyphenated().to_string()).into())
+}
+
+/// Looks up the entity of the item with `uuid`.
+fn item_entid<Q: Queryable>(queryable: &Q, uuid: &Uuid) -> Result<KnownEntid> {
+    let query = r#"[:find ?eid .
+                    :in ?uuid
+                    :where
+                    [?eid :todo/uuid ?uuid]
+    ]"#;
+    let args = QueryInputs::with_value_sequence(vec![(var!(?uuid), uuid.to_typed_value())]);
+    let entity: Option<Entity> = queryable
+        .q_once(query, args)
+        .into_scalar_result()?
+        .and_then(|binding| binding.val())
+        .and_then(|v| v.to_inner());
+    entity.map(|e| KnownEntid(e.id))
+          .ok_or_else(|| ErrorKind::ItemNotFound(uuid.hyphenated().to_string()).into())
+}
+
+/// Looks up the entity of the list with `uuid`.
+fn list_entid<Q: Queryable>(queryable: &Q, uuid: &Uuid) -> Result<KnownEntid> {
+    let query = r#"[:find ?l .
+                    :in ?uuid
+                    :where
+                    [?l :list/uuid ?uuid]
+    ]"#;
+    let args = QueryInputs::with_value_sequence(vec![(var!(?uuid), uuid.to_typed_value())]);
+    let entity: Option<Entity> = queryable
+        .q_once(query, args)
+        .into_scalar_result()?
+        .and_then(|binding| binding.val())
+        .and_then(|v| v.to_inner());
+    entity.map(|e| KnownEntid(e.id))
+          .ok_or_else(|| ErrorKind::ListNotFound(uuid.hyphenated().to_string()).into())
+}
+
+/// Looks up the list the item `entid` is in, if it's in one.
+fn item_list_entid<Q: Queryable>(queryable: &Q, entid: KnownEntid) -> Result<Option<KnownEntid>> {
+    let query = r#"[:find ?list .
+                    :in ?eid
+                    :where
+                    [?eid :todo/list ?list]
+    ]"#;
+    let args = QueryInputs::with_value_sequence(vec![(var!(?eid), TypedValue::Ref(entid.0))]);
+    let list: Option<Entity> = queryable
+        .q_once(query, args)
+        .into_scalar_result()?
+        .and_then(|binding| binding.val())
+        .and_then(|v| v.to_inner());
+    Ok(list.map(|list| KnownEntid(list.id)))
+}
+
+/// Returns the entities of the items directly under `parent`.
+fn child_entids<Q: Queryable>(queryable: &Q, parent: KnownEntid) -> Result<Vec<Entid>> {
+    let query = r#"[:find [?child ...]
+                    :in ?parent
+                    :where
+                    [?child :todo/parent ?parent]
+    ]"#;
+    let args = QueryInputs::with_value_sequence(vec![(var!(?parent), TypedValue::Ref(parent.0))]);
+    Ok(queryable
+        .q_once(query, args)
+        .into_coll_result()?
+        .into_iter()
+        .filter_map(|binding| {
+            let entity: Option<Entity> = binding.val().and_then(|v| v.to_inner());
+            entity.map(|e| e.id)
+        })
+        .collect())
+}
+
+/// Returns the entities of every item above `entid`, nearest first.
+fn ancestor_entids<Q: Queryable>(queryable: &Q, entid: KnownEntid) -> Result<Vec<Entid>> {
+    let query = r#"[:find ?parent .
+                    :in ?eid
+                    :where
+                    [?eid :todo/parent ?parent]
+    ]"#;
+    let mut ancestors: Vec<Entid> = vec![];
+    let mut current = entid.0;
+    loop {
+        let args = QueryInputs::with_value_sequence(vec![(var!(?eid), TypedValue::Ref(current))]);
+        let parent: Option<Entity> = queryable
+            .q_once(query, args)
+            .into_scalar_result()?
+            .and_then(|binding| binding.val())
+            .and_then(|v| v.to_inner());
+        match parent {
+            // Stop rather than spin if a merge has somehow left a loop behind.
+            Some(parent) if parent.id != entid.0 && !ancestors.contains(&parent.id) => {
+                ancestors.push(parent.id);
+                current = parent.id;
+            },
+            _ => return Ok(ancestors),
+        }
+    }
+}
+
+/// When the item `entid` was put in the trash, if it's there.
+fn deletion_date<Q: Queryable>(queryable: &Q, entid: Entid) -> Result<Option<Timespec>> {
+    let query = r#"[:find ?date .
+                    :in ?eid
+                    :where
+                    [?eid :todo/deleted_at ?date]
+    ]"#;
+    let args = QueryInputs::with_value_sequence(vec![(var!(?eid), TypedValue::Ref(entid))]);
+    return_date_field(queryable.q_once(query, args))
+}
+
+/// Adds the terms that change the dates of the item `entid`, which is currently `item`, to
+/// `due_date` and `completion_date`.
+///
+/// Completing a recurring item moves it on to its next occurrence instead, so it keeps its
+/// UUID, labels and place in any list. Only the last occurrence is marked complete.
+fn add_date_terms(builder: &mut InProgressBuilder,
+                  entid: KnownEntid,
+                  item: &Item,
+                  due_date: Option<Timespec>,
+                  completion_date: Option<Timespec>) -> Result<()> {
+    let mut due_date = due_date;
+    let mut completion_date = completion_date;
+    let mut completed_occurrence: Option<Timespec> = None;
+    let mut next_recurrence: Option<Recurrence> = None;
+    if let (None, Some(completed), Some(recurrence)) = (item.completion_date, completion_date, item.recurrence.as_ref()) {
+        completed_occurrence = Some(completed);
+        let current = due_date.or(item.due_date).unwrap_or(completed);
+        if let Some(next) = recurrence.next_after(current) {
+            due_date = Some(next);
+            completion_date = None;
+            next_recurrence = Some(recurrence.advanced());
+        }
+    }
+
+    // Dates are only stored to the microsecond, so compare them as they'd be stored.
+    if item.due_date.map(|date| date.to_typed_value()) != due_date.map(|date| date.to_typed_value()) {
+        let due_date_kw = kw!(:todo/due_date);
+        if let Some(date) = due_date {
+            builder.add_kw(entid, &due_date_kw, date.to_typed_value())?;
+        } else if let Some(date) = item.due_date {
+            builder.retract_kw(entid, &due_date_kw, date.to_typed_value())?;
+        }
+    }
+
+    if item.completion_date.map(|date| date.to_typed_value()) != completion_date.map(|date| date.to_typed_value()) {
+        let completion_date_kw = kw!(:todo/completion_date);
+        if let Some(date) = completion_date {
+            builder.add_kw(entid, &completion_date_kw, date.to_typed_value())?;
+        } else if let Some(date) = item.completion_date {
+            builder.retract_kw(entid, &completion_date_kw, date.to_typed_value())?;
+        }
+    }
+
+    if let Some(date) = completed_occurrence {
+        builder.add_kw(entid, &kw!(:todo/completions), date.to_typed_value())?;
+    }
+    if let Some(recurrence) = next_recurrence {
+        if item.recurrence.as_ref() != Some(&recurrence) {
+            builder.add_kw(entid, &kw!(:todo/recurrence), TypedValue::typed_string(&recurrence.to_string()))?;
+        }
+    }
+    Ok(())
+}
+
+/// Fetches the items with the given UUIDs, failing if any of them isn't there.
+fn fetch_items_with_uuids<Q: Queryable>(queryable: &Q, uuids: &[Uuid]) -> Result<Vec<Item>> {
+    if uuids.is_empty() {
+        return Ok(vec![]);
+    }
+    let names: Vec<String> = (0..uuids.len()).map(|i| format!("?uuid_{}", i)).collect();
+    let branches: Vec<String> = names.iter().map(|name| format!("[?eid :todo/uuid {}]", name)).collect();
+    let clauses = format!("(or-join [?eid] {})", branches.join(" "));
+    let inputs = names.iter().map(|name| name.as_str()).zip(uuids.iter().map(|uuid| uuid.to_typed_value())).collect();
+    let items = fetch_items_where(queryable, ItemSelection::new(&clauses, inputs))?;
+    if let Some(missing) = uuids.iter().find(|&uuid| !items.iter().any(|item| item.uuid == *uuid)) {
+        bail!(ErrorKind::ItemNotFound(missing.hyphenated().to_string()));
+    }
+    Ok(items)
+}
+
+/// Fetches the item with `uuid`, if there is one.
+fn fetch_item_with_uuid<Q: Queryable>(queryable: &Q, uuid: &Uuid) -> Result<Option<Item>> {
+    let clauses = r#"[?eid :todo/uuid ?uuid]"#;
+    fetch_items_where(queryable, ItemSelection::new(clauses, vec![("?uuid", uuid.to_typed_value())]))
+        .map(|items| items.into_iter().next())
+}
+
+/// Looks up the label called `name`, if there is one.
+fn fetch_label_named<Q: Queryable>(queryable: &Q, name: &str) -> Result<Option<Label>> {
+    let query = r#"[:find [?eid ?name ?color]
+                    :in ?name
+                    :where
+                    [?eid :label/name ?name]
+                    [?eid :label/color ?color]
+    ]"#;
+    let args = QueryInputs::with_value_sequence(vec![(var!(?name), TypedValue::typed_string(name))]);
+    let row = queryable.q_once(query, args).into_tuple_result()?;
+    row.map_or(Ok(None), |row| Label::from_row(&row).map(Some))
+}
+
+fn fetch_all_labels<Q: Queryable>(queryable: &Q) -> Result<Vec<Label>> {
+    let query = r#"[:find ?eid ?name ?color
+                    :where
+                    [?eid :label/name ?name]
+                    [?eid :label/color ?color]
+    ]"#;
+    queryable
+        .q_once(query, None)
+        .into_rel_result()?
+        .into_iter()
+        .map(|row| Label::from_row(&row))
+        .collect()
+}
+
+/// The labels on the item with `uuid`.
+fn fetch_item_labels<Q: Queryable>(queryable: &Q, uuid: &Uuid) -> Result<Vec<Label>> {
+    let query = r#"[:find ?l ?name ?color
+                    :in ?item_uuid
+                    :where
+                    [?i :todo/uuid ?item_uuid]
+                    [?i :todo/label ?l]
+                    [?l :label/name ?name]
+                    [?l :label/color ?color]
+    ]"#;
+    let args = QueryInputs::with_value_sequence(vec![(var!(?item_uuid), uuid.to_typed_value())]);
+    queryable
+        .q_once(query, args)
+        .into_rel_result()?
+        .into_iter()
+        .map(|row| Label::from_row(&row))
+        .collect()
+}
+
+/// Builds the deletion of the items `to_delete` and their reminders. Every datom about them is
+/// retracted, rather than just the attributes we know about, so that nothing is left
+/// dangling. Subtasks that aren't being deleted outlive their parent, moving up to take its
+/// place.
+fn delete_entities<'a, 'c>(in_progress: InProgress<'a, 'c>, to_delete: &[Entid]) -> Result<InProgressBuilder<'a, 'c>> {
+    let mut datoms: Vec<(KnownEntid, KnownEntid, TypedValue)> = vec![];
+    let mut new_parents: Vec<(Entid, Entid, Option<Entid>)> = vec![];
+    for &entid in to_delete.iter() {
+        let entid = KnownEntid(entid);
+        let mut entities = vec![entid];
+        entities.extend(reminder_entids(&in_progress, entid)?);
+        for entity in entities {
+            for (attribute, value) in entity_datoms(&in_progress, entity)? {
+                datoms.push((entity, attribute, value));
+            }
+        }
+        let ancestor = ancestor_entids(&in_progress, entid)?
+            .into_iter()
+            .find(|ancestor| !to_delete.contains(ancestor));
+        for child in child_entids(&in_progress, entid)? {
+            if !to_delete.contains(&child) {
+                new_parents.push((child, entid.0, ancestor));
+            }
+        }
+    }
+
+    let mut builder = in_progress.builder();
+    let parent_kw = kw!(:todo/parent);
+    for (child, parent, ancestor) in new_parents {
+        match ancestor {
+            Some(ancestor) => builder.add_kw(KnownEntid(child), &parent_kw, TypedValue::Ref(ancestor))?,
+            None => builder.retract_kw(KnownEntid(child), &parent_kw, TypedValue::Ref(parent))?,
+        }
+    }
+    for (entity, attribute, value) in datoms {
+        builder.retract(entity, attribute, value)?;
+    }
+    Ok(builder)
+}
+
+/// The position of every item that has a valid one.
+fn item_positions<Q: Queryable>(queryable: &Q) -> Result<Vec<(Entid, String)>> {
+    let query = r#"[:find ?eid ?position
+                    :where
+                    [?eid :todo/position ?position]
+    ]"#;
+    let positions: HashMap<Entid, String> = values_by_entity(queryable.q_once(query, None).into_rel_result()?)?;
+    Ok(positions.into_iter()
+                .filter(|&(_, ref p)| position::is_valid(p))
+                .collect())
+}
+
+/// A position after every item's.
+fn last_position<Q: Queryable>(queryable: &Q) -> Result<String> {
+    let positions = item_positions(queryable)?;
+    Ok(position::between(positions.iter().map(|&(_, ref p)| p.as_str()).max(), None))
+}
+
+/// Nests `items` under their parents. Items whose parent isn't among `items` become roots.
+fn build_item_tree(items: Vec<Item>) -> Vec<ItemNode> {
+    fn build(item: Item, children: &mut HashMap<Uuid, Vec<Item>>) -> ItemNode {
+        let nested = children.remove(&item.uuid)
+                             .unwrap_or_default()
+                             .into_iter()
+                             .map(|child| build(child, children))
+                             .collect();
+        ItemNode {
+            item: item,
+            children: nested,
+        }
+    }
+
+    let uuids: Vec<Uuid> = items.iter().map(|item| item.uuid).collect();
+    let mut roots: Vec<Item> = vec![];
+    let mut children: HashMap<Uuid, Vec<Item>> = HashMap::new();
+    for item in items {
+        match item.parent {
+            Some(parent) if uuids.contains(&parent) => children.entry(parent).or_insert_with(Vec::new).push(item),
+            _ => roots.push(item),
+        }
+    }
+    roots.into_iter().map(|item| build(item, &mut children)).collect()
+}
+
+/// A Mentat store, along with what Toodle keeps about it that stays on this device, which is
+/// described in `local`. Everything else is reached through the Mentat store, which this
+/// dereferences to.
+///
+/// The Mentat store comes first, so that a pointer to a `Store` can be handed to the
+/// `store_*` functions `mentat_ffi` provides.
+#[repr(C)]
+pub struct Store {
+    pub(crate) mentat: mentat::Store,
+    pub(crate) local: Local,
+}
+
+impl Store {
+    /// Opens the store at `path`, or an in-memory one if `path` is empty.
+    pub fn open(path: &str) -> Result<Store> {
+        Ok(Store {
+            mentat: mentat::Store::open(path)?,
+            local: Local::open(path)?,
+        })
+    }
+}
+
+impl Deref for Store {
+    type Target = mentat::Store;
+
+    fn deref(&self) -> &mentat::Store {
+        &self.mentat
+    }
+}
+
+impl DerefMut for Store {
+    fn deref_mut(&mut self) -> &mut mentat::Store {
+        &mut self.mentat
+    }
+}
+
 pub trait Toodle {
     fn initialize(&mut self) -> Result<()>;
-    fn item_row_to_item(&mut self, row: Vec<Binding>) -> Item;
+    /// Makes the changes `f` makes through its `Transaction` all together: they're committed
+    /// only if `f` succeeds, and undone in one go. See `transaction`.
+    fn transact<F, T>(&mut self, f: F) -> Result<T>
+        where F: FnOnce(&mut Transaction) -> Result<T>;
     fn fetch_completion_date_for_item(&mut self, item_id: &Uuid) -> Result<Option<Timespec>>;
     fn fetch_due_date_for_item(&mut self, item_id: &Uuid) -> Result<Option<Timespec>>;
 
     fn create_label(&mut self, name: String, color: String) -> Result<Option<Label>>;
     fn fetch_label(&mut self, name: &String) -> Result<Option<Label>>;
     fn fetch_labels(&mut self) -> Result<Vec<Label>>;
+    fn rename_label(&mut self, label: &Label, name: String) -> Result<Option<Label>>;
+    fn update_label_color(&mut self, label: &Label, color: String) -> Result<Option<Label>>;
+    fn delete_label(&mut self, name: &String) -> Result<()>;
     fn fetch_labels_for_item(&mut self, item_uuid: &Uuid) -> Result<Vec<Label>>;
+    fn create_list(&mut self, name: String, color: String) -> Result<List>;
+    fn fetch_list(&mut self, uuid: &Uuid) -> Result<Option<List>>;
+    fn fetch_lists(&mut self) -> Result<Vec<List>>;
+    fn update_list(&mut self,
+                   list: &List,
+                   name: Option<String>,
+                   color: Option<String>,
+                   sort_order: Option<i64>) -> Result<List>;
+    fn delete_list(&mut self, uuid: &Uuid) -> Result<()>;
+    /// Moves an item and its subtasks to `list`. A subtask moved away from its parent's list
+    /// stops being a subtask.
+    fn move_item_to_list(&mut self, uuid: &Uuid, list: &Uuid) -> Result<()>;
+    /// Fetches the items with `label` that aren't in the trash. Use `fetch_items` with
+    /// `ItemQuery::with_any_label` and `ItemQuery::including_trashed` to get them all.
     fn fetch_items_with_label(&mut self, label: &Label) -> Result<Vec<Item>>;
-    fn fetch_items(&mut self) -> Result<Items>;
+    fn fetch_items(&mut self, query: &ItemQuery) -> Result<Items>;
+    /// Fetches the item with `uuid`, even if it's in the trash.
     fn fetch_item(&mut self, uuid: &Uuid) -> Result<Option<Item>>;
+    /// Creates `item`, in the first list if it isn't given one. Subtasks always go in their
+    /// parent's list.
     fn create_item(&mut self, item: &Item) -> Result<Uuid>;
     fn create_and_fetch_item(&mut self, item: &Item) -> Result<Option<Item>>;
     fn update_item_by_uuid(&mut self,
@@ -119,214 +843,286 @@ pub trait Toodle {
                        due_date: Option<Timespec>,
                        completion_date: Option<Timespec>,
                        labels: Option<&Vec<Label>>) -> Result<()>;
+    fn update_item_notes(&mut self, item: &Item, notes: Option<String>) -> Result<()>;
+    fn update_item_recurrence(&mut self, item: &Item, recurrence: Option<Recurrence>) -> Result<()>;
+    fn fetch_completions(&mut self, uuid: &Uuid) -> Result<Vec<Timespec>>;
+    fn fetch_occurrences(&mut self, start: Timespec, end: Timespec) -> Result<Vec<Occurrence>>;
+    fn delete_item(&mut self, uuid: &Uuid) -> Result<()>;
+    /// Deletes every item in `uuids`, as `delete_item` does, as a single change.
+    fn delete_items(&mut self, uuids: &[Uuid]) -> Result<()>;
+    /// Puts an item, and any of its subtasks that aren't there already, in the trash.
+    fn trash_item(&mut self, uuid: &Uuid) -> Result<()>;
+    /// Takes an item out of the trash, along with the subtasks that were trashed with it.
+    fn restore_item(&mut self, uuid: &Uuid) -> Result<()>;
+    /// Deletes the items that were put in the trash before `older_than` for good, returning
+    /// how many there were.
+    fn empty_trash(&mut self, older_than: Timespec) -> Result<usize>;
+    /// Fetches the subtasks of an item that aren't in the trash. Use `fetch_items` with
+    /// `ItemQuery::subtasks_of` and `ItemQuery::including_trashed` to get them all.
+    fn fetch_children(&mut self, uuid: &Uuid) -> Result<Vec<Item>>;
+    fn fetch_item_tree(&mut self) -> Result<Vec<ItemNode>>;
+    /// Puts an item under `parent`, or back at the top level, moving it and its subtasks to
+    /// the parent's list.
+    fn move_item(&mut self, uuid: &Uuid, parent: Option<&Uuid>) -> Result<()>;
+    /// Puts an item right after `previous` and before `next` in the order items are fetched
+    /// in by default. Given only one of them, the item goes next to it, and given neither, it
+    /// goes at the end. Only the item's own position changes, so items moved on different
+    /// devices end up where they were put on each once they sync.
+    fn reorder_item(&mut self, uuid: &Uuid, previous: Option<&Uuid>, next: Option<&Uuid>) -> Result<()>;
+    fn complete_item(&mut self, uuid: &Uuid, completion_date: Timespec, cascade: bool) -> Result<()>;
+    /// Completes every item in `uuids` that isn't already complete, as a single change.
+    /// Recurring items move on to their next occurrence, as they do with `update_item`.
+    fn complete_items(&mut self, uuids: &[Uuid], completion_date: Timespec) -> Result<()>;
+    /// Marks every item in `uuids` incomplete, as a single change.
+    fn uncomplete_items(&mut self, uuids: &[Uuid]) -> Result<()>;
+    /// Sets the due date of every item in `uuids`, or clears it if `due_date` is `None`, as a
+    /// single change.
+    fn set_due_date_for_items(&mut self, uuids: &[Uuid], due_date: Option<Timespec>) -> Result<()>;
+    /// Adds `label` to every item in `uuids`, as a single change.
+    fn add_label_to_items(&mut self, uuids: &[Uuid], label: &Label) -> Result<()>;
+    /// Takes `label` off every item in `uuids`, as a single change.
+    fn remove_label_from_items(&mut self, uuids: &[Uuid], label: &Label) -> Result<()>;
+    fn search_items(&mut self, text: &str) -> Result<Vec<SearchResult>>;
+    fn add_reminder(&mut self, item: &Uuid, trigger: ReminderTrigger) -> Result<Reminder>;
+    fn fetch_reminder(&mut self, uuid: &Uuid) -> Result<Option<Reminder>>;
+    fn fetch_reminders(&mut self, item: &Uuid) -> Result<Vec<Reminder>>;
+    fn delete_reminder(&mut self, uuid: &Uuid) -> Result<()>;
+    fn pending_reminders(&mut self, now: Timespec, window: Duration) -> Result<Vec<PendingReminder>>;
+    fn acknowledge_reminder(&mut self, uuid: &Uuid) -> Result<()>;
+    fn snooze_reminder(&mut self, uuid: &Uuid, until: Timespec) -> Result<()>;
+    fn fetch_item_history(&mut self, uuid: &Uuid) -> Result<Vec<ItemChange>>;
+    fn can_undo(&mut self) -> Result<bool>;
+    fn can_redo(&mut self) -> Result<bool>;
+    /// Reverts the most recent change, returning false if there was nothing to undo.
+    fn undo(&mut self) -> Result<bool>;
+    /// Reapplies the most recently undone change, returning false if there was nothing to redo.
+    fn redo(&mut self) -> Result<bool>;
+    /// Calls `notify` with `key`, from another thread, whenever items, labels, lists or
+    /// reminders change. The changes themselves are read with `take_change_events`.
+    fn register_change_observer<F>(&mut self, key: String, notify: F) -> Result<ChangeObserver>
+        where F: Fn(&str) + Send + Sync + 'static;
+    fn take_change_events(&mut self, observer: &ChangeObserver) -> Result<Vec<ChangeEvent>>;
+    fn unregister_change_observer(&mut self, observer: ChangeObserver);
+    /// Writes every item, label and list out as a JSON document, in the format described in
+    /// the `json` module.
+    fn export_json(&mut self) -> Result<String>;
+    /// Merges a document written by `export_json` into the store, as a single change.
+    fn import_json(&mut self, json: &str) -> Result<()>;
+    /// Writes every item out as an iCalendar file of `VTODO`s, as described in the `ical`
+    /// module.
+    fn export_ics(&mut self) -> Result<String>;
+    /// Merges the `VTODO`s in an iCalendar file into the store by UID, as a single change.
+    fn import_ics(&mut self, ics: &str) -> Result<()>;
+    /// Writes every item out as a todo.txt file, as described in the `todotxt` module.
+    fn export_todo_txt(&mut self) -> Result<String>;
+    /// Merges the items in a todo.txt file into the store, as a single change.
+    fn import_todo_txt(&mut self, text: &str) -> Result<()>;
+    /// Writes every item out as Taskwarrior tasks, as described in the `taskwarrior` module.
+    fn export_taskwarrior(&mut self) -> Result<String>;
+    /// Merges tasks exported from Taskwarrior into the store by UUID, as a single change.
+    fn import_taskwarrior(&mut self, json: &str) -> Result<()>;
 }
 
 impl Toodle for Store {
 
     fn initialize(&mut self) -> Result<()> {
         //println!("initializing Toodle");
-        let mut in_progress = self.begin_transaction()?;
+        let mut in_progress = self.mentat.begin_transaction()?;
+        let before = history::transactions(&in_progress)?.last().cloned();
         log::d(&format!("ensuring vocab..."));
-        in_progress.ensure_vocabulary(&Definition {
-            name: kw!(:toodle/list),
-            version: 1,
-            pre: Definition::no_op,
-            post: Definition::no_op,
-            attributes: vec![
-                (kw!(:todo/uuid),
-                AttributeBuilder::default()
-                    .value_type(ValueType::Uuid)
-                    .multival(false)
-                    .unique(Unique::Value)
-                    .index(true)
-                    .build()),
-                (kw!(:todo/name),
-                AttributeBuilder::default()
-                    .value_type(ValueType::String)
-                    .multival(false)
-                    .build()),
-                (kw!(:todo/due_date),
-                AttributeBuilder::default()
-                    .value_type(ValueType::Instant)
-                    .multival(false)
-                    .build()),
-                (kw!(:todo/completion_date),
-                AttributeBuilder::default()
-                    .value_type(ValueType::Instant)
-                    .multival(false)
-                    .build()),
-                (kw!(:todo/label),
-                AttributeBuilder::default()
-                    .value_type(ValueType::Ref)
-                    .multival(true)
-                    .build()),
-                (kw!(:label/name),
-                AttributeBuilder::default()
-                    .value_type(ValueType::String)
-                    .multival(false)
-                    .unique(Unique::Identity)
-                    .index(true)
-                    .fulltext(true)
-                    .build()),
-                (kw!(:label/color),
-                AttributeBuilder::default()
-                    .value_type(ValueType::String)
-                    .multival(false)
-                    .build()),
-            ],
-        })?;
+        schema::ensure(&mut in_progress, schema::current_version())?;
+        let txs: Vec<Entid> = history::transactions(&in_progress)?
+            .into_iter()
+            .filter(|&tx| before.map_or(true, |before| tx > before))
+            .collect();
         log::d(&format!("comitting vocab"));
-        in_progress.commit()
-            .map_err(|e| e.into())
-            .and(Ok(()))
+        in_progress.commit()?;
+        // Whatever was in the log before this device started keeping track of where changes
+        // come from can't be told apart. The upgrade's own changes were made here.
+        history::track_origins(&self.local.conn, before.unwrap_or(0))?;
+        history::mark_local(&self.local.conn, &txs)
     }
 
-    fn item_row_to_item(&mut self, row: Vec<Binding>) -> Item {
-        println!("Toodle::item_row_to_item");
-        let uuid = row[1].clone().val().expect("typed value").to_inner();
-        let labels = self.fetch_labels_for_item(&uuid).unwrap_or(vec![]);
-        println!("fetched labels {:?}", labels);
-        let item;
-        {
-            item = Item {
-                id: row[0].clone().val().expect("typed value").to_inner(),
-                uuid: uuid,
-                name: row[2].clone().val().expect("typed value").to_inner(),
-                due_date: self.fetch_due_date_for_item(&uuid).unwrap_or(None),
-                completion_date: self.fetch_completion_date_for_item(&uuid).unwrap_or(None),
-                labels: labels,
-            }
-        }
-        item
+    fn transact<F, T>(&mut self, f: F) -> Result<T>
+        where F: FnOnce(&mut Transaction) -> Result<T> {
+        // Returning early drops the transaction, and with it the `InProgress`, which rolls
+        // back whatever `f` did.
+        let mut transaction = Transaction::new(self.mentat.begin_transaction()?);
+        let result = f(&mut transaction)?;
+        transaction.commit(&mut self.local)?;
+        Ok(result)
     }
 
     fn create_label(&mut self, name: String, color: String) -> Result<Option<Label>> {
         //println!("Toodle::create_labels");
-        {
-            let in_progress = self.begin_transaction()?;
-            let mut builder = in_progress.builder().describe_tempid("label");
-
-            builder.add_kw(&kw!(:label/name), TypedValue::typed_string(&name))?;
-            builder.add_kw(&kw!(:label/color), TypedValue::typed_string(&color))?;
-
-            builder.commit()?;
-        }
-        self.fetch_label(&name)
+        self.transact(|tx| tx.create_label(name, color))
     }
 
     fn fetch_label(&mut self, name: &String) -> Result<Option<Label>> {
         //println!("Toodle::fetch_label");
-        let query = r#"[:find [?eid ?name ?color]
-                        :in ?name
-                        :where
-                        [?eid :label/name ?name]
-                        [?eid :label/color ?color]
-        ]"#;
-        let in_progress_read = self.begin_read()?;
-        let args = QueryInputs::with_value_sequence(vec![(var!(?name), name.to_typed_value())]);
-        in_progress_read
-            .q_once(query, args)
-            .into_tuple_result()
-            .map(|o| o.as_ref().and_then(Label::from_row))
-            .map_err(|e| e.into())
+        fetch_label_named(&self.begin_read()?, name)
     }
 
     fn fetch_labels(&mut self) -> Result<Vec<Label>> {
         //println!("Toodle::fetch_labels");
-        let query = r#"[:find ?eid ?name ?color
+        fetch_all_labels(&self.begin_read()?)
+    }
+
+    fn rename_label(&mut self, label: &Label, name: String) -> Result<Option<Label>> {
+        self.transact(|tx| tx.rename_label(label, name))
+    }
+
+    fn update_label_color(&mut self, label: &Label, color: String) -> Result<Option<Label>> {
+        self.transact(|tx| tx.update_label_color(label, color))
+    }
+
+    fn delete_label(&mut self, name: &String) -> Result<()> {
+        self.transact(|tx| tx.delete_label(name))
+    }
+
+    fn fetch_labels_for_item(&mut self, item_uuid: &Uuid) -> Result<Vec<Label>> {
+        //println!("Toodle::fetch_labels_for_item");
+        fetch_item_labels(&self.begin_read()?, item_uuid)
+    }
+
+
+    fn create_list(&mut self, name: String, color: String) -> Result<List> {
+        // New lists go at the end.
+        let sort_order = self.fetch_lists()?
+                             .iter()
+                             .map(|list| list.sort_order + 1)
+                             .max()
+                             .unwrap_or(0);
+        let uuid = create_uuid();
+        {
+            let in_progress = self.mentat.begin_transaction()?;
+            let mut builder = in_progress.builder().describe_tempid("list");
+            builder.add_kw(&kw!(:list/uuid), TypedValue::Uuid(uuid))?;
+            builder.add_kw(&kw!(:list/name), TypedValue::typed_string(&name))?;
+            builder.add_kw(&kw!(:list/color), TypedValue::typed_string(&color))?;
+            builder.add_kw(&kw!(:list/sort_order), TypedValue::Long(sort_order))?;
+            undo::commit(&mut self.local, builder)?;
+        }
+        self.fetch_list(&uuid)?
+            .ok_or_else(|| ErrorKind::ListNotFound(uuid.hyphenated().to_string()).into())
+    }
+
+    fn fetch_list(&mut self, uuid: &Uuid) -> Result<Option<List>> {
+        let query = r#"[:find [?l ?uuid ?name ?color ?sort_order]
+                        :in ?uuid
                         :where
-                        [?eid :label/name ?name]
-                        [?eid :label/color ?color]
+                        [?l :list/uuid ?uuid]
+                        [?l :list/name ?name]
+                        [?l :list/color ?color]
+                        [?l :list/sort_order ?sort_order]
         ]"#;
         let in_progress_read = self.begin_read()?;
-        in_progress_read
-            .q_once(query, None)
-            .into_rel_result()
-            .map(|rows| rows.into_iter().filter_map(|row| Label::from_row(&row)).collect())
-            .map_err(|e| e.into())
+        let args = QueryInputs::with_value_sequence(vec![(var!(?uuid), uuid.to_typed_value())]);
+        let row = in_progress_read.q_once(query, args).into_tuple_result()?;
+        row.map_or(Ok(None), |row| List::from_row(&row).map(Some))
     }
 
-    fn fetch_labels_for_item(&mut self, item_uuid: &Uuid) -> Result<Vec<Label>> {
-        println!("Toodle::fetch_labels_for_item");
-        let query = r#"[:find ?l ?name ?color
-                        :in ?item_uuid
+    fn fetch_lists(&mut self) -> Result<Vec<List>> {
+        let query = r#"[:find ?l ?uuid ?name ?color ?sort_order
                         :where
-                        [?i :todo/uuid ?item_uuid]
-                        [?i :todo/label ?l]
-                        [?l :label/name ?name]
-                        [?l :label/color ?color]
+                        [?l :list/uuid ?uuid]
+                        [?l :list/name ?name]
+                        [?l :list/color ?color]
+                        [?l :list/sort_order ?sort_order]
+                        :order (asc ?sort_order) (asc ?l)
         ]"#;
         let in_progress_read = self.begin_read()?;
-        let args = QueryInputs::with_value_sequence(vec![(var!(?item_uuid), item_uuid.to_typed_value())]);
         in_progress_read
-            .q_once(query, args)
-            .into_rel_result()
-            .map(|rows| rows.into_iter().filter_map(|row| Label::from_row(&row)).collect())
-            .map_err(|e| e.into())
+            .q_once(query, None)
+            .into_rel_result()?
+            .into_iter()
+            .map(|row| List::from_row(&row))
+            .collect()
     }
 
-
-    fn fetch_items_with_label(&mut self, label: &Label) -> Result<Vec<Item>> {
-        //println!("Toodle::fetch_items_with_label");
-        let query = r#"[:find ?eid ?uuid ?name
-                        :in ?label
-                        :where
-                        [?l :label/name ?label]
-                        [?eid :todo/label ?l]
-                        [?eid :todo/uuid ?uuid]
-                        [?eid :todo/name ?name]
-        ]"#;
-        let rows;
+    fn update_list(&mut self,
+                   list: &List,
+                   name: Option<String>,
+                   color: Option<String>,
+                   sort_order: Option<i64>) -> Result<List> {
+        let entid = KnownEntid(list.id.to_owned().ok_or_else(|| ErrorKind::ListNotFound(list.uuid.hyphenated().to_string()))?.id);
         {
-            let in_progress_read = self.begin_read()?;
-            let args = QueryInputs::with_value_sequence(vec![(var!(?label), label.name.to_typed_value())]);
-            rows = in_progress_read
-                .q_once(query, args)
-                .into_rel_result()
-                .map_err(|e| e.into());
+            let in_progress = self.mentat.begin_transaction()?;
+            let mut builder = in_progress.builder().describe(entid);
+            if let Some(name) = name {
+                builder.add_kw(&kw!(:list/name), TypedValue::typed_string(&name))?;
+            }
+            if let Some(color) = color {
+                builder.add_kw(&kw!(:list/color), TypedValue::typed_string(&color))?;
+            }
+            if let Some(sort_order) = sort_order {
+                builder.add_kw(&kw!(:list/sort_order), TypedValue::Long(sort_order))?;
+            }
+            undo::commit(&mut self.local, builder)?;
         }
-        rows.map(|rows| rows.into_iter().map(|r| self.item_row_to_item(r)).collect())
+        self.fetch_list(&list.uuid)?
+            .ok_or_else(|| ErrorKind::ListNotFound(list.uuid.hyphenated().to_string()).into())
     }
 
-    fn fetch_items(&mut self) -> Result<Items> {
-        //println!("Toodle::fetch_items");
-        let query = r#"[:find ?eid ?uuid ?name
-                        :where
-                        [?eid :todo/uuid ?uuid]
-                        [?eid :todo/name ?name]
+    fn delete_list(&mut self, uuid: &Uuid) -> Result<()> {
+        let list = self.fetch_list(uuid)?
+                       .ok_or_else(|| ErrorKind::ListNotFound(uuid.hyphenated().to_string()))?;
+        let entid = KnownEntid(list.id.clone().ok_or_else(|| ErrorKind::ListNotFound(uuid.hyphenated().to_string()))?.id);
+        // A list's items go with it, along with their reminders, all in one transaction so
+        // that it can be undone in one go. Subtasks always share their parent's list, so
+        // there's nothing outside the list left pointing at what's deleted.
+        let items_query = r#"[:find [?item ...]
+                              :in ?list
+                              :where
+                              [?item :todo/list ?list]
         ]"#;
+        let in_progress = self.mentat.begin_transaction()?;
+        let args = QueryInputs::with_value_sequence(vec![(var!(?list), TypedValue::Ref(entid.0))]);
+        let mut to_delete: Vec<KnownEntid> = vec![];
+        for item in in_progress.q_once(items_query, args).into_coll_result()? {
+            let item: Option<Entity> = item.val().and_then(|v| v.to_inner());
+            if let Some(item) = item {
+                to_delete.push(KnownEntid(item.id));
+                to_delete.extend(reminder_entids(&in_progress, KnownEntid(item.id))?);
+            }
+        }
+        let mut datoms: Vec<(KnownEntid, KnownEntid, TypedValue)> = vec![];
+        for entity in to_delete {
+            for (attribute, value) in entity_datoms(&in_progress, entity)? {
+                datoms.push((entity, attribute, value));
+            }
+        }
 
-        let rows;
-        {
-            let in_progress_read = self.begin_read()?;
-            rows = in_progress_read
-                .q_once(query, None)
-                .into_rel_result()
-                .map_err(|e| e.into());
+        let mut builder = in_progress.builder();
+        for (entity, attribute, value) in datoms {
+            builder.retract(entity, attribute, value)?;
         }
-        rows.map(|rows| Items::new(rows.into_iter().map(|r| self.item_row_to_item(r)).collect()))
+        builder.retract_kw(entid, &kw!(:list/uuid), TypedValue::Uuid(list.uuid))?;
+        builder.retract_kw(entid, &kw!(:list/name), TypedValue::typed_string(&list.name))?;
+        builder.retract_kw(entid, &kw!(:list/color), TypedValue::typed_string(&list.color))?;
+        builder.retract_kw(entid, &kw!(:list/sort_order), TypedValue::Long(list.sort_order))?;
+        undo::commit(&mut self.local, builder).and(Ok(()))
+    }
+
+    fn move_item_to_list(&mut self, uuid: &Uuid, list: &Uuid) -> Result<()> {
+        self.transact(|tx| tx.move_item_to_list(uuid, list))
+    }
+
+    fn fetch_items_with_label(&mut self, label: &Label) -> Result<Vec<Item>> {
+        //println!("Toodle::fetch_items_with_label");
+        self.fetch_items(&ItemQuery::new().with_any_label(vec![label.name.clone()]))
+            .map(|items| items.vec)
+    }
+
+    fn fetch_items(&mut self, query: &ItemQuery) -> Result<Items> {
+        //println!("Toodle::fetch_items");
+        fetch_items_where(&self.begin_read()?, query.to_selection())
+            .map(Items::new)
     }
 
     fn fetch_item(&mut self, uuid: &Uuid) -> Result<Option<Item>> {
         //println!("Toodle::fetch_item");
-        let query = r#"[:find [?eid ?uuid ?name]
-                        :in ?uuid
-                        :where
-                        [?eid :todo/uuid ?uuid]
-                        [?eid :todo/name ?name]
-        ]"#;
-        let rows;
-        {
-            let in_progress_read = self.begin_read()?;
-            let args = QueryInputs::with_value_sequence(vec![(var!(?uuid), uuid.to_typed_value())]);
-            rows = in_progress_read
-                .q_once(query, args)
-                .into_tuple_result()
-                .map(|o| o.map(|r| r))
-                .map_err(|e| e.into());
-        }
-
-        rows.map(|row| row.map(|r| self.item_row_to_item(r)))
+        fetch_item_with_uuid(&self.begin_read()?, uuid)
     }
 
     fn fetch_completion_date_for_item(&mut self, item_id: &Uuid) -> Result<Option<Timespec>> {
@@ -362,36 +1158,7 @@ impl Toodle for Store {
     }
 
     fn create_item(&mut self, item: &Item) -> Result<Uuid> {
-        let item_uuid = create_uuid();
-        log::d(&format!("create_item item_uuid: {:?}", item_uuid));
-        {
-            let in_progress = self.begin_transaction()?;
-            log::d(&format!("create_item in_progress"));
-            let mut builder = in_progress.builder().describe_tempid("item");
-            log::d(&format!("create_item builder"));
-            builder.add_kw(&kw!(:todo/uuid), TypedValue::Uuid(item_uuid))?;
-            log::d(&format!("create_item builder uuid"));
-            builder.add_kw(&kw!(:todo/name), TypedValue::typed_string(&item.name))?;
-            log::d(&format!("create_item builder name"));
-            if let Some(due_date) = item.due_date {
-                builder.add_kw(&kw!(:todo/due_date), due_date.to_typed_value())?;
-                log::d(&format!("create_item builder due_date"));
-            }
-            if let Some(completion_date) = item.completion_date {
-                builder.add_kw(&kw!(:todo/completion_date), completion_date.to_typed_value())?;
-                log::d(&format!("create_item builder completion_date"));
-            }
-
-            for label in item.labels.iter() {
-                builder.add_kw(&kw!(:todo/label), label.id.clone().unwrap().to_typed_value())?;
-                log::d(&format!("create_item builder label"));
-            }
-
-            log::d(&format!("create_item builder pre commit"));
-            builder.commit()?;
-            log::d(&format!("create_item builder post commit"));
-        }
-        Ok(item_uuid)
+        self.transact(|tx| tx.create_item(item))
     }
 
     fn create_and_fetch_item(&mut self, item: &Item) -> Result<Option<Item>> {
@@ -440,72 +1207,497 @@ impl Toodle for Store {
                        completion_date: Option<Timespec>,
                        labels: Option<&Vec<Label>>) -> Result<()> {
         //println!("Toodle::update_item {:?}, {:?}, {:?}", name, due_date, completion_date);
-        let entid = KnownEntid(item.id.to_owned().ok_or_else(|| ErrorKind::ItemNotFound(item.uuid.hyphenated().to_string()))?.id);
-        let existing_labels = self.fetch_labels_for_item(&(item.uuid)).unwrap_or(vec![]);
-        let in_progress = self.begin_transaction()?;
-        let mut builder = in_progress.builder().describe(entid);
+        self.transact(|tx| tx.update_item(item, name, due_date, completion_date, labels))
+    }
 
-        if let Some(name) = name {
-            if item.name != name {
-                builder.add_kw(&kw!(:todo/name), TypedValue::typed_string(&name))?;
-            }
+    fn update_item_notes(&mut self, item: &Item, notes: Option<String>) -> Result<()> {
+        self.transact(|tx| tx.update_item_notes(item, notes))
+    }
+
+    fn update_item_recurrence(&mut self, item: &Item, recurrence: Option<Recurrence>) -> Result<()> {
+        self.transact(|tx| tx.update_item_recurrence(item, recurrence))
+    }
+
+    fn fetch_completions(&mut self, uuid: &Uuid) -> Result<Vec<Timespec>> {
+        let query = r#"[:find [?date ...]
+                        :in ?uuid
+                        :where
+                        [?eid :todo/uuid ?uuid]
+                        [?eid :todo/completions ?date]
+        ]"#;
+        let in_progress_read = self.begin_read()?;
+        let args = QueryInputs::with_value_sequence(vec![(var!(?uuid), uuid.to_typed_value())]);
+        let mut dates: Vec<Timespec> = in_progress_read
+            .q_once(query, args)
+            .into_coll_result()?
+            .into_iter()
+            .map(|binding| column(&[binding], 0))
+            .collect::<Result<_>>()?;
+        dates.sort();
+        Ok(dates)
+    }
+
+    fn fetch_occurrences(&mut self, start: Timespec, end: Timespec) -> Result<Vec<Occurrence>> {
+        let items = self.fetch_items(&ItemQuery::new().completed(false).due_before(end))?;
+        let mut occurrences: Vec<Occurrence> = vec![];
+        for item in items.vec.iter() {
+            let due_date = match item.due_date {
+                Some(due_date) => due_date,
+                None => continue,
+            };
+            let dates: Vec<Timespec> = match item.recurrence {
+                Some(ref recurrence) => recurrence.occurrences(due_date)
+                                                  .skip_while(|date| *date < start)
+                                                  .take_while(|date| *date < end)
+                                                  .collect(),
+                None if due_date >= start => vec![due_date],
+                None => vec![],
+            };
+            occurrences.extend(dates.into_iter().map(|date| Occurrence {
+                item: item.clone(),
+                due_date: date,
+            }));
         }
+        // `sort_by_key` is stable, so items due at the same time stay in creation order.
+        occurrences.sort_by_key(|occurrence| occurrence.due_date);
+        Ok(occurrences)
+    }
+
+    fn delete_item(&mut self, uuid: &Uuid) -> Result<()> {
+        self.transact(|tx| tx.delete_item(uuid))
+    }
 
-        if item.due_date != due_date {
-            let due_date_kw = kw!(:todo/due_date);
-            if let Some(date) = due_date {
-                builder.add_kw(&due_date_kw, date.to_typed_value())?;
-            } else if let Some(date) = item.due_date {
-                builder.retract_kw(&due_date_kw, date.to_typed_value())?;
+    fn delete_items(&mut self, uuids: &[Uuid]) -> Result<()> {
+        self.transact(|tx| tx.delete_items(uuids))
+    }
+
+    fn trash_item(&mut self, uuid: &Uuid) -> Result<()> {
+        let in_progress = self.mentat.begin_transaction()?;
+        let entid = item_entid(&in_progress, uuid)?;
+        if deletion_date(&in_progress, entid.0)?.is_some() {
+            return Ok(());
+        }
+        let mut to_trash = vec![entid.0];
+        let mut pending = child_entids(&in_progress, entid)?;
+        while let Some(child) = pending.pop() {
+            pending.extend(child_entids(&in_progress, KnownEntid(child))?);
+            if deletion_date(&in_progress, child)?.is_none() {
+                to_trash.push(child);
             }
         }
 
-        if item.completion_date != completion_date {
-            let completion_date_kw = kw!(:todo/completion_date);
-            if let Some(date) = completion_date {
-                //println!("Adding completion date");
-                builder.add_kw(&completion_date_kw, date.to_typed_value())?;
-            } else if let Some(date) = item.completion_date {
-                //println!("retracting completion date");
-                builder.retract_kw(&completion_date_kw, date.to_typed_value())?;
-            }
+        let mut builder = in_progress.builder();
+        let deleted_at = time::now_utc().to_timespec();
+        let deleted_at_kw = kw!(:todo/deleted_at);
+        for entid in to_trash {
+            builder.add_kw(KnownEntid(entid), &deleted_at_kw, deleted_at.to_typed_value())?;
         }
+        undo::commit(&mut self.local, builder).and(Ok(()))
+    }
 
-        if let Some(new_labels) = labels {
-            let item_labels_kw = kw!(:todo/label);
-            for label in new_labels {
-                builder.add_kw(&item_labels_kw, TypedValue::Ref(label.id.clone().unwrap().id))?;
-            }
-            for label in existing_labels {
-                if !new_labels.contains(&label) && label.id.is_some() {
-                    builder.retract_kw(&item_labels_kw, TypedValue::Ref(label.id.clone().unwrap().id))?;
-                }
+    fn restore_item(&mut self, uuid: &Uuid) -> Result<()> {
+        let in_progress = self.mentat.begin_transaction()?;
+        let entid = item_entid(&in_progress, uuid)?;
+        let deleted_at = match deletion_date(&in_progress, entid.0)? {
+            Some(deleted_at) => deleted_at,
+            None => return Ok(()),
+        };
+        // Subtasks that were trashed before their parent stay where they are.
+        let mut to_restore = vec![entid.0];
+        let mut pending = child_entids(&in_progress, entid)?;
+        while let Some(child) = pending.pop() {
+            if deletion_date(&in_progress, child)? == Some(deleted_at) {
+                pending.extend(child_entids(&in_progress, KnownEntid(child))?);
+                to_restore.push(child);
             }
         }
-        builder.commit()
-               .map_err(|e| e.into())
-               .and(Ok(()))
+
+        let mut builder = in_progress.builder();
+        let deleted_at_kw = kw!(:todo/deleted_at);
+        for entid in to_restore {
+            builder.retract_kw(KnownEntid(entid), &deleted_at_kw, deleted_at.to_typed_value())?;
+        }
+        undo::commit(&mut self.local, builder).and(Ok(()))
     }
-}
 
-#[cfg(test)]
-mod test {
-    use super::{
-        Item,
-        Label,
-        Toodle,
-        create_uuid,
-    };
+    fn empty_trash(&mut self, older_than: Timespec) -> Result<usize> {
+        let query = r#"[:find [?eid ...]
+                        :in ?older_than
+                        :where
+                        [?eid :todo/deleted_at ?deleted_at]
+                        [(< ?deleted_at ?older_than)]
+        ]"#;
+        let in_progress = self.mentat.begin_transaction()?;
+        let args = QueryInputs::with_value_sequence(vec![(var!(?older_than), older_than.to_typed_value())]);
+        let to_delete: Vec<Entid> = in_progress
+            .q_once(query, args)
+            .into_coll_result()?
+            .into_iter()
+            .filter_map(|binding| {
+                let entity: Option<Entity> = binding.val().and_then(|v| v.to_inner());
+                entity.map(|e| e.id)
+            })
+            .collect();
+        if to_delete.is_empty() {
+            return Ok(0);
+        }
 
-    use time::{
-        now_utc,
-    };
+        // Subtasks that have been restored outlive their parent, as with `delete_item`.
+        undo::commit(&mut self.local, delete_entities(in_progress, &to_delete)?)?;
+        Ok(to_delete.len())
+    }
 
-    use mentat::{
-        Uuid,
+    fn fetch_children(&mut self, uuid: &Uuid) -> Result<Vec<Item>> {
+        self.fetch_items(&ItemQuery::new().subtasks_of(uuid.clone()))
+            .map(|items| items.vec)
+    }
+
+    fn fetch_item_tree(&mut self) -> Result<Vec<ItemNode>> {
+        fetch_items_where(&self.begin_read()?, ItemQuery::new().to_selection())
+            .map(build_item_tree)
+    }
+
+    fn move_item(&mut self, uuid: &Uuid, parent: Option<&Uuid>) -> Result<()> {
+        self.transact(|tx| tx.move_item(uuid, parent))
+    }
+
+    fn reorder_item(&mut self, uuid: &Uuid, previous: Option<&Uuid>, next: Option<&Uuid>) -> Result<()> {
+        let in_progress = self.mentat.begin_transaction()?;
+        let entid = item_entid(&in_progress, uuid)?;
+        let previous = match previous {
+            Some(previous) => Some(item_entid(&in_progress, previous)?),
+            None => None,
+        };
+        let next = match next {
+            Some(next) => Some(item_entid(&in_progress, next)?),
+            None => None,
+        };
+        if previous == Some(entid) || next == Some(entid) {
+            return Ok(());
+        }
+
+        let positions: Vec<(Entid, String)> = item_positions(&in_progress)?
+            .into_iter()
+            .filter(|&(e, _)| e != entid.0)
+            .collect();
+        let position_of = |item: Option<KnownEntid>| {
+            item.and_then(|item| positions.iter().find(|&&(e, _)| e == item.0).map(|&(_, ref p)| p.clone()))
+        };
+        let after = |position: &str| {
+            positions.iter().map(|&(_, ref p)| p).filter(|p| p.as_str() > position).min().cloned()
+        };
+        let before = |position: &str| {
+            positions.iter().map(|&(_, ref p)| p).filter(|p| p.as_str() < position).max().cloned()
+        };
+        let previous_position = position_of(previous);
+        let next_position = position_of(next);
+        let (lower, upper) = match (previous_position, next_position.clone()) {
+            (Some(lower), Some(upper)) if lower < upper => (Some(lower), Some(upper)),
+            (Some(lower), _) => {
+                let upper = after(&lower);
+                (Some(lower), upper)
+            },
+            (None, Some(upper)) => (before(&upper), Some(upper)),
+            (None, None) => (positions.iter().map(|&(_, ref p)| p).max().cloned(), None),
+        };
+        let new_position = position::between(lower.as_ref().map(|p| p.as_str()), upper.as_ref().map(|p| p.as_str()));
+
+        let mut builder = in_progress.builder();
+        let position_kw = kw!(:todo/position);
+        builder.add_kw(entid, &position_kw, TypedValue::typed_string(&new_position))?;
+        // Moves made on different devices can leave `next` at the same position as
+        // `previous`, or before it, in which case it has to move along too.
+        if let (Some(next), Some(next_position)) = (next, next_position) {
+            if next_position <= new_position {
+                let next_position = position::between(Some(new_position.as_str()), upper.as_ref().map(|p| p.as_str()));
+                builder.add_kw(next, &position_kw, TypedValue::typed_string(&next_position))?;
+            }
+        }
+        undo::commit(&mut self.local, builder).and(Ok(()))
+    }
+
+    fn complete_item(&mut self, uuid: &Uuid, completion_date: Timespec, cascade: bool) -> Result<()> {
+        self.transact(|tx| tx.complete_item(uuid, completion_date, cascade))
+    }
+
+    fn complete_items(&mut self, uuids: &[Uuid], completion_date: Timespec) -> Result<()> {
+        self.transact(|tx| tx.complete_items(uuids, completion_date))
+    }
+
+    fn uncomplete_items(&mut self, uuids: &[Uuid]) -> Result<()> {
+        self.transact(|tx| tx.uncomplete_items(uuids))
+    }
+
+    fn set_due_date_for_items(&mut self, uuids: &[Uuid], due_date: Option<Timespec>) -> Result<()> {
+        self.transact(|tx| tx.set_due_date_for_items(uuids, due_date))
+    }
+
+    fn add_label_to_items(&mut self, uuids: &[Uuid], label: &Label) -> Result<()> {
+        self.transact(|tx| tx.add_label_to_items(uuids, label))
+    }
+
+    fn remove_label_from_items(&mut self, uuids: &[Uuid], label: &Label) -> Result<()> {
+        self.transact(|tx| tx.remove_label_from_items(uuids, label))
+    }
+
+    fn search_items(&mut self, text: &str) -> Result<Vec<SearchResult>> {
+        let terms = search::terms(text);
+        if terms.is_empty() {
+            return Ok(vec![]);
+        }
+        let term = search::match_expression(&terms);
+        let name_clauses = r#"[(fulltext $ :todo/name_fulltext ?term) [[?eid _ _ _]]]
+                              (not [?eid :todo/deleted_at _])"#;
+        let notes_clauses = r#"[(fulltext $ :todo/notes ?term) [[?eid _ _ _]]]
+                               (not [?eid :todo/deleted_at _])"#;
+        let mut items = fetch_items_where(&self.begin_read()?, ItemSelection::new(name_clauses, vec![("?term", term.to_typed_value())]))?;
+        for item in fetch_items_where(&self.begin_read()?, ItemSelection::new(notes_clauses, vec![("?term", term.to_typed_value())]))? {
+            if !items.iter().any(|i| i.uuid == item.uuid) {
+                items.push(item);
+            }
+        }
+
+        let mut results: Vec<SearchResult> = items.into_iter().map(|item| {
+            // Matches in the name count for more than matches in the notes, and make for a
+            // better snippet.
+            let name_score = search::score(&item.name, &terms);
+            let notes_score = item.notes.as_ref().map_or(0.0, |notes| search::score(notes, &terms));
+            let snippet = match item.notes {
+                Some(ref notes) if name_score == 0.0 => search::snippet(notes, &terms),
+                _ => search::snippet(&item.name, &terms),
+            };
+            SearchResult {
+                score: name_score + notes_score / 2.0,
+                snippet: snippet,
+                item: item,
+            }
+        }).collect();
+        // `sort_by` is stable, so equally ranked items stay in creation order.
+        results.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(Ordering::Equal));
+        Ok(results)
+    }
+
+    fn add_reminder(&mut self, item: &Uuid, trigger: ReminderTrigger) -> Result<Reminder> {
+        let uuid = create_uuid();
+        {
+            let in_progress = self.mentat.begin_transaction()?;
+            let item = item_entid(&in_progress, item)?;
+            let mut builder = in_progress.builder().describe_tempid("reminder");
+            builder.add_kw(&kw!(:reminder/uuid), TypedValue::Uuid(uuid))?;
+            builder.add_kw(&kw!(:reminder/item), TypedValue::Ref(item.0))?;
+            match trigger {
+                ReminderTrigger::At(date) => builder.add_kw(&kw!(:reminder/at), date.to_typed_value())?,
+                ReminderTrigger::BeforeDue(offset) => builder.add_kw(&kw!(:reminder/offset), TypedValue::Long(offset.num_seconds()))?,
+            }
+            undo::commit(&mut self.local, builder)?;
+        }
+        self.fetch_reminder(&uuid)?
+            .ok_or_else(|| ErrorKind::ReminderNotFound(uuid.hyphenated().to_string()).into())
+    }
+
+    fn fetch_reminder(&mut self, uuid: &Uuid) -> Result<Option<Reminder>> {
+        let clauses = r#"[?r :reminder/uuid ?reminder_uuid]"#;
+        fetch_reminders_where(self, clauses, vec![("?reminder_uuid", uuid.to_typed_value())])
+            .map(|reminders| reminders.into_iter().next())
+    }
+
+    fn fetch_reminders(&mut self, item: &Uuid) -> Result<Vec<Reminder>> {
+        let clauses = r#"[?item :todo/uuid ?uuid]
+                         [?r :reminder/item ?item]"#;
+        fetch_reminders_where(self, clauses, vec![("?uuid", item.to_typed_value())])
+    }
+
+    fn delete_reminder(&mut self, uuid: &Uuid) -> Result<()> {
+        let in_progress = self.mentat.begin_transaction()?;
+        let entid = reminder_entid(&in_progress, uuid)?;
+        let datoms = entity_datoms(&in_progress, entid)?;
+        let mut builder = in_progress.builder().describe(entid);
+        for (attribute, value) in datoms {
+            builder.retract(attribute, value)?;
+        }
+        undo::commit(&mut self.local, builder).and(Ok(()))
+    }
+
+    fn pending_reminders(&mut self, now: Timespec, window: Duration) -> Result<Vec<PendingReminder>> {
+        let deadline = now + window;
+        // Reminders for finished items never go off.
+        let items: HashMap<Uuid, Item> = self.fetch_items(&ItemQuery::new().completed(false))?
+                                             .vec
+                                             .into_iter()
+                                             .map(|item| (item.uuid, item))
+                                             .collect();
+        let mut pending: Vec<PendingReminder> = vec![];
+        for reminder in fetch_reminders_where(self, "[?r :reminder/uuid _]", vec![])? {
+            let item = match items.get(&reminder.item) {
+                Some(item) => item,
+                None => continue,
+            };
+            // Missed reminders are still pending until they're acknowledged.
+            match reminder.fire_at(item.due_date) {
+                Some(fire_at) if fire_at <= deadline => pending.push(PendingReminder {
+                    reminder: reminder,
+                    item: item.clone(),
+                    fire_at: fire_at,
+                }),
+                _ => {},
+            }
+        }
+        pending.sort_by_key(|reminder| reminder.fire_at);
+        Ok(pending)
+    }
+
+    fn acknowledge_reminder(&mut self, uuid: &Uuid) -> Result<()> {
+        let reminder = self.fetch_reminder(uuid)?
+                           .ok_or_else(|| ErrorKind::ReminderNotFound(uuid.hyphenated().to_string()))?;
+        let entid = KnownEntid(reminder.id.clone().ok_or_else(|| ErrorKind::ReminderNotFound(uuid.hyphenated().to_string()))?.id);
+        let due_date = self.fetch_due_date_for_item(&reminder.item)?;
+        let in_progress = self.mentat.begin_transaction()?;
+        let mut builder = in_progress.builder().describe(entid);
+        if let Some(scheduled) = reminder.scheduled_at(due_date) {
+            builder.add_kw(&kw!(:reminder/acknowledged), scheduled.to_typed_value())?;
+        }
+        if let Some(snoozed_until) = reminder.snoozed_until {
+            builder.retract_kw(&kw!(:reminder/snoozed_until), snoozed_until.to_typed_value())?;
+        }
+        undo::commit(&mut self.local, builder).and(Ok(()))
+    }
+
+    fn snooze_reminder(&mut self, uuid: &Uuid, until: Timespec) -> Result<()> {
+        let in_progress = self.mentat.begin_transaction()?;
+        let entid = reminder_entid(&in_progress, uuid)?;
+        let mut builder = in_progress.builder().describe(entid);
+        builder.add_kw(&kw!(:reminder/snoozed_until), until.to_typed_value())?;
+        undo::commit(&mut self.local, builder).and(Ok(()))
+    }
+
+    fn fetch_item_history(&mut self, uuid: &Uuid) -> Result<Vec<ItemChange>> {
+        history::fetch_item_history(self, uuid)
+    }
+
+    fn can_undo(&mut self) -> Result<bool> {
+        undo::can_revert(self, false)
+    }
+
+    fn can_redo(&mut self) -> Result<bool> {
+        undo::can_revert(self, true)
+    }
+
+    fn undo(&mut self) -> Result<bool> {
+        undo::revert(self, false)
+    }
+
+    fn redo(&mut self) -> Result<bool> {
+        undo::revert(self, true)
+    }
+
+    fn register_change_observer<F>(&mut self, key: String, notify: F) -> Result<ChangeObserver>
+        where F: Fn(&str) + Send + Sync + 'static {
+        events::register_observer(self, key, notify)
+    }
+
+    fn take_change_events(&mut self, observer: &ChangeObserver) -> Result<Vec<ChangeEvent>> {
+        events::take_events(self, observer)
+    }
+
+    fn unregister_change_observer(&mut self, observer: ChangeObserver) {
+        events::unregister_observer(self, observer)
+    }
+
+    fn export_json(&mut self) -> Result<String> {
+        json::export_json(self)
+    }
+
+    fn import_json(&mut self, json: &str) -> Result<()> {
+        json::import_json(self, json)
+    }
+
+    fn export_ics(&mut self) -> Result<String> {
+        ical::export_ics(self)
+    }
+
+    fn import_ics(&mut self, ics: &str) -> Result<()> {
+        ical::import_ics(self, ics)
+    }
+
+    fn export_todo_txt(&mut self) -> Result<String> {
+        todotxt::export_todo_txt(self)
+    }
+
+    fn import_todo_txt(&mut self, text: &str) -> Result<()> {
+        todotxt::import_todo_txt(self, text)
+    }
+
+    fn export_taskwarrior(&mut self) -> Result<String> {
+        taskwarrior::export_taskwarrior(self)
+    }
+
+    fn import_taskwarrior(&mut self, json: &str) -> Result<()> {
+        taskwarrior::import_taskwarrior(self, json)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        ChangeEvent,
+        ChangeOrigin,
+        Frequency,
+        Item,
+        ItemOrder,
+        ItemQuery,
+        Label,
+        Recurrence,
+        ReminderTrigger,
+        SortDirection,
         Store,
+        Toodle,
+        create_uuid,
+        item_entid,
+    };
+
+    use errors::{
+        Error,
+        ErrorKind,
+    };
+
+    use local::{
+        Local,
+    };
+
+    use schema;
+
+    use serde_json;
+
+    use undo;
+
+    use std::sync::{
+        Mutex,
+        mpsc,
+    };
+
+    use time::{
+        self,
+        Duration,
+        Timespec,
+        now_utc,
+    };
+
+    use mentat::{
+        IntoResult,
+        Queryable,
+        TypedValue,
+        Uuid,
     };
     use mentat::edn;
+    use mentat::entity_builder::{
+        BuildTerms,
+    };
+    use mentat::vocabulary::{
+        HasVocabularies,
+    };
+    use utils::{
+        ToTypedValue,
+    };
 
     fn toodle() -> Store {
         let mut store = Store::open("").expect("Expected a Toodle");
@@ -601,12 +1793,11 @@ mod test {
 
         let date = now_utc().to_timespec();
         let i = Item {
-            id: None,
-            uuid: Uuid::nil(),
             name: "test item".to_string(),
             due_date: Some(date.clone()),
             completion_date: Some(date.clone()),
-            labels: vec![label, label2]
+            labels: vec![label, label2],
+            ..Item::default()
         };
 
         let item = manager.create_and_fetch_item(&i).expect("expected an item option").expect("expected an item");
@@ -619,6 +1810,28 @@ mod test {
         assert_eq!(item.labels, i.labels);
     }
 
+    #[test]
+    fn test_create_item_with_unsaved_label() {
+        let mut manager = toodle();
+        let unsaved = Label {
+            id: None,
+            name: "unsaved".to_string(),
+            color: "#000000".to_string(),
+        };
+        let mut item = create_named_item(&mut manager, "Buy milk", None);
+        item.labels = vec![unsaved.clone()];
+
+        match manager.create_item(&item) {
+            Err(Error(ErrorKind::LabelWithoutId(ref name), _)) if name == "unsaved" => {},
+            result => panic!("expected a label without id error, got {:?}", result),
+        }
+        match manager.update_item(&item, None, None, None, Some(&vec![unsaved])) {
+            Err(Error(ErrorKind::LabelWithoutId(_), _)) => {},
+            result => panic!("expected a label without id error, got {:?}", result),
+        }
+        assert_eq!(manager.fetch_items(&ItemQuery::new()).expect("expected items").vec.len(), 1);
+    }
+
     #[test]
     fn test_create_item_no_due_date() {
         let mut manager = toodle();
@@ -638,12 +1851,10 @@ mod test {
 
         let date = now_utc().to_timespec();
         let i = Item {
-            id: None,
-            uuid: Uuid::nil(),
             name: "test item".to_string(),
-            due_date: None,
             completion_date: Some(date.clone()),
-            labels: vec![label, label2]
+            labels: vec![label, label2],
+            ..Item::default()
         };
 
         let item = manager.create_and_fetch_item(&i).expect("expected an item option").expect("expected an item");
@@ -674,12 +1885,10 @@ mod test {
 
         let date = now_utc().to_timespec();
         let i = Item {
-            id: None,
-            uuid: Uuid::nil(),
             name: "test item".to_string(),
             due_date: Some(date.clone()),
-            completion_date: None,
-            labels: vec![label, label2]
+            labels: vec![label, label2],
+            ..Item::default()
         };
 
         let item = manager.create_and_fetch_item(&i).expect("expected an item option").expect("expected an item");
@@ -696,12 +1905,9 @@ mod test {
         let mut manager = toodle();
         let label = manager.create_label("label1".to_string(), "#000000".to_string()).expect("expected a label option").unwrap();
         let mut created_item = Item {
-            id: None,
-            uuid: Uuid::nil(),
             name: "test item".to_string(),
-            due_date: None,
-            completion_date: None,
-            labels: vec![label]
+            labels: vec![label],
+            ..Item::default()
         };
 
         created_item.uuid = manager.create_item(&created_item).expect("expected a uuid");
@@ -718,6 +1924,166 @@ mod test {
         assert_eq!(fetched_item, None);
     }
 
+    #[test]
+    fn test_fetch_items() {
+        let mut manager = toodle();
+        let label = manager.create_label("label1".to_string(), "#000000".to_string()).expect("expected a label option").unwrap();
+        let label2 = manager.create_label("label2".to_string(), "#000000".to_string()).expect("expected a label option").unwrap();
+
+        let date = now_utc().to_timespec();
+        let item1 = Item {
+            name: "test item 1".to_string(),
+            due_date: Some(date),
+            labels: vec![label.clone(), label2.clone()],
+            ..Item::default()
+        };
+        let item2 = Item {
+            name: "test item 2".to_string(),
+            completion_date: Some(date),
+            ..Item::default()
+        };
+        let item3 = Item {
+            name: "test item 3".to_string(),
+            labels: vec![label2.clone()],
+            ..Item::default()
+        };
+
+        let item1 = manager.create_and_fetch_item(&item1).expect("expected an item option").expect("expected item1");
+        let item2 = manager.create_and_fetch_item(&item2).expect("expected an item option").expect("expected item2");
+        let item3 = manager.create_and_fetch_item(&item3).expect("expected an item option").expect("expected item3");
+        assert_eq!(item1.labels, vec![label, label2.clone()]);
+        assert!(item1.due_date.is_some());
+        assert_eq!(item1.completion_date, None);
+        assert_eq!(item2.due_date, None);
+        assert!(item2.completion_date.is_some());
+        assert_eq!(item2.labels, vec![]);
+        assert_eq!(item3.labels, vec![label2]);
+
+        let fetched_items = manager.fetch_items(&ItemQuery::new()).expect("expected items");
+        assert_eq!(fetched_items.vec, vec![item1, item2, item3]);
+    }
+
+    fn create_items_for_query(manager: &mut Store) -> (Vec<Item>, Timespec) {
+        let work = manager.create_label("work".to_string(), "#000000".to_string()).expect("expected a label option").unwrap();
+        let home = manager.create_label("home".to_string(), "#000000".to_string()).expect("expected a label option").unwrap();
+
+        let now = now_utc().to_timespec();
+        let items = vec![
+            ("Write report", Some(now + Duration::days(2)), None, vec![work.clone()]),
+            ("Buy milk", Some(now + Duration::days(1)), Some(now), vec![home.clone()]),
+            ("Fix the report template", None, None, vec![work.clone(), home.clone()]),
+            ("Call plumber", Some(now + Duration::days(3)), None, vec![home.clone()]),
+        ];
+        let items = items.into_iter().map(|(name, due_date, completion_date, labels)| {
+            let item = Item {
+                name: name.to_string(),
+                due_date: due_date,
+                completion_date: completion_date,
+                labels: labels,
+                ..Item::default()
+            };
+            manager.create_and_fetch_item(&item).expect("expected an item option").expect("expected an item")
+        }).collect();
+        (items, now)
+    }
+
+    fn names(items: Vec<Item>) -> Vec<String> {
+        items.iter().map(|item| item.name.clone()).collect()
+    }
+
+    #[test]
+    fn test_fetch_items_query_completed() {
+        let mut manager = toodle();
+        let _ = create_items_for_query(&mut manager);
+
+        let completed = manager.fetch_items(&ItemQuery::new().completed(true)).expect("expected items");
+        assert_eq!(names(completed.vec), vec!["Buy milk"]);
+
+        let incomplete = manager.fetch_items(&ItemQuery::new().completed(false)).expect("expected items");
+        assert_eq!(names(incomplete.vec), vec!["Write report", "Fix the report template", "Call plumber"]);
+    }
+
+    #[test]
+    fn test_fetch_items_query_due_dates() {
+        let mut manager = toodle();
+        let (_, now) = create_items_for_query(&mut manager);
+
+        let query = ItemQuery::new()
+            .due_after(now + Duration::days(1))
+            .due_before(now + Duration::days(3))
+            .order_by(ItemOrder::DueDate, SortDirection::Descending);
+        let due = manager.fetch_items(&query).expect("expected items");
+        assert_eq!(names(due.vec), vec!["Write report", "Buy milk"]);
+
+        // Undated items sort last in both directions.
+        let query = ItemQuery::new().order_by(ItemOrder::DueDate, SortDirection::Ascending);
+        let sorted = manager.fetch_items(&query).expect("expected items");
+        assert_eq!(names(sorted.vec), vec!["Buy milk", "Write report", "Call plumber", "Fix the report template"]);
+
+        let query = ItemQuery::new().order_by(ItemOrder::DueDate, SortDirection::Descending);
+        let sorted = manager.fetch_items(&query).expect("expected items");
+        assert_eq!(names(sorted.vec), vec!["Call plumber", "Write report", "Buy milk", "Fix the report template"]);
+    }
+
+    #[test]
+    fn test_fetch_items_query_labels() {
+        let mut manager = toodle();
+        let _ = create_items_for_query(&mut manager);
+
+        let query = ItemQuery::new().with_any_label(vec!["work".to_string(), "home".to_string()]);
+        let any = manager.fetch_items(&query).expect("expected items");
+        assert_eq!(any.vec.len(), 4);
+
+        let query = ItemQuery::new().with_all_labels(vec!["work".to_string(), "home".to_string()]);
+        let all = manager.fetch_items(&query).expect("expected items");
+        assert_eq!(names(all.vec), vec!["Fix the report template"]);
+
+        let query = ItemQuery::new()
+            .with_any_label(vec!["home".to_string()])
+            .order_by(ItemOrder::Name, SortDirection::Ascending);
+        let home = manager.fetch_items(&query).expect("expected items");
+        assert_eq!(names(home.vec), vec!["Buy milk", "Call plumber", "Fix the report template"]);
+    }
+
+    #[test]
+    fn test_fetch_items_query_name_and_limit() {
+        let mut manager = toodle();
+        let _ = create_items_for_query(&mut manager);
+
+        let query = ItemQuery::new().name_contains("REPORT".to_string());
+        let matching = manager.fetch_items(&query).expect("expected items");
+        assert_eq!(names(matching.vec), vec!["Write report", "Fix the report template"]);
+
+        // Text matches anywhere in the name, even in the middle of a word or across words.
+        let query = ItemQuery::new().name_contains("port".to_string());
+        let matching = manager.fetch_items(&query).expect("expected items");
+        assert_eq!(names(matching.vec), vec!["Write report", "Fix the report template"]);
+
+        let query = ItemQuery::new().name_contains("e rep".to_string());
+        let matching = manager.fetch_items(&query).expect("expected items");
+        assert_eq!(names(matching.vec), vec!["Fix the report template"]);
+
+        let query = ItemQuery::new().name_contains("LK".to_string());
+        let matching = manager.fetch_items(&query).expect("expected items");
+        assert_eq!(names(matching.vec), vec!["Buy milk"]);
+
+        let query = ItemQuery::new().name_contains("reports".to_string());
+        assert!(manager.fetch_items(&query).expect("expected items").vec.is_empty());
+
+        let query = ItemQuery::new()
+            .order_by(ItemOrder::Creation, SortDirection::Descending)
+            .limit(2);
+        let page = manager.fetch_items(&query).expect("expected items");
+        assert_eq!(names(page.vec), vec!["Call plumber", "Fix the report template"]);
+
+        let query = ItemQuery::new()
+            .name_contains("report".to_string())
+            .order_by(ItemOrder::Name, SortDirection::Ascending)
+            .limit(1);
+        let page = manager.fetch_items(&query).expect("expected items");
+        assert_eq!(names(page.vec), vec!["Fix the report template"]);
+    }
+
     #[test]
     fn test_fetch_labels_for_item() {
         let mut manager = toodle();
@@ -726,12 +2092,9 @@ mod test {
         let label3 = manager.create_label("label3".to_string(), "#000000".to_string()).expect("expected a label option").unwrap();
 
         let mut item1 = Item {
-            id: None,
-            uuid: Uuid::nil(),
             name: "test item 1".to_string(),
-            due_date: None,
-            completion_date: None,
-            labels: vec![label, label2, label3]
+            labels: vec![label, label2, label3],
+            ..Item::default()
         };
 
         item1.uuid = manager.create_item(&item1).expect("expected a uuid");
@@ -747,37 +2110,25 @@ mod test {
         let label2 = manager.create_label("label2".to_string(), "#000000".to_string()).expect("expected a label option").unwrap();
 
         let item1 = Item {
-            id: None,
-            uuid: Uuid::nil(),
             name: "test item 1".to_string(),
-            due_date: None,
-            completion_date: None,
-            labels: vec![label.clone()]
+            labels: vec![label.clone()],
+            ..Item::default()
         };
         let item2 = Item {
-            id: None,
-            uuid: Uuid::nil(),
             name: "test item 2".to_string(),
-            due_date: None,
-            completion_date: None,
-            labels: vec![label.clone()]
+            labels: vec![label.clone()],
+            ..Item::default()
         };
         let item3 = Item {
-            id: None,
-            uuid: Uuid::nil(),
             name: "test item 3".to_string(),
-            due_date: None,
-            completion_date: None,
-            labels: vec![label.clone(), label2.clone()]
+            labels: vec![label.clone(), label2.clone()],
+            ..Item::default()
         };
 
         let item4 = Item {
-            id: None,
-            uuid: Uuid::nil(),
             name: "test item 4".to_string(),
-            due_date: None,
-            completion_date: None,
-            labels: vec![label2.clone()]
+            labels: vec![label2.clone()],
+            ..Item::default()
         };
 
         let item1 = manager.create_and_fetch_item(&item1).expect("expected an item option").expect("expected item1");
@@ -799,12 +2150,9 @@ mod test {
         let label3 = manager.create_label("label3".to_string(), "#000000".to_string()).expect("expected a label option").unwrap();
 
         let item1 = Item {
-            id: None,
-            uuid: Uuid::nil(),
             name: "test item 1".to_string(),
-            due_date: None,
-            completion_date: None,
-            labels: vec![label, label2]
+            labels: vec![label, label2],
+            ..Item::default()
         };
 
         let mut created_item = manager.create_and_fetch_item(&item1).expect("expected an item option").expect("expected an item");
@@ -822,6 +2170,8 @@ mod test {
         created_item.labels = new_labels;
 
         let fetched_item = manager.fetch_item(&created_item.uuid).expect("expected an item option").expect("expected an item");
+        assert!(fetched_item.modified_at > created_item.modified_at);
+        created_item.modified_at = fetched_item.modified_at;
         assert_eq!(fetched_item, created_item);
     }
 
@@ -834,12 +2184,9 @@ mod test {
         println!("labels created");
 
         let item1 = Item {
-            id: None,
-            uuid: Uuid::nil(),
             name: "test item 1".to_string(),
-            due_date: None,
-            completion_date: None,
-            labels: vec![label, label2, label3]
+            labels: vec![label, label2, label3],
+            ..Item::default()
         };
 
         let mut created_item = manager.create_and_fetch_item(&item1).expect("expected an item option").expect("expected an item");
@@ -861,6 +2208,8 @@ mod test {
         created_item.labels = new_labels;
 
         let fetched_item = manager.fetch_item(&created_item.uuid).expect("expected an item option").expect("expected an item");
+        assert!(fetched_item.modified_at > created_item.modified_at);
+        created_item.modified_at = fetched_item.modified_at;
         assert_eq!(fetched_item, created_item);
     }
 
@@ -873,12 +2222,9 @@ mod test {
 
         let date = now_utc().to_timespec();
         let item1 = Item {
-            id: None,
-            uuid: Uuid::nil(),
             name: "test item 1".to_string(),
-            due_date: None,
-            completion_date: None,
-            labels: vec![label, label2, label3]
+            labels: vec![label, label2, label3],
+            ..Item::default()
         };
 
         let created_item = manager.create_and_fetch_item(&item1).expect("expected an item option").expect("expected an item");
@@ -905,12 +2251,10 @@ mod test {
 
         let date = now_utc().to_timespec();
         let item1 = Item {
-            id: None,
-            uuid: Uuid::nil(),
             name: "test item 1".to_string(),
             due_date: Some(date),
-            completion_date: None,
-            labels: vec![label, label2, label3]
+            labels: vec![label, label2, label3],
+            ..Item::default()
         };
 
         let mut created_item = manager.create_and_fetch_item(&item1).expect("expected an item option").expect("expected an item");
@@ -937,12 +2281,9 @@ mod test {
 
         let date = now_utc().to_timespec();
         let item1 = Item {
-            id: None,
-            uuid: Uuid::nil(),
             name: "test item 1".to_string(),
-            due_date: None,
-            completion_date: None,
-            labels: vec![label, label2, label3]
+            labels: vec![label, label2, label3],
+            ..Item::default()
         };
 
         let created_item = manager.create_and_fetch_item(&item1).expect("expected an item option").expect("expected an item");
@@ -958,4 +2299,1137 @@ mod test {
         let completion_date = fetched_item.completion_date.expect("expected a completion_date");
         assert_eq!(completion_date.sec, date.sec);
     }
+
+    #[test]
+    fn test_delete_item() {
+        let mut manager = toodle();
+        let label = manager.create_label("label1".to_string(), "#000000".to_string()).expect("expected a label option").unwrap();
+
+        let date = now_utc().to_timespec();
+        let item1 = Item {
+            name: "test item 1".to_string(),
+            due_date: Some(date),
+            completion_date: Some(date),
+            labels: vec![label.clone()],
+            ..Item::default()
+        };
+        let item2 = Item {
+            name: "test item 2".to_string(),
+            labels: vec![label.clone()],
+            ..Item::default()
+        };
+
+        let item1 = manager.create_and_fetch_item(&item1).expect("expected an item option").expect("expected item1");
+        let item2 = manager.create_and_fetch_item(&item2).expect("expected an item option").expect("expected item2");
+
+        manager.delete_item(&item1.uuid).expect("expected item to be deleted");
+
+        let fetched_item = manager.fetch_item(&item1.uuid).expect("expected an item option");
+        assert_eq!(fetched_item, None);
+        let fetched_items = manager.fetch_items(&ItemQuery::new()).expect("expected items");
+        assert_eq!(fetched_items.vec, vec![item2.clone()]);
+        let fetched_label_items = manager.fetch_items_with_label(&label).expect("expected a vector of items");
+        assert_eq!(fetched_label_items, vec![item2]);
+
+        // Labels outlive the items they're attached to.
+        let fetched_label = manager.fetch_label(&label.name).expect("expected a label option");
+        assert_eq!(fetched_label, Some(label));
+
+        assert!(manager.delete_item(&item1.uuid).is_err());
+    }
+
+    #[test]
+    fn test_rename_label() {
+        let mut manager = toodle();
+        let label = manager.create_label("label1".to_string(), "#000000".to_string()).expect("expected a label option").unwrap();
+        let label2 = manager.create_label("label2".to_string(), "#000000".to_string()).expect("expected a label option").unwrap();
+        let item = Item {
+            name: "test item".to_string(),
+            labels: vec![label.clone()],
+            ..Item::default()
+        };
+        let item = manager.create_and_fetch_item(&item).expect("expected an item option").expect("expected an item");
+
+        let renamed = manager.rename_label(&label, "renamed".to_string()).expect("expected a label option").expect("expected a label");
+        assert_eq!(renamed.id, label.id);
+        assert_eq!(renamed.name, "renamed");
+        assert_eq!(renamed.color, label.color);
+        assert_eq!(manager.fetch_label(&label.name).expect("expected a label option"), None);
+
+        let fetched_item = manager.fetch_item(&item.uuid).expect("expected an item option").expect("expected an item");
+        assert_eq!(fetched_item.labels, vec![renamed.clone()]);
+
+        match manager.rename_label(&renamed, label2.name.clone()) {
+            Err(Error(ErrorKind::LabelAlreadyExists(name), _)) => assert_eq!(name, label2.name),
+            r => panic!("expected LabelAlreadyExists, got {:?}", r),
+        }
+        assert_eq!(manager.fetch_label(&label2.name).expect("expected a label option"), Some(label2));
+    }
+
+    #[test]
+    fn test_update_label_color() {
+        let mut manager = toodle();
+        let label = manager.create_label("label1".to_string(), "#000000".to_string()).expect("expected a label option").unwrap();
+        let updated = manager.update_label_color(&label, "#ffffff".to_string()).expect("expected a label option").expect("expected a label");
+        assert_eq!(updated.id, label.id);
+        assert_eq!(updated.name, label.name);
+        assert_eq!(updated.color, "#ffffff");
+    }
+
+    #[test]
+    fn test_delete_label() {
+        let mut manager = toodle();
+        let label = manager.create_label("label1".to_string(), "#000000".to_string()).expect("expected a label option").unwrap();
+        let label2 = manager.create_label("label2".to_string(), "#000000".to_string()).expect("expected a label option").unwrap();
+        let item = Item {
+            name: "test item".to_string(),
+            labels: vec![label.clone(), label2.clone()],
+            ..Item::default()
+        };
+        let item = manager.create_and_fetch_item(&item).expect("expected an item option").expect("expected an item");
+
+        manager.delete_label(&label.name).expect("expected label to be deleted");
+
+        assert_eq!(manager.fetch_label(&label.name).expect("expected a label option"), None);
+        assert_eq!(manager.fetch_labels().expect("expected a vector of labels"), vec![label2.clone()]);
+        assert_eq!(manager.fetch_labels_for_item(&item.uuid).expect("expected a vector of labels"), vec![label2]);
+        assert_eq!(manager.fetch_items_with_label(&label).expect("expected a vector of items"), vec![]);
+
+        assert!(manager.delete_label(&label.name).is_err());
+    }
+
+    #[test]
+    fn test_search_items() {
+        let mut manager = toodle();
+        let names = ["Buy milk", "Milk the cow, then bottle the milk", "Buy bread", "Call mum"];
+        for name in names.iter() {
+            let item = Item {
+                name: name.to_string(),
+                ..Item::default()
+            };
+            manager.create_item(&item).expect("expected a uuid");
+        }
+
+        let results = manager.search_items("milk").expect("expected search results");
+        let found: Vec<String> = results.iter().map(|result| result.item.name.clone()).collect();
+        assert_eq!(found, vec!["Milk the cow, then bottle the milk", "Buy milk"]);
+        assert!(results[0].score > results[1].score);
+        assert_eq!(results[1].snippet, "Buy milk");
+
+        // Partially typed words match too.
+        let results = manager.search_items("bu").expect("expected search results");
+        assert_eq!(results.len(), 2);
+
+        assert!(manager.search_items("coffee").expect("expected search results").is_empty());
+        assert!(manager.search_items("  ").expect("expected search results").is_empty());
+    }
+
+    #[test]
+    fn test_search_items_after_rename() {
+        let mut manager = toodle();
+        let item = Item {
+            name: "Buy milk".to_string(),
+            ..Item::default()
+        };
+        let item = manager.create_and_fetch_item(&item).expect("expected an item option").expect("expected an item");
+        manager.update_item(&item, Some("Buy bread".to_string()), None, None, None).expect("expected update to succeed");
+
+        assert!(manager.search_items("milk").expect("expected search results").is_empty());
+        let results = manager.search_items("bread").expect("expected search results");
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].item.uuid, item.uuid);
+    }
+
+    #[test]
+    fn test_upgrade_from_v1() {
+        let mut manager = Store::open("").expect("Expected a store");
+        {
+            let mut in_progress = manager.begin_transaction().expect("expected a transaction");
+            schema::ensure(&mut in_progress, 1).expect("expected version 1 of the vocabulary");
+            in_progress.commit().expect("expected vocabulary to commit");
+        }
+
+        // Write an item the way version 1 did, without a fulltext copy of its name.
+        let label = manager.create_label("label1".to_string(), "#000000".to_string()).expect("expected a label option").unwrap();
+        let date = now_utc().to_timespec();
+        let uuid = create_uuid();
+        {
+            let in_progress = manager.begin_transaction().expect("expected a transaction");
+            let mut builder = in_progress.builder().describe_tempid("item");
+            builder.add_kw(&kw!(:todo/uuid), TypedValue::Uuid(uuid)).expect("expected uuid");
+            builder.add_kw(&kw!(:todo/name), TypedValue::typed_string("Buy milk")).expect("expected name");
+            builder.add_kw(&kw!(:todo/due_date), date.to_typed_value()).expect("expected due date");
+            builder.add_kw(&kw!(:todo/label), label.id.clone().unwrap().to_typed_value()).expect("expected label");
+            builder.commit().expect("expected item to commit");
+        }
+
+        manager.initialize().expect("expected upgrade to work");
+        {
+            let in_progress = manager.begin_transaction().expect("expected a transaction");
+            let vocabulary = in_progress.read_vocabulary_named(&kw!(:toodle/list))
+                                        .expect("expected a vocabulary option")
+                                        .expect("expected a vocabulary");
+            assert_eq!(vocabulary.version, schema::current_version());
+        }
+
+        let items = manager.fetch_items(&ItemQuery::new()).expect("expected items");
+        assert_eq!(items.vec.len(), 1);
+        let item = items.vec[0].clone();
+        assert_eq!(item.uuid, uuid);
+        assert_eq!(item.name, "Buy milk");
+        assert_eq!(item.due_date.expect("expected a due date").sec, date.sec);
+        assert_eq!(item.labels, vec![label]);
+
+        // Version 5 moved the item into the inbox.
+        let lists = manager.fetch_lists().expect("expected lists");
+        assert_eq!(lists.len(), 1);
+        assert_eq!(lists[0].name, "Inbox");
+        assert_eq!(item.list, Some(lists[0].uuid));
+
+        // Version 13 stamped it from the transaction log.
+        assert!(item.created_at.is_some());
+        assert!(item.modified_at >= item.created_at);
+
+        // Version 2 indexed the existing name.
+        let results = manager.search_items("milk").expect("expected search results");
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].item.uuid, uuid);
+
+        // Version 14 indexed its trigrams.
+        let matching = manager.fetch_items(&ItemQuery::new().name_contains("y mil".to_string())).expect("expected items");
+        assert_eq!(matching.vec.len(), 1);
+
+        // Initializing an up-to-date store changes nothing.
+        manager.initialize().expect("expected initialize to work");
+        assert_eq!(manager.fetch_items(&ItemQuery::new()).expect("expected items").vec, vec![item]);
+    }
+
+    #[test]
+    fn test_item_notes() {
+        let mut manager = toodle();
+        let item = Item {
+            name: "Plan trip".to_string(),
+            notes: Some("Book flights.\nFind somewhere to stay near the harbour.".to_string()),
+            ..Item::default()
+        };
+        let created_item = manager.create_and_fetch_item(&item).expect("expected an item option").expect("expected an item");
+        assert_eq!(created_item.notes, item.notes);
+
+        let results = manager.search_items("harbour").expect("expected search results");
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].item.uuid, created_item.uuid);
+        assert!(results[0].snippet.contains("harbour"));
+
+        manager.update_item_notes(&created_item, Some("Just book flights.".to_string())).expect("expected notes to update");
+        let updated_item = manager.fetch_item(&created_item.uuid).expect("expected an item option").expect("expected an item");
+        assert_eq!(updated_item.notes, Some("Just book flights.".to_string()));
+        assert!(manager.search_items("harbour").expect("expected search results").is_empty());
+
+        manager.update_item_notes(&updated_item, None).expect("expected notes to be cleared");
+        let cleared_item = manager.fetch_item(&created_item.uuid).expect("expected an item option").expect("expected an item");
+        assert_eq!(cleared_item.notes, None);
+        assert_eq!(cleared_item.name, created_item.name);
+    }
+
+    fn create_named_item(manager: &mut Store, name: &str, parent: Option<Uuid>) -> Item {
+        let item = Item {
+            name: name.to_string(),
+            parent: parent,
+            ..Item::default()
+        };
+        manager.create_and_fetch_item(&item).expect("expected an item option").expect("expected an item")
+    }
+
+    #[test]
+    fn test_subtasks() {
+        let mut manager = toodle();
+        let trip = create_named_item(&mut manager, "Plan trip", None);
+        let flights = create_named_item(&mut manager, "Book flights", Some(trip.uuid));
+        let seats = create_named_item(&mut manager, "Pick seats", Some(flights.uuid));
+        let hotel = create_named_item(&mut manager, "Book hotel", Some(trip.uuid));
+        let groceries = create_named_item(&mut manager, "Buy groceries", None);
+
+        assert_eq!(flights.parent, Some(trip.uuid));
+        let trip = manager.fetch_item(&trip.uuid).expect("expected an item option").expect("expected an item");
+        assert_eq!(trip.parent, None);
+        assert_eq!(trip.child_count, 2);
+
+        let children: Vec<Uuid> = manager.fetch_children(&trip.uuid).expect("expected children").iter().map(|item| item.uuid).collect();
+        assert_eq!(children, vec![flights.uuid, hotel.uuid]);
+
+        let top_level: Vec<Uuid> = manager.fetch_items(&ItemQuery::new().top_level_only()).expect("expected items").vec.iter().map(|item| item.uuid).collect();
+        assert_eq!(top_level, vec![trip.uuid, groceries.uuid]);
+
+        let tree = manager.fetch_item_tree().expect("expected a tree");
+        assert_eq!(tree.len(), 2);
+        assert_eq!(tree[0].item.uuid, trip.uuid);
+        assert_eq!(tree[0].children.len(), 2);
+        assert_eq!(tree[0].children[0].children[0].item.uuid, seats.uuid);
+        assert!(tree[1].children.is_empty());
+
+        for (uuid, parent) in vec![(trip.uuid, seats.uuid), (trip.uuid, trip.uuid)] {
+            match manager.move_item(&uuid, Some(&parent)) {
+                Err(Error(ErrorKind::ItemCycle(_), _)) => {},
+                r => panic!("expected ItemCycle, got {:?}", r),
+            }
+        }
+
+        manager.move_item(&hotel.uuid, Some(&groceries.uuid)).expect("expected move to succeed");
+        manager.move_item(&seats.uuid, None).expect("expected move to succeed");
+        let hotel = manager.fetch_item(&hotel.uuid).expect("expected an item option").expect("expected an item");
+        assert_eq!(hotel.parent, Some(groceries.uuid));
+        let seats = manager.fetch_item(&seats.uuid).expect("expected an item option").expect("expected an item");
+        assert_eq!(seats.parent, None);
+        assert_eq!(manager.fetch_item(&flights.uuid).expect("expected an item option").expect("expected an item").child_count, 0);
+    }
+
+    #[test]
+    fn test_complete_item_cascades() {
+        let mut manager = toodle();
+        let trip = create_named_item(&mut manager, "Plan trip", None);
+        let flights = create_named_item(&mut manager, "Book flights", Some(trip.uuid));
+        let seats = create_named_item(&mut manager, "Pick seats", Some(flights.uuid));
+        let hotel = create_named_item(&mut manager, "Book hotel", Some(trip.uuid));
+
+        let earlier = Timespec::new(1_500_000_000, 0);
+        let now = Timespec::new(1_600_000_000, 0);
+        manager.complete_item(&hotel.uuid, earlier, false).expect("expected completion");
+        manager.complete_item(&flights.uuid, now, false).expect("expected completion");
+        assert_eq!(manager.fetch_completion_date_for_item(&seats.uuid).expect("expected a date option"), None);
+
+        manager.complete_item(&trip.uuid, now, true).expect("expected completion");
+        assert_eq!(manager.fetch_completion_date_for_item(&trip.uuid).expect("expected a date option"), Some(now));
+        assert_eq!(manager.fetch_completion_date_for_item(&seats.uuid).expect("expected a date option"), Some(now));
+        assert_eq!(manager.fetch_completion_date_for_item(&hotel.uuid).expect("expected a date option"), Some(earlier));
+    }
+
+    #[test]
+    fn test_complete_recurring_item_cascades() {
+        let mut manager = toodle();
+        let due = Timespec::new(1_517_389_200, 0);
+        let week = 7 * 86_400;
+        let item = Item {
+            name: "Clean the house".to_string(),
+            due_date: Some(due),
+            recurrence: Some(Recurrence::new(Frequency::Weekly)),
+            ..Item::default()
+        };
+        let house = manager.create_and_fetch_item(&item).expect("expected an item option").expect("expected an item");
+        let kitchen = create_named_item(&mut manager, "Clean the kitchen", Some(house.uuid));
+        let garage = create_named_item(&mut manager, "Clean the garage", Some(house.uuid));
+        manager.trash_item(&garage.uuid).expect("expected trash to succeed");
+
+        // The parent rolls on to its next occurrence, while its subtasks that aren't in the
+        // trash are completed.
+        let done = Timespec::new(due.sec + 3600, 0);
+        manager.complete_item(&house.uuid, done, true).expect("expected completion");
+        let house = manager.fetch_item(&house.uuid).expect("expected an item option").expect("expected an item");
+        assert_eq!(house.completion_date, None);
+        assert_eq!(house.due_date, Some(Timespec::new(due.sec + week, 0)));
+        assert_eq!(manager.fetch_completions(&house.uuid).expect("expected completions"), vec![done]);
+        assert_eq!(manager.fetch_completion_date_for_item(&kitchen.uuid).expect("expected a date option"), Some(done));
+        assert_eq!(manager.fetch_completion_date_for_item(&garage.uuid).expect("expected a date option"), None);
+    }
+
+    #[test]
+    fn test_delete_item_keeps_subtasks() {
+        let mut manager = toodle();
+        let trip = create_named_item(&mut manager, "Plan trip", None);
+        let flights = create_named_item(&mut manager, "Book flights", Some(trip.uuid));
+        let seats = create_named_item(&mut manager, "Pick seats", Some(flights.uuid));
+
+        manager.delete_item(&flights.uuid).expect("expected delete to succeed");
+        let seats = manager.fetch_item(&seats.uuid).expect("expected an item option").expect("expected an item");
+        assert_eq!(seats.parent, Some(trip.uuid));
+
+        manager.delete_item(&trip.uuid).expect("expected delete to succeed");
+        let seats = manager.fetch_item(&seats.uuid).expect("expected an item option").expect("expected an item");
+        assert_eq!(seats.parent, None);
+    }
+
+    #[test]
+    fn test_trash() {
+        let mut manager = toodle();
+        let label = manager.create_label("travel".to_string(), "#00ff00".to_string()).expect("expected a label option").expect("expected a label");
+        let trip = create_named_item(&mut manager, "Plan trip", None);
+        let flights = create_named_item(&mut manager, "Book flights", Some(trip.uuid));
+        let hotel = create_named_item(&mut manager, "Book hotel", Some(trip.uuid));
+        manager.update_item(&trip, None, None, None, Some(&vec![label.clone()])).expect("expected update to succeed");
+
+        // Trashing an item takes its subtasks with it, and leaves them all out of fetches.
+        manager.trash_item(&hotel.uuid).expect("expected trash to succeed");
+        assert_eq!(manager.fetch_item(&trip.uuid).expect("expected an item option").expect("expected an item").child_count, 1);
+        manager.trash_item(&trip.uuid).expect("expected trash to succeed");
+        assert!(manager.fetch_items(&ItemQuery::new()).expect("expected items").vec.is_empty());
+        assert!(manager.fetch_items_with_label(&label).expect("expected items").is_empty());
+        assert!(manager.fetch_children(&trip.uuid).expect("expected children").is_empty());
+        let trashed_children = manager.fetch_items(&ItemQuery::new().subtasks_of(trip.uuid).including_trashed()).expect("expected items");
+        assert_eq!(names(trashed_children.vec), vec!["Book flights", "Book hotel"]);
+        let trashed_with_label = manager.fetch_items(&ItemQuery::new().with_any_label(vec!["travel".to_string()]).including_trashed()).expect("expected items");
+        assert_eq!(names(trashed_with_label.vec), vec!["Plan trip"]);
+        assert!(manager.search_items("trip").expect("expected results").is_empty());
+        let trashed = manager.fetch_items(&ItemQuery::new().trashed_only()).expect("expected items");
+        assert_eq!(names(trashed.vec), vec!["Plan trip", "Book flights", "Book hotel"]);
+        let trip_deleted_at = manager.fetch_item(&trip.uuid).expect("expected an item option").expect("expected an item").deleted_at;
+        assert!(trip_deleted_at.is_some());
+        let hotel = manager.fetch_item(&hotel.uuid).expect("expected an item option").expect("expected an item");
+        assert!(hotel.deleted_at.is_some() && hotel.deleted_at != trip_deleted_at);
+
+        // Restoring brings back the subtasks trashed along with it, but not those trashed
+        // before.
+        manager.restore_item(&trip.uuid).expect("expected restore to succeed");
+        assert_eq!(names(manager.fetch_items(&ItemQuery::new()).expect("expected items").vec), vec!["Plan trip", "Book flights"]);
+        assert_eq!(manager.fetch_items(&ItemQuery::new().including_trashed()).expect("expected items").vec.len(), 3);
+        assert_eq!(manager.fetch_item(&flights.uuid).expect("expected an item option").expect("expected an item").deleted_at, None);
+
+        // Emptying the trash only deletes what's been there long enough.
+        let hotel_deleted_at = hotel.deleted_at.expect("expected a deletion date");
+        assert_eq!(manager.empty_trash(hotel_deleted_at).expect("expected to empty the trash"), 0);
+        manager.trash_item(&flights.uuid).expect("expected trash to succeed");
+        manager.restore_item(&flights.uuid).expect("expected restore to succeed");
+        assert_eq!(manager.empty_trash(now_utc().to_timespec() + Duration::seconds(1)).expect("expected to empty the trash"), 1);
+        assert_eq!(manager.fetch_item(&hotel.uuid).expect("expected an item option"), None);
+        assert_eq!(names(manager.fetch_items(&ItemQuery::new().including_trashed()).expect("expected items").vec), vec!["Plan trip", "Book flights"]);
+    }
+
+    #[test]
+    fn test_reorder_item() {
+        let mut manager = toodle();
+        let a = create_named_item(&mut manager, "A", None);
+        let b = create_named_item(&mut manager, "B", None);
+        let c = create_named_item(&mut manager, "C", None);
+        let d = create_named_item(&mut manager, "D", None);
+        let order = |manager: &mut Store| names(manager.fetch_items(&ItemQuery::new().top_level_only()).expect("expected items").vec);
+        assert_eq!(order(&mut manager), vec!["A", "B", "C", "D"]);
+
+        manager.reorder_item(&d.uuid, Some(&a.uuid), Some(&b.uuid)).expect("expected reorder to succeed");
+        assert_eq!(order(&mut manager), vec!["A", "D", "B", "C"]);
+        manager.reorder_item(&a.uuid, Some(&c.uuid), None).expect("expected reorder to succeed");
+        assert_eq!(order(&mut manager), vec!["D", "B", "C", "A"]);
+        manager.reorder_item(&c.uuid, None, Some(&d.uuid)).expect("expected reorder to succeed");
+        assert_eq!(order(&mut manager), vec!["C", "D", "B", "A"]);
+        manager.reorder_item(&d.uuid, None, None).expect("expected reorder to succeed");
+        assert_eq!(order(&mut manager), vec!["C", "B", "A", "D"]);
+
+        // Subtasks come back in the same order.
+        let e = create_named_item(&mut manager, "E", Some(c.uuid));
+        let f = create_named_item(&mut manager, "F", Some(c.uuid));
+        manager.reorder_item(&f.uuid, None, Some(&e.uuid)).expect("expected reorder to succeed");
+        assert_eq!(names(manager.fetch_children(&c.uuid).expect("expected children")), vec!["F", "E"]);
+
+        // Moves on two devices can leave items with the same position once they sync.
+        // Putting another item between them moves the second one along.
+        let b_position = manager.fetch_item(&b.uuid).expect("expected an item option").expect("expected an item").position.expect("expected a position");
+        let json = format!(r#"{{"version": 1, "todos": {{"{}": {{"name": "A", "position": "{}"}}}}}}"#, a.uuid.hyphenated(), b_position);
+        manager.import_json(&json).expect("expected import to succeed");
+        manager.reorder_item(&d.uuid, Some(&b.uuid), Some(&a.uuid)).expect("expected reorder to succeed");
+        assert_eq!(order(&mut manager), vec!["C", "B", "D", "A"]);
+    }
+
+    #[test]
+    fn test_bulk_operations() {
+        let mut manager = toodle();
+        let label = manager.create_label("work".to_string(), "#00ff00".to_string()).expect("expected a label option").expect("expected a label");
+        let report = create_named_item(&mut manager, "Write report", None);
+        let slides = create_named_item(&mut manager, "Make slides", None);
+        let milk = create_named_item(&mut manager, "Buy milk", None);
+        let work = vec![report.uuid, slides.uuid];
+        let incomplete = |manager: &mut Store| names(manager.fetch_items(&ItemQuery::new().completed(false)).expect("expected items").vec);
+
+        manager.add_label_to_items(&work, &label).expect("expected labels to be added");
+        assert_eq!(names(manager.fetch_items_with_label(&label).expect("expected items")), vec!["Write report", "Make slides"]);
+        let due = Timespec::new(1_517_389_200, 0);
+        manager.set_due_date_for_items(&work, Some(due)).expect("expected due dates to be set");
+        assert_eq!(manager.fetch_item(&slides.uuid).expect("expected an item option").expect("expected an item").due_date, Some(due));
+
+        let now = now_utc().to_timespec();
+        manager.complete_items(&work, now).expect("expected items to be completed");
+        assert_eq!(incomplete(&mut manager), vec!["Buy milk"]);
+        // Each batch is a single change, so it's undone all at once.
+        assert!(manager.undo().expect("expected undo to succeed"));
+        assert_eq!(incomplete(&mut manager), vec!["Write report", "Make slides", "Buy milk"]);
+        manager.complete_items(&work, now).expect("expected items to be completed");
+        manager.uncomplete_items(&[report.uuid]).expect("expected items to be uncompleted");
+        assert_eq!(incomplete(&mut manager), vec!["Write report", "Buy milk"]);
+
+        manager.remove_label_from_items(&work, &label).expect("expected labels to be removed");
+        assert!(manager.fetch_items_with_label(&label).expect("expected items").is_empty());
+
+        // Nothing is deleted if any of the items isn't there.
+        match manager.delete_items(&[milk.uuid, Uuid::new_v4()]) {
+            Err(Error(ErrorKind::ItemNotFound(_), _)) => {},
+            result => panic!("expected an item not found error, got {:?}", result),
+        }
+        manager.delete_items(&work).expect("expected delete to succeed");
+        assert_eq!(names(manager.fetch_items(&ItemQuery::new()).expect("expected items").vec), vec!["Buy milk"]);
+    }
+
+    #[test]
+    fn test_transact() {
+        let mut manager = toodle();
+        let errand = Item {
+            name: "Post a letter".to_string(),
+            ..Item::default()
+        };
+
+        // Everything made in the transaction is there once it's committed.
+        let (label, uuid) = manager.transact(|tx| {
+            let label = tx.create_label("errands".to_string(), "#ff0000".to_string())?.expect("expected a label");
+            let uuid = tx.create_item(&errand)?;
+            // Each change sees the ones before it.
+            let item = tx.fetch_item(&uuid)?.expect("expected an item");
+            tx.update_item(&item, None, None, None, Some(&vec![label.clone()]))?;
+            Ok((label, uuid))
+        }).expect("expected transact to succeed");
+        let item = manager.fetch_item(&uuid).expect("expected an item option").expect("expected an item");
+        assert_eq!(item.labels, vec![label.clone()]);
+
+        // A failing closure leaves nothing behind.
+        let mut created = None;
+        let result: Result<(), Error> = manager.transact(|tx| {
+            created = Some(tx.create_item(&errand)?);
+            tx.rename_label(&label, "chores".to_string())?;
+            tx.delete_item(&uuid)?;
+            Err(ErrorKind::LabelNotFound("missing".to_string()).into())
+        });
+        match result {
+            Err(Error(ErrorKind::LabelNotFound(_), _)) => {},
+            x => panic!("expected LabelNotFound, got {:?}", x),
+        }
+        assert_eq!(manager.fetch_item(&created.expect("expected an item to have been created")).expect("expected an item option"), None);
+        assert_eq!(manager.fetch_item(&uuid).expect("expected an item option"), Some(item.clone()));
+        assert_eq!(manager.fetch_label(&"errands".to_string()).expect("expected a label option"), Some(label.clone()));
+        assert_eq!(manager.fetch_label(&"chores".to_string()).expect("expected a label option"), None);
+
+        // So does one whose changes fail part way through.
+        let result = manager.transact(|tx| {
+            tx.create_label("chores".to_string(), "#00ff00".to_string())?;
+            tx.move_item(&uuid, Some(&uuid))
+        });
+        assert!(result.is_err());
+        assert_eq!(manager.fetch_label(&"chores".to_string()).expect("expected a label option"), None);
+
+        // A committed transaction is undone in one go.
+        assert!(manager.undo().expect("expected undo to succeed"));
+        assert_eq!(manager.fetch_item(&uuid).expect("expected an item option"), None);
+        assert_eq!(manager.fetch_label(&label.name).expect("expected a label option"), None);
+        assert!(!manager.can_undo().expect("expected undo state"));
+        assert!(manager.redo().expect("expected redo to succeed"));
+        assert_eq!(manager.fetch_item(&uuid).expect("expected an item option"), Some(item));
+    }
+
+    #[test]
+    fn test_lists() {
+        let mut manager = toodle();
+        // New stores start with an inbox.
+        let lists = manager.fetch_lists().expect("expected lists");
+        assert_eq!(lists.len(), 1);
+        let inbox = lists[0].clone();
+        assert_eq!(inbox.name, "Inbox");
+        let work = manager.create_list("Work".to_string(), "#0000FF".to_string()).expect("expected a list");
+        let home = manager.create_list("Home".to_string(), "#00FF00".to_string()).expect("expected a list");
+        assert_eq!(work.name, "Work");
+        assert!(home.sort_order > work.sort_order);
+        assert_eq!(manager.fetch_lists().expect("expected lists"), vec![inbox.clone(), work.clone(), home.clone()]);
+
+        // Moving a list forward reorders them.
+        let home = manager.update_list(&home, Some("House".to_string()), None, Some(work.sort_order - 1)).expect("expected a list");
+        assert_eq!(home.name, "House");
+        assert_eq!(home.color, "#00FF00");
+        assert_eq!(manager.fetch_lists().expect("expected lists"), vec![inbox.clone(), home.clone(), work.clone()]);
+
+        let report = Item {
+            name: "Write report".to_string(),
+            list: Some(work.uuid),
+            ..Item::default()
+        };
+        let report = manager.create_and_fetch_item(&report).expect("expected an item option").expect("expected an item");
+        assert_eq!(report.list, Some(work.uuid));
+        // Subtasks go in their parent's list, and everything else in the inbox.
+        let outline = create_named_item(&mut manager, "Outline report", Some(report.uuid));
+        assert_eq!(outline.list, Some(work.uuid));
+        let unfiled = create_named_item(&mut manager, "Fix the sink", None);
+        assert_eq!(unfiled.list, Some(inbox.uuid));
+
+        let in_work: Vec<Uuid> = manager.fetch_items(&ItemQuery::new().in_list(work.uuid)).expect("expected items").vec.iter().map(|item| item.uuid).collect();
+        assert_eq!(in_work, vec![report.uuid]);
+
+        manager.move_item_to_list(&report.uuid, &home.uuid).expect("expected move to succeed");
+        manager.move_item_to_list(&unfiled.uuid, &home.uuid).expect("expected move to succeed");
+        let in_home: Vec<Uuid> = manager.fetch_items(&ItemQuery::new().in_list(home.uuid)).expect("expected items").vec.iter().map(|item| item.uuid).collect();
+        assert_eq!(in_home.len(), 3);
+        assert!(in_home.contains(&outline.uuid));
+        assert!(manager.fetch_items(&ItemQuery::new().in_list(work.uuid)).expect("expected items").vec.is_empty());
+
+        // Moving an item under a parent in another list brings its subtasks along.
+        let sink = create_named_item(&mut manager, "Call a plumber", None);
+        let quote = create_named_item(&mut manager, "Get a quote", Some(sink.uuid));
+        manager.move_item(&sink.uuid, Some(&unfiled.uuid)).expect("expected move to succeed");
+        let quote = manager.fetch_item(&quote.uuid).expect("expected an item option").expect("expected an item");
+        assert_eq!(quote.list, Some(home.uuid));
+
+        // Moving a subtask to another list takes it out from under its parent.
+        manager.move_item_to_list(&quote.uuid, &work.uuid).expect("expected move to succeed");
+        let quote = manager.fetch_item(&quote.uuid).expect("expected an item option").expect("expected an item");
+        assert_eq!(quote.list, Some(work.uuid));
+        assert_eq!(quote.parent, None);
+
+        manager.delete_list(&home.uuid).expect("expected delete to succeed");
+        assert_eq!(manager.fetch_lists().expect("expected lists"), vec![inbox.clone(), work.clone()]);
+        assert_eq!(manager.fetch_item(&report.uuid).expect("expected an item option"), None);
+        assert_eq!(manager.fetch_item(&unfiled.uuid).expect("expected an item option"), None);
+        assert_eq!(manager.fetch_item(&sink.uuid).expect("expected an item option"), None);
+        assert!(manager.fetch_item(&quote.uuid).expect("expected an item option").is_some());
+
+        match manager.delete_list(&home.uuid) {
+            Err(Error(ErrorKind::ListNotFound(_), _)) => {},
+            r => panic!("expected ListNotFound, got {:?}", r),
+        }
+
+        // With every list gone, new items start a new inbox.
+        manager.delete_list(&inbox.uuid).expect("expected delete to succeed");
+        manager.delete_list(&work.uuid).expect("expected delete to succeed");
+        let chore = create_named_item(&mut manager, "Water the plants", None);
+        let lists = manager.fetch_lists().expect("expected lists");
+        assert_eq!(lists.len(), 1);
+        assert_eq!(lists[0].name, "Inbox");
+        assert_eq!(chore.list, Some(lists[0].uuid));
+    }
+
+    #[test]
+    fn test_recurring_item() {
+        let mut manager = toodle();
+        let due = Timespec::new(1_517_389_200, 0);
+        let week = 7 * 86_400;
+        let mut recurrence = Recurrence::new(Frequency::Weekly);
+        recurrence.count = Some(2);
+        let item = Item {
+            name: "Take out the bins".to_string(),
+            due_date: Some(due),
+            recurrence: Some(recurrence.clone()),
+            ..Item::default()
+        };
+        let item = manager.create_and_fetch_item(&item).expect("expected an item option").expect("expected an item");
+        assert_eq!(item.recurrence, Some(recurrence));
+
+        // Completing the first occurrence rolls the item on to the next.
+        let first_done = Timespec::new(due.sec + 3600, 0);
+        manager.update_item(&item, None, item.due_date, Some(first_done), None).expect("expected update to succeed");
+        let item = manager.fetch_item(&item.uuid).expect("expected an item option").expect("expected an item");
+        assert_eq!(item.completion_date, None);
+        assert_eq!(item.due_date, Some(Timespec::new(due.sec + week, 0)));
+        assert_eq!(item.recurrence.as_ref().and_then(|r| r.count), Some(1));
+
+        // The last one completes it for good.
+        let second_done = Timespec::new(due.sec + week + 3600, 0);
+        manager.update_item(&item, None, item.due_date, Some(second_done), None).expect("expected update to succeed");
+        let item = manager.fetch_item(&item.uuid).expect("expected an item option").expect("expected an item");
+        assert_eq!(item.completion_date, Some(second_done));
+        assert_eq!(item.due_date, Some(Timespec::new(due.sec + week, 0)));
+        assert_eq!(manager.fetch_completions(&item.uuid).expect("expected completions"), vec![first_done, second_done]);
+    }
+
+    #[test]
+    fn test_fetch_occurrences() {
+        let mut manager = toodle();
+        let due = Timespec::new(1_517_389_200, 0);
+        let day = 86_400;
+        let daily = create_named_item(&mut manager, "Water the plants", None);
+        manager.update_item(&daily, None, Some(due), None, None).expect("expected update to succeed");
+        let daily = manager.fetch_item(&daily.uuid).expect("expected an item option").expect("expected an item");
+        // Due on a Wednesday, so a rule for every seventh day on Mondays never comes around.
+        let never: Recurrence = "FREQ=DAILY;INTERVAL=7;BYDAY=MO".parse().expect("expected a rule");
+        match manager.update_item_recurrence(&daily, Some(never)) {
+            Err(Error(ErrorKind::InvalidRecurrence(_), _)) => {},
+            result => panic!("expected an invalid recurrence, got {:?}", result),
+        }
+        manager.update_item_recurrence(&daily, Some(Recurrence::new(Frequency::Daily))).expect("expected update to succeed");
+        let once = create_named_item(&mut manager, "Renew passport", None);
+        manager.update_item(&once, None, Some(Timespec::new(due.sec + day + 60, 0)), None, None).expect("expected update to succeed");
+        create_named_item(&mut manager, "Someday", None);
+
+        let occurrences: Vec<(String, i64)> = manager
+            .fetch_occurrences(Timespec::new(due.sec + day, 0), Timespec::new(due.sec + 3 * day, 0))
+            .expect("expected occurrences")
+            .into_iter()
+            .map(|occurrence| (occurrence.item.name.clone(), occurrence.due_date.sec))
+            .collect();
+        assert_eq!(occurrences, vec![
+            ("Water the plants".to_string(), due.sec + day),
+            ("Renew passport".to_string(), due.sec + day + 60),
+            ("Water the plants".to_string(), due.sec + 2 * day),
+        ]);
+    }
+
+    #[test]
+    fn test_reminders() {
+        let mut manager = toodle();
+        let now = Timespec::new(1_517_389_200, 0);
+        let item = create_named_item(&mut manager, "Call the dentist", None);
+        manager.update_item(&item, None, Some(now + Duration::hours(2)), None, None).expect("expected update to succeed");
+
+        let at = manager.add_reminder(&item.uuid, ReminderTrigger::At(now + Duration::minutes(30))).expect("expected a reminder");
+        let before = manager.add_reminder(&item.uuid, ReminderTrigger::BeforeDue(Duration::hours(1))).expect("expected a reminder");
+        assert_eq!(manager.fetch_reminders(&item.uuid).expect("expected reminders").len(), 2);
+        assert_eq!(manager.fetch_reminder(&before.uuid).expect("expected a reminder option"), Some(before.clone()));
+
+        let pending = manager.pending_reminders(now, Duration::minutes(45)).expect("expected pending reminders");
+        assert_eq!(pending.iter().map(|p| p.reminder.uuid).collect::<Vec<_>>(), vec![at.uuid]);
+        assert_eq!(pending[0].item.uuid, item.uuid);
+
+        // Snoozing pushes the reminder out of the window; acknowledging silences it.
+        manager.snooze_reminder(&at.uuid, now + Duration::hours(3)).expect("expected snooze to succeed");
+        let pending = manager.pending_reminders(now, Duration::hours(1)).expect("expected pending reminders");
+        assert_eq!(pending.iter().map(|p| p.reminder.uuid).collect::<Vec<_>>(), vec![before.uuid]);
+        assert_eq!(pending[0].fire_at, now + Duration::hours(1));
+
+        manager.acknowledge_reminder(&before.uuid).expect("expected acknowledge to succeed");
+        manager.acknowledge_reminder(&at.uuid).expect("expected acknowledge to succeed");
+        assert!(manager.pending_reminders(now, Duration::days(1)).expect("expected pending reminders").is_empty());
+
+        // Moving the due date re-arms the relative reminder.
+        manager.update_item(&item, None, Some(now + Duration::days(1)), None, None).expect("expected update to succeed");
+        let pending = manager.pending_reminders(now, Duration::days(1)).expect("expected pending reminders");
+        assert_eq!(pending.iter().map(|p| p.reminder.uuid).collect::<Vec<_>>(), vec![before.uuid]);
+
+        manager.delete_reminder(&at.uuid).expect("expected delete to succeed");
+        assert_eq!(manager.fetch_reminder(&at.uuid).expect("expected a reminder option"), None);
+        match manager.snooze_reminder(&at.uuid, now) {
+            Err(Error(ErrorKind::ReminderNotFound(_), _)) => {},
+            _ => panic!("expected a ReminderNotFound error"),
+        }
+
+        // Reminders are deleted with their item.
+        manager.delete_item(&item.uuid).expect("expected delete to succeed");
+        assert_eq!(manager.fetch_reminder(&before.uuid).expect("expected a reminder option"), None);
+    }
+
+    #[test]
+    fn test_undo_redo() {
+        let mut manager = toodle();
+        assert!(!manager.can_undo().expect("expected undo state"));
+        assert!(!manager.undo().expect("expected undo to succeed"));
+
+        let label = manager.create_label("errands".to_string(), "#ff0000".to_string()).expect("expected a label option").expect("expected a label");
+        let mut item = create_named_item(&mut manager, "Buy milk", None);
+        manager.update_item(&item, None, None, None, Some(&vec![label.clone()])).expect("expected update to succeed");
+        item = manager.fetch_item(&item.uuid).expect("expected an item option").expect("expected an item");
+
+        // Undoing an accidental completion.
+        manager.complete_item(&item.uuid, now_utc().to_timespec(), false).expect("expected complete to succeed");
+        assert!(manager.undo().expect("expected undo to succeed"));
+        assert_eq!(manager.fetch_item(&item.uuid).expect("expected an item option"), Some(item.clone()));
+        assert!(manager.can_redo().expect("expected redo state"));
+
+        // Undoing a label removal, then redoing it.
+        manager.update_item(&item, None, None, None, Some(&vec![])).expect("expected update to succeed");
+        assert!(!manager.can_redo().expect("expected redo state"));
+        assert!(manager.undo().expect("expected undo to succeed"));
+        assert_eq!(manager.fetch_item(&item.uuid).expect("expected an item option").expect("expected an item").labels, vec![label.clone()]);
+        assert!(manager.redo().expect("expected redo to succeed"));
+        assert!(manager.fetch_item(&item.uuid).expect("expected an item option").expect("expected an item").labels.is_empty());
+        assert!(!manager.redo().expect("expected redo to succeed"));
+
+        // Undo walks back through everything, including the item's creation.
+        while manager.undo().expect("expected undo to succeed") {}
+        assert_eq!(manager.fetch_item(&item.uuid).expect("expected an item option"), None);
+        assert_eq!(manager.fetch_label(&label.name).expect("expected a label option"), None);
+        assert!(manager.redo().expect("expected redo to succeed"));
+        assert_eq!(manager.fetch_label(&label.name).expect("expected a label option"), Some(label));
+    }
+
+    #[test]
+    fn test_undo_skips_unknown_transactions() {
+        let mut manager = toodle();
+        let item = create_named_item(&mut manager, "Buy milk", None);
+
+        // The stacks stay on this device, out of the store that syncs.
+        {
+            let in_progress_read = manager.begin_read().expect("expected a read");
+            let entries = in_progress_read.q_once("[:find ?e :where [?e :undo/tx _]]", None).into_rel_result().expect("expected entries");
+            assert!(entries.is_empty());
+        }
+
+        // An entry for a transaction this device doesn't have is passed over, and the change
+        // before it is undone instead.
+        undo::push(&manager.local.conn, &[i64::max_value()], false).expect("expected push to succeed");
+        assert!(manager.undo().expect("expected undo to succeed"));
+        assert_eq!(manager.fetch_item(&item.uuid).expect("expected an item option"), None);
+        assert!(!manager.can_undo().expect("expected undo state"));
+
+        undo::push(&manager.local.conn, &[i64::max_value()], true).expect("expected push to succeed");
+        assert!(manager.redo().expect("expected redo to succeed"));
+        assert_eq!(manager.fetch_item(&item.uuid).expect("expected an item option"), Some(item));
+
+        // With nothing else on the stack, there's nothing to undo.
+        while manager.undo().expect("expected undo to succeed") {}
+        undo::push(&manager.local.conn, &[i64::max_value()], false).expect("expected push to succeed");
+        assert!(!manager.can_undo().expect("expected undo state"));
+        assert!(!manager.undo().expect("expected undo to succeed"));
+    }
+
+    #[test]
+    fn test_item_history() {
+        let mut manager = toodle();
+        let label = manager.create_label("work".to_string(), "#00ff00".to_string()).expect("expected a label option").expect("expected a label");
+        let item = create_named_item(&mut manager, "Write report", None);
+        let due = Timespec::new(1_517_389_200, 0);
+        manager.update_item(&item, Some("Write the report".to_string()), Some(due), None, Some(&vec![label])).expect("expected update to succeed");
+        let item = manager.fetch_item(&item.uuid).expect("expected an item option").expect("expected an item");
+        manager.update_item(&item, None, Some(due + Duration::days(1)), None, None).expect("expected update to succeed");
+
+        let history = manager.fetch_item_history(&item.uuid).expect("expected history");
+        assert!(history.iter().all(|change| change.origin == ChangeOrigin::Local));
+        let changes: Vec<(String, Option<TypedValue>, Option<TypedValue>)> = history
+            .into_iter()
+            .filter(|change| change.attribute != kw!(:todo/uuid) && change.attribute != kw!(:todo/position))
+            .map(|change| (change.attribute.to_string(), change.old_value, change.new_value))
+            .collect();
+        let name = |name: &str| Some(TypedValue::typed_string(name));
+        let inbox = manager.fetch_lists().expect("expected lists")[0].uuid;
+        assert_eq!(changes, vec![
+            (":todo/list".to_string(), None, Some(TypedValue::Uuid(inbox))),
+            (":todo/name".to_string(), None, name("Write report")),
+            (":todo/due_date".to_string(), None, Some(due.to_typed_value())),
+            (":todo/label".to_string(), None, name("work")),
+            (":todo/name".to_string(), name("Write report"), name("Write the report")),
+            (":todo/due_date".to_string(), Some(due.to_typed_value()), Some((due + Duration::days(1)).to_typed_value())),
+        ]);
+    }
+
+    #[test]
+    fn test_item_history_origins() {
+        let mut manager = toodle();
+        let item = create_named_item(&mut manager, "Write report", None);
+
+        // A change that doesn't go through Toodle, as one arriving by sync doesn't.
+        {
+            let in_progress = manager.begin_transaction().expect("expected a transaction");
+            let entid = item_entid(&in_progress, &item.uuid).expect("expected an entid");
+            let mut builder = in_progress.builder().describe(entid);
+            builder.add_kw(&kw!(:todo/name), TypedValue::typed_string("Write the report")).expect("expected name");
+            builder.commit().expect("expected change to commit");
+        }
+        let origins = |manager: &mut Store| -> Vec<ChangeOrigin> {
+            manager.fetch_item_history(&item.uuid).expect("expected history")
+                .into_iter()
+                .filter(|change| change.attribute == kw!(:todo/name))
+                .map(|change| change.origin)
+                .collect()
+        };
+        assert_eq!(origins(&mut manager), vec![ChangeOrigin::Local, ChangeOrigin::Sync]);
+
+        // What's recorded about the origins stays on the device, so a device that didn't
+        // record them can't tell where the changes came from.
+        {
+            let in_progress_read = manager.begin_read().expect("expected a read");
+            let marks = in_progress_read.q_once("[:find ?tx :where [?tx :toodle.tx/local _]]", None).into_rel_result().expect("expected marks");
+            assert!(marks.is_empty());
+        }
+        manager.local = Local::open("").expect("expected local tables");
+        manager.initialize().expect("expected initialize to work");
+        assert_eq!(origins(&mut manager), vec![ChangeOrigin::Unknown, ChangeOrigin::Unknown]);
+    }
+
+    #[test]
+    fn test_item_timestamps() {
+        let mut manager = toodle();
+        let report = create_named_item(&mut manager, "Write report", None);
+        let milk = create_named_item(&mut manager, "Buy milk", None);
+        assert!(report.created_at.is_some());
+        assert_eq!(report.modified_at, report.created_at);
+        assert!(milk.created_at > report.created_at);
+
+        // Changing an item only moves its modification date.
+        manager.update_item_notes(&report, Some("Two pages".to_string())).expect("expected notes to update");
+        let updated = manager.fetch_item(&report.uuid).expect("expected an item option").expect("expected an item");
+        assert_eq!(updated.created_at, report.created_at);
+        assert!(updated.modified_at > milk.modified_at);
+
+        let recent = |manager: &mut Store, order: ItemOrder| {
+            names(manager.fetch_items(&ItemQuery::new().order_by(order, SortDirection::Descending)).expect("expected items").vec)
+        };
+        assert_eq!(recent(&mut manager, ItemOrder::Creation), vec!["Buy milk", "Write report"]);
+        assert_eq!(recent(&mut manager, ItemOrder::Modification), vec!["Write report", "Buy milk"]);
+
+        // The stamps aren't changes in their own right, and undoing puts them back.
+        let history = manager.fetch_item_history(&report.uuid).expect("expected history");
+        assert!(history.iter().all(|change| change.attribute != kw!(:todo/modified_at)));
+        assert!(manager.undo().expect("expected undo to succeed"));
+        assert_eq!(manager.fetch_item(&report.uuid).expect("expected an item option"), Some(report));
+    }
+
+    #[test]
+    fn test_dates_keep_sub_second_precision() {
+        let mut manager = toodle();
+        let due = Timespec::new(1_517_389_200, 123_456_000);
+        let completed = Timespec::new(1_517_389_260, 999_999_000);
+        let item = create_named_item(&mut manager, "Catch the train", None);
+        manager.update_item(&item, None, Some(due), Some(completed), None).expect("expected update to succeed");
+        let item = manager.fetch_item(&item.uuid).expect("expected an item option").expect("expected an item");
+        assert_eq!(item.due_date, Some(due));
+        assert_eq!(item.completion_date, Some(completed));
+
+        // A date that only differs below a microsecond isn't a change.
+        let changes = manager.fetch_item_history(&item.uuid).expect("expected history").len();
+        manager.update_item(&item, None, Some(Timespec::new(due.sec, due.nsec + 789)), Some(completed), None).expect("expected update to succeed");
+        assert_eq!(manager.fetch_item_history(&item.uuid).expect("expected history").len(), changes);
+    }
+
+    #[test]
+    fn test_export_and_import_json() {
+        let mut source = toodle();
+        let label = source.create_label("work".to_string(), "#00ff00".to_string()).expect("expected a label option").expect("expected a label");
+        let errands = source.create_list("Errands".to_string(), "#0000ff".to_string()).expect("expected a list");
+        let report = create_named_item(&mut source, "Write report", None);
+        source.update_item(&report, None, Some(Timespec::new(1_517_389_200, 250_000)), None, Some(&vec![label])).expect("expected update to succeed");
+        source.move_item_to_list(&report.uuid, &errands.uuid).expect("expected move to succeed");
+        let draft = create_named_item(&mut source, "Draft outline", Some(report.uuid));
+        source.update_item_notes(&draft, Some("Keep it short".to_string())).expect("expected notes to update");
+
+        let export = source.export_json().expect("expected an export");
+        let mut destination = toodle();
+        destination.import_json(&export).expect("expected import to succeed");
+        // Importing is a change like any other, so only the creation dates carry over.
+        let todos = |manager: &mut Store| {
+            let mut export: serde_json::Value = serde_json::from_str(&manager.export_json().expect("expected an export")).expect("expected JSON");
+            for todo in export["todos"].as_object_mut().expect("expected todos").values_mut() {
+                todo.as_object_mut().expect("expected a todo").remove("modifiedAt");
+            }
+            export["todos"].clone()
+        };
+        assert_eq!(todos(&mut destination), todos(&mut source));
+        let imported = destination.fetch_item(&report.uuid).expect("expected an item option").expect("expected an item");
+        assert_eq!(imported.list, Some(errands.uuid));
+        assert_eq!(names(destination.fetch_children(&report.uuid).expect("expected children")), vec!["Draft outline"]);
+
+        // Importing the same thing again changes nothing.
+        let before = destination.export_json().expect("expected an export");
+        destination.import_json(&export).expect("expected import to succeed");
+        assert_eq!(destination.export_json().expect("expected an export"), before);
+
+        // Importing a newer export brings existing items up to date.
+        let report = source.fetch_item(&report.uuid).expect("expected an item option").expect("expected an item");
+        source.update_item(&report, Some("Write the report".to_string()), None, None, Some(&vec![])).expect("expected update to succeed");
+        destination.import_json(&source.export_json().expect("expected an export")).expect("expected import to succeed");
+        assert_eq!(todos(&mut destination), todos(&mut source));
+        let imported = destination.fetch_item(&report.uuid).expect("expected an item option").expect("expected an item");
+        assert_eq!(imported.name, "Write the report");
+        assert_eq!(imported.due_date, None);
+        assert!(imported.labels.is_empty());
+
+        match destination.import_json(r#"{"version": 2}"#) {
+            Err(Error(ErrorKind::UnsupportedExportVersion(2), _)) => {},
+            result => panic!("expected an unsupported version error, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_export_and_import_ics() {
+        let mut source = toodle();
+        let label = source.create_label("work, mostly".to_string(), "#00ff00".to_string()).expect("expected a label option").expect("expected a label");
+        let errands = source.create_list("Errands".to_string(), "#0000ff".to_string()).expect("expected a list");
+        let report = create_named_item(&mut source, "Write report; today", None);
+        let recurrence: Recurrence = "FREQ=WEEKLY;BYDAY=MO".parse().expect("expected a rule");
+        source.update_item(&report, None, Some(Timespec::new(1_517_389_200, 0)), Some(Timespec::new(1_517_392_800, 0)), Some(&vec![label])).expect("expected update to succeed");
+        source.update_item_recurrence(&report, Some(recurrence.clone())).expect("expected recurrence to update");
+        let draft = create_named_item(&mut source, "Draft outline", Some(report.uuid));
+        source.update_item_notes(&draft, Some("Keep it short,\nand clear".to_string())).expect("expected notes to update");
+
+        let ics = source.export_ics().expect("expected an export");
+        assert!(ics.starts_with("BEGIN:VCALENDAR\r\n"));
+        assert!(ics.contains("SUMMARY:Write report\\; today\r\n"));
+        assert!(ics.contains("DUE:20180131T090000Z\r\n"));
+        assert!(ics.contains("CATEGORIES:work\\, mostly\r\n"));
+
+        let mut destination = toodle();
+        destination.import_ics(&ics).expect("expected import to succeed");
+        let imported = destination.fetch_item(&report.uuid).expect("expected an item option").expect("expected an item");
+        assert_eq!(imported.name, "Write report; today");
+        assert_eq!(imported.due_date, Some(Timespec::new(1_517_389_200, 0)));
+        assert_eq!(imported.completion_date, Some(Timespec::new(1_517_392_800, 0)));
+        assert_eq!(imported.recurrence, Some(recurrence));
+        assert_eq!(imported.labels.iter().map(|label| label.name.clone()).collect::<Vec<String>>(), vec!["work, mostly"]);
+        let imported = destination.fetch_item(&draft.uuid).expect("expected an item option").expect("expected an item");
+        assert_eq!(imported.notes, Some("Keep it short,\nand clear".to_string()));
+        assert_eq!(imported.parent, Some(report.uuid));
+
+        // Importing again changes nothing, and lists are left alone.
+        source.move_item_to_list(&report.uuid, &errands.uuid).expect("expected move to succeed");
+        destination.import_json(&source.export_json().expect("expected an export")).expect("expected import to succeed");
+        let before = destination.export_json().expect("expected an export");
+        destination.import_ics(&ics).expect("expected import to succeed");
+        assert_eq!(destination.export_json().expect("expected an export"), before);
+
+        // Other calendars' UIDs and dates.
+        let foreign = "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nBEGIN:VTODO\r\nUID:1234@example.com\r\nDTSTAMP:20180201T120000Z\r\n\
+                       SUMMARY:Buy a very long list of things that needs folding onto more than one li\r\n ne\r\n\
+                       DUE;VALUE=DATE:20180202\r\nSTATUS:COMPLETED\r\nRELATED-TO:missing@example.com\r\n\
+                       BEGIN:VALARM\r\nACTION:DISPLAY\r\nDESCRIPTION:Reminder\r\nEND:VALARM\r\nEND:VTODO\r\nEND:VCALENDAR\r\n";
+        destination.import_ics(foreign).expect("expected import to succeed");
+        destination.import_ics(foreign).expect("expected import to succeed");
+        let foreign_items: Vec<Item> = destination.fetch_items(&ItemQuery::new())
+                                                  .expect("expected items")
+                                                  .vec
+                                                  .into_iter()
+                                                  .filter(|item| item.name.starts_with("Buy"))
+                                                  .collect();
+        assert_eq!(foreign_items.len(), 1);
+        assert_eq!(foreign_items[0].name, "Buy a very long list of things that needs folding onto more than one line");
+        assert_eq!(foreign_items[0].due_date, Some(Timespec::new(1_517_529_600, 0)));
+        assert_eq!(foreign_items[0].completion_date, Some(Timespec::new(1_517_486_400, 0)));
+        assert_eq!(foreign_items[0].notes, None);
+        assert_eq!(foreign_items[0].parent, None);
+
+        match destination.import_ics("BEGIN:VCALENDAR\r\nBEGIN:VTODO\r\nSUMMARY:No UID\r\nEND:VTODO\r\nEND:VCALENDAR\r\n") {
+            Err(Error(ErrorKind::InvalidCalendar(_), _)) => {},
+            result => panic!("expected an invalid calendar error, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_export_and_import_todo_txt() {
+        let mut source = toodle();
+        let work = source.create_label("work".to_string(), "#00ff00".to_string()).expect("expected a label option").expect("expected a label");
+        let office = source.create_label("@office".to_string(), "#0000ff".to_string()).expect("expected a label option").expect("expected a label");
+        let report = create_named_item(&mut source, "(A) Write report rec:1w", None);
+        source.update_item(&report, None, Some(Timespec::new(1_517_356_800, 0)), Some(Timespec::new(1_517_392_800, 500_000)), Some(&vec![work, office])).expect("expected update to succeed");
+        let errands = create_named_item(&mut source, "Buy milk", None);
+
+        let text = source.export_todo_txt().expect("expected an export");
+        let line = text.lines().find(|line| line.ends_with(&format!("uuid:{}", report.uuid.hyphenated()))).expect("expected a line");
+        let created_at = source.fetch_item(&report.uuid).expect("expected an item option").expect("expected an item").created_at.expect("expected a creation date");
+        let created_on = time::strftime("%Y-%m-%d", &time::at_utc(created_at)).expect("expected a date");
+        assert!(line.starts_with(&format!("x 2018-01-31 {} (A) Write report rec:1w ", created_on)));
+        assert!(line.contains(" +work ") && line.contains(" @office ") && line.contains(" due:2018-01-31 "));
+        let lines = |text: &str| {
+            let mut lines: Vec<String> = text.lines().map(|line| line.to_string()).collect();
+            lines.sort();
+            lines
+        };
+        let mut destination = toodle();
+        destination.import_todo_txt(&text).expect("expected import to succeed");
+        assert_eq!(lines(&destination.export_todo_txt().expect("expected an export")), lines(&text));
+        let imported = destination.fetch_item(&errands.uuid).expect("expected an item option").expect("expected an item");
+        assert_eq!(imported.name, "Buy milk");
+        assert_eq!(imported.completion_date, None);
+
+        // Items keep their time when the day hasn't changed, and what todo.txt can't say is
+        // left alone.
+        destination.import_json(&source.export_json().expect("expected an export")).expect("expected import to succeed");
+        destination.update_item_notes(&imported, Some("Semi-skimmed".to_string())).expect("expected notes to update");
+        let before = destination.export_json().expect("expected an export");
+        destination.import_todo_txt(&text).expect("expected import to succeed");
+        assert_eq!(destination.export_json().expect("expected an export"), before);
+        let imported = destination.fetch_item(&report.uuid).expect("expected an item option").expect("expected an item");
+        assert_eq!(imported.completion_date, Some(Timespec::new(1_517_392_800, 500_000)));
+
+        // Hand-written lines are matched by their text.
+        let written = "(B) 2018-01-20 Call Mom +family @phone due:2018-02-01 t:2018-01-25\nx Call Mom\n";
+        destination.import_todo_txt(written).expect("expected import to succeed");
+        destination.import_todo_txt(written).expect("expected import to succeed");
+        let calls: Vec<Item> = destination.fetch_items(&ItemQuery::new())
+                                          .expect("expected items")
+                                          .vec
+                                          .into_iter()
+                                          .filter(|item| item.name.contains("Call Mom"))
+                                          .collect();
+        assert_eq!(calls.len(), 2);
+        let pending = calls.iter().find(|item| item.completion_date.is_none()).expect("expected a pending call");
+        assert_eq!(pending.name, "(B) Call Mom t:2018-01-25");
+        assert_eq!(pending.created_at, Some(Timespec::new(1_516_406_400, 0)));
+        assert_eq!(pending.due_date, Some(Timespec::new(1_517_443_200, 0)));
+        let mut labels: Vec<String> = pending.labels.iter().map(|label| label.name.clone()).collect();
+        labels.sort();
+        assert_eq!(labels, vec!["@phone", "family"]);
+        assert!(calls.iter().any(|item| item.name == "Call Mom" && item.completion_date.is_some()));
+    }
+
+    #[test]
+    fn test_export_and_import_taskwarrior() {
+        let mut source = toodle();
+        let work = source.create_label("work".to_string(), "#00ff00".to_string()).expect("expected a label option").expect("expected a label");
+        let report = create_named_item(&mut source, "Write report", None);
+        source.update_item(&report, None, Some(Timespec::new(1_517_389_200, 0)), Some(Timespec::new(1_517_392_800, 500_000)), Some(&vec![work])).expect("expected update to succeed");
+        create_named_item(&mut source, "Buy milk", None);
+
+        let json = source.export_taskwarrior().expect("expected an export");
+        let tasks: serde_json::Value = serde_json::from_str(&json).expect("expected JSON");
+        let task = tasks.as_array()
+                        .expect("expected tasks")
+                        .iter()
+                        .find(|task| task["uuid"] == report.uuid.hyphenated().to_string())
+                        .expect("expected a task")
+                        .clone();
+        assert_eq!(task["description"], "Write report");
+        assert_eq!(task["status"], "completed");
+        assert_eq!(task["due"], "20180131T090000Z");
+        assert_eq!(task["end"], "20180131T100000Z");
+        assert_eq!(task["tags"][0], "work");
+        assert!(task["entry"].is_string() && task["modified"].is_string());
+
+        let mut destination = toodle();
+        destination.import_taskwarrior(&json).expect("expected import to succeed");
+        let imported = destination.fetch_item(&report.uuid).expect("expected an item option").expect("expected an item");
+        assert_eq!(imported.name, "Write report");
+        assert_eq!(imported.due_date, Some(Timespec::new(1_517_389_200, 0)));
+        assert_eq!(imported.completion_date, Some(Timespec::new(1_517_392_800, 0)));
+        assert_eq!(imported.labels.iter().map(|label| label.name.clone()).collect::<Vec<String>>(), vec!["work"]);
+
+        // Importing again updates rather than duplicates, and times within the second are kept.
+        destination.import_json(&source.export_json().expect("expected an export")).expect("expected import to succeed");
+        let before = destination.export_json().expect("expected an export");
+        destination.import_taskwarrior(&json).expect("expected import to succeed");
+        assert_eq!(destination.export_json().expect("expected an export"), before);
+
+        // Tasks a line at a time, as older versions of Taskwarrior write them.
+        let lines = format!("{{\"uuid\":\"{}\",\"description\":\"Write the report\",\"status\":\"pending\",\"entry\":\"20180130T090000Z\"}}\n\
+                             {{\"uuid\":\"{}\",\"description\":\"Gone\",\"status\":\"deleted\",\"entry\":\"20180130T090000Z\"}}\n",
+                            report.uuid.hyphenated(), Uuid::new_v4().hyphenated());
+        destination.import_taskwarrior(&lines).expect("expected import to succeed");
+        let imported = destination.fetch_item(&report.uuid).expect("expected an item option").expect("expected an item");
+        assert_eq!(imported.name, "Write the report");
+        assert_eq!(imported.created_at, Some(Timespec::new(1_517_302_800, 0)));
+        assert_eq!(imported.completion_date, None);
+        assert_eq!(imported.due_date, None);
+        assert!(imported.labels.is_empty());
+        assert_eq!(destination.fetch_items(&ItemQuery::new()).expect("expected items").vec.len(), 2);
+
+        match destination.import_taskwarrior(r#"[{"uuid": "nope", "description": "", "status": "pending"}]"#) {
+            Err(Error(ErrorKind::InvalidTaskwarrior(_), _)) => {},
+            result => panic!("expected an invalid Taskwarrior error, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_change_events() {
+        let mut manager = toodle();
+        let (sender, receiver) = mpsc::channel();
+        let sender = Mutex::new(sender);
+        let observer = manager.register_change_observer("test".to_string(), move |key| {
+            sender.lock().expect("expected a sender").send(key.to_string()).expect("expected to notify");
+        }).expect("expected an observer");
+
+        let label = manager.create_label("work".to_string(), "#00ff00".to_string()).expect("expected a label option").expect("expected a label");
+        let item = create_named_item(&mut manager, "Write report", None);
+        manager.update_item(&item, Some("Write the report".to_string()), None, None, Some(&vec![label])).expect("expected update to succeed");
+        manager.delete_item(&item.uuid).expect("expected delete to succeed");
+        for _ in 0..4 {
+            let key = receiver.recv_timeout(::std::time::Duration::from_secs(5)).expect("expected a notification");
+            assert_eq!(key, "test");
+        }
+
+        let mut events = manager.take_change_events(&observer).expect("expected events");
+        if let Some(&mut ChangeEvent::ItemUpdated(_, ref mut fields)) = events.get_mut(2) {
+            fields.sort_by_key(|field| field.to_string());
+        }
+        assert_eq!(events, vec![
+            ChangeEvent::LabelCreated("work".to_string()),
+            ChangeEvent::ItemCreated(item.uuid),
+            ChangeEvent::ItemUpdated(item.uuid, vec![kw!(:todo/label), kw!(:todo/name)]),
+            ChangeEvent::ItemDeleted(item.uuid),
+        ]);
+        assert!(manager.take_change_events(&observer).expect("expected events").is_empty());
+        manager.unregister_change_observer(observer);
+    }
 }
@@ -0,0 +1,50 @@
+// Copyright 2018 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+use mentat::{
+    Binding,
+    Uuid,
+};
+
+use errors::{
+    Result,
+};
+
+use utils::{
+    column,
+    Entity,
+};
+
+/// The name of the inbox, which new stores start with, and which items created before lists
+/// existed are moved into.
+pub const DEFAULT_LIST_NAME: &str = "Inbox";
+pub const DEFAULT_LIST_COLOR: &str = "#808080";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct List {
+    pub id: Option<Entity>,    // id should not be leaked outside of the library
+    pub uuid: Uuid,
+    pub name: String,
+    pub color: String,
+    pub sort_order: i64,
+}
+
+impl List {
+    /// Builds a list from a `?l ?uuid ?name ?color ?sort_order` row.
+    pub fn from_row(row: &[Binding]) -> Result<List> {
+        Ok(List {
+            id: Some(column(row, 0)?),
+            uuid: column(row, 1)?,
+            name: column(row, 2)?,
+            color: column(row, 3)?,
+            sort_order: column(row, 4)?,
+        })
+    }
+}
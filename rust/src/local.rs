@@ -0,0 +1,59 @@
+// Copyright 2018 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! What Toodle keeps about a store that has to stay on this device.
+//!
+//! Mentat syncs every datom, but some of what Toodle tracks is only true of this device: the
+//! undo and redo stacks, which refer to transactions by ids that other devices don't share,
+//! and which transactions were made here. That's kept in tables of Toodle's own, beside
+//! Mentat's in the same SQLite database, where sync never sees it.
+
+use rusqlite::{
+    Connection,
+};
+
+use errors::{
+    Result,
+};
+
+pub struct Local {
+    pub(crate) conn: Connection,
+}
+
+impl Local {
+    /// Opens the tables in the database at `path`, creating them if they aren't there yet. As
+    /// with `Store::open`, an empty path means an in-memory database.
+    pub fn open(path: &str) -> Result<Local> {
+        let conn = if path.is_empty() {
+            Connection::open_in_memory()?
+        } else {
+            Connection::open(path)?
+        };
+        // The undo and redo stacks, one row per transaction. `entry` groups the transactions
+        // that are undone together, and is the id of the first of them. Then the transactions
+        // made on this device, and the last one from before it started keeping track.
+        conn.execute_batch(r#"
+            CREATE TABLE IF NOT EXISTS toodle_undo (
+                entry INTEGER NOT NULL,
+                tx INTEGER NOT NULL,
+                redo BOOLEAN NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS toodle_local_txs (
+                tx INTEGER PRIMARY KEY
+            );
+            CREATE TABLE IF NOT EXISTS toodle_tracked_since (
+                tx INTEGER NOT NULL
+            );
+        "#)?;
+        Ok(Local {
+            conn: conn,
+        })
+    }
+}
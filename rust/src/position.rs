@@ -0,0 +1,265 @@
+// Copyright 2018 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! Positions that order items by hand.
+//!
+//! A position is a string, and items are ordered by comparing their positions byte by byte.
+//! There's always room for another position between any two, so moving an item only ever
+//! changes that item, and devices that reorder items independently never have to agree on
+//! a renumbering when they sync.
+//!
+//! Positions are base 62 fractional keys. They start with an integer part, whose first
+//! character says how many digits follow it, and end with a fraction that has no trailing
+//! zeros. Positions for items added at the end are found by incrementing the integer part,
+//! so they stay short however many items there are; only moving items between neighbours
+//! that are already close together grows the fraction.
+
+const DIGITS: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+const BASE: usize = 62;
+
+/// The position of the first item put in order.
+const FIRST: &str = "a0";
+
+/// The smallest integer part. Only positions with a fraction can come after it.
+const SMALLEST_INTEGER: &str = "A00000000000000000000000000";
+
+/// A position between `lower` and `upper`, either of which may be missing to leave that end
+/// open. Both have to be valid, and `lower` has to come before `upper`.
+pub fn between(lower: Option<&str>, upper: Option<&str>) -> String {
+    let key = match (lower, upper) {
+        (None, None) => FIRST.as_bytes().to_vec(),
+        (None, Some(upper)) => {
+            let (integer, fraction) = split(upper);
+            if integer == SMALLEST_INTEGER.as_bytes() {
+                concat(integer, midpoint(&[], Some(fraction)))
+            } else if !fraction.is_empty() {
+                integer.to_vec()
+            } else {
+                decrement(integer).unwrap_or_else(|| concat(integer, midpoint(&[], Some(fraction))))
+            }
+        },
+        (Some(lower), None) => {
+            let (integer, fraction) = split(lower);
+            increment(integer).unwrap_or_else(|| concat(integer, midpoint(fraction, None)))
+        },
+        (Some(lower), Some(upper)) => {
+            let (lower_integer, lower_fraction) = split(lower);
+            let (upper_integer, upper_fraction) = split(upper);
+            if lower_integer == upper_integer {
+                concat(lower_integer, midpoint(lower_fraction, Some(upper_fraction)))
+            } else {
+                match increment(lower_integer) {
+                    Some(ref integer) if &integer[..] < upper.as_bytes() => integer.clone(),
+                    _ => concat(lower_integer, midpoint(lower_fraction, None)),
+                }
+            }
+        },
+    };
+    key.into_iter().map(|byte| byte as char).collect()
+}
+
+/// Whether `key` is a position `between` can work with. Positions arrive by sync, so they
+/// might have been written by anything.
+pub fn is_valid(key: &str) -> bool {
+    let bytes = key.as_bytes();
+    let length = match bytes.first().and_then(|&head| integer_length(head)) {
+        Some(length) => length,
+        None => return false,
+    };
+    bytes.len() >= length &&
+        key != SMALLEST_INTEGER &&
+        bytes[1..].iter().all(|byte| DIGITS.contains(byte)) &&
+        bytes[length..].last() != Some(&b'0')
+}
+
+fn digit(byte: u8) -> usize {
+    DIGITS.iter().position(|&d| d == byte).unwrap_or(0)
+}
+
+/// How long an integer part starting with `head` is: `a` to `z` start ever longer positive
+/// integers, and `Z` to `A` ever longer negative ones.
+fn integer_length(head: u8) -> Option<usize> {
+    if head >= b'a' && head <= b'z' {
+        Some((head - b'a') as usize + 2)
+    } else if head >= b'A' && head <= b'Z' {
+        Some((b'Z' - head) as usize + 2)
+    } else {
+        None
+    }
+}
+
+fn split(key: &str) -> (&[u8], &[u8]) {
+    let bytes = key.as_bytes();
+    let length = bytes.first()
+                      .and_then(|&head| integer_length(head))
+                      .map_or(bytes.len(), |length| length.min(bytes.len()));
+    bytes.split_at(length)
+}
+
+fn concat(integer: &[u8], fraction: Vec<u8>) -> Vec<u8> {
+    let mut key = integer.to_vec();
+    key.extend(fraction);
+    key
+}
+
+/// A fraction between `lower` and `upper`, or after `lower` if there's no `upper`.
+fn midpoint(lower: &[u8], upper: Option<&[u8]>) -> Vec<u8> {
+    if let Some(upper) = upper {
+        // Keep whatever they start with in common, reading missing digits of `lower` as zeros.
+        let common = upper.iter()
+                          .enumerate()
+                          .take_while(|&(i, &byte)| lower.get(i).cloned().unwrap_or(b'0') == byte)
+                          .count();
+        if common > 0 {
+            let rest = if common < lower.len() { &lower[common..] } else { &[] };
+            return concat(&upper[..common], midpoint(rest, Some(&upper[common..])));
+        }
+    }
+    let lower_digit = lower.first().map_or(0, |&byte| digit(byte));
+    let upper_digit = upper.and_then(|upper| upper.first()).map_or(BASE, |&byte| digit(byte));
+    if upper_digit > lower_digit + 1 {
+        return vec![DIGITS[(lower_digit + upper_digit + 1) / 2]];
+    }
+    match upper {
+        // `upper`'s first digit alone is smaller than `upper`, and still bigger than `lower`.
+        Some(upper) if upper.len() > 1 => vec![upper[0]],
+        _ => {
+            let rest = if lower.is_empty() { &[] } else { &lower[1..] };
+            concat(&[DIGITS[lower_digit]], midpoint(rest, None))
+        },
+    }
+}
+
+fn increment(integer: &[u8]) -> Option<Vec<u8>> {
+    let (head, digits) = match integer.split_first() {
+        Some((&head, digits)) => (head, digits),
+        None => return None,
+    };
+    let mut digits = digits.to_vec();
+    let mut carry = true;
+    for byte in digits.iter_mut().rev() {
+        let value = digit(*byte) + 1;
+        if value == BASE {
+            *byte = b'0';
+        } else {
+            *byte = DIGITS[value];
+            carry = false;
+            break;
+        }
+    }
+    if carry {
+        match head {
+            b'Z' => return Some(FIRST.as_bytes().to_vec()),
+            b'z' => return None,
+            _ => {},
+        }
+        let head = head + 1;
+        if head > b'a' {
+            digits.push(b'0');
+        } else {
+            digits.pop();
+        }
+        return Some(concat(&[head], digits));
+    }
+    Some(concat(&[head], digits))
+}
+
+fn decrement(integer: &[u8]) -> Option<Vec<u8>> {
+    let (head, digits) = match integer.split_first() {
+        Some((&head, digits)) => (head, digits),
+        None => return None,
+    };
+    let mut digits = digits.to_vec();
+    let mut borrow = true;
+    for byte in digits.iter_mut().rev() {
+        let value = digit(*byte);
+        if value == 0 {
+            *byte = DIGITS[BASE - 1];
+        } else {
+            *byte = DIGITS[value - 1];
+            borrow = false;
+            break;
+        }
+    }
+    if borrow {
+        match head {
+            b'a' => return Some(concat(b"Z", vec![DIGITS[BASE - 1]])),
+            b'A' => return None,
+            _ => {},
+        }
+        let head = head - 1;
+        if head < b'Z' {
+            digits.push(DIGITS[BASE - 1]);
+        } else {
+            digits.pop();
+        }
+        return Some(concat(&[head], digits));
+    }
+    Some(concat(&[head], digits))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        between,
+        is_valid,
+    };
+
+    #[test]
+    fn test_between() {
+        assert_eq!(between(None, None), "a0");
+        assert_eq!(between(Some("a0"), None), "a1");
+        assert_eq!(between(Some("az"), None), "b00");
+        assert_eq!(between(None, Some("a0")), "Zz");
+        assert_eq!(between(Some("Zz"), None), "a0");
+        assert_eq!(between(None, Some("b00")), "az");
+        assert_eq!(between(Some("a0"), Some("a1")), "a0V");
+        assert_eq!(between(Some("a0"), Some("a0V")), "a0G");
+        assert_eq!(between(Some("a0V"), Some("a1")), "a0l");
+        assert_eq!(between(Some("a1"), Some("a3")), "a2");
+    }
+
+    #[test]
+    fn test_between_stays_in_order() {
+        // Keep putting positions just after the lower bound, and just before the upper one.
+        let (mut lower, mut upper) = ("a0".to_string(), "a1".to_string());
+        for i in 0..200 {
+            let middle = between(Some(&lower), Some(&upper));
+            assert!(is_valid(&middle), "{} isn't valid", middle);
+            assert!(lower < middle && middle < upper, "{} isn't between {} and {}", middle, lower, upper);
+            if i % 2 == 0 {
+                upper = middle;
+            } else {
+                lower = middle;
+            }
+        }
+
+        let mut last = between(None, None);
+        for _ in 0..10_000 {
+            let next = between(Some(&last), None);
+            assert!(last < next);
+            last = next;
+        }
+        assert!(last.len() <= 4);
+    }
+
+    #[test]
+    fn test_is_valid() {
+        assert!(is_valid("a0"));
+        assert!(is_valid("Zz"));
+        assert!(is_valid("b00"));
+        assert!(is_valid("a0V"));
+        assert!(!is_valid(""));
+        assert!(!is_valid("a"));
+        assert!(!is_valid("a0V0"));
+        assert!(!is_valid("a0-"));
+        assert!(!is_valid("~"));
+    }
+}
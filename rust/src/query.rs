@@ -0,0 +1,325 @@
+// Copyright 2018 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+use time::Timespec;
+
+use mentat::{
+    TypedValue,
+    Uuid,
+};
+
+use search;
+
+use utils::{
+    ToTypedValue,
+};
+
+/// Stand-ins for missing dates when sorting, so that undated items sort after dated ones
+/// in either direction instead of dropping out of the query.
+const LATEST_DATE: Timespec = Timespec { sec: 253402300799, nsec: 0 };   // 9999-12-31T23:59:59Z
+const EARLIEST_DATE: Timespec = Timespec { sec: -62135596800, nsec: 0 }; // 0001-01-01T00:00:00Z
+
+/// Likewise for items without a position, which sort after any position in ascending order
+/// and before any in descending order.
+const LAST_POSITION: &str = "~";
+const FIRST_POSITION: &str = "";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LabelMatch {
+    Any,
+    All,
+}
+
+impl Default for LabelMatch {
+    fn default() -> LabelMatch {
+        LabelMatch::Any
+    }
+}
+
+/// Which items to match, depending on whether they're in the trash.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TrashFilter {
+    Exclude,
+    Include,
+    Only,
+}
+
+impl Default for TrashFilter {
+    fn default() -> TrashFilter {
+        TrashFilter::Exclude
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ItemOrder {
+    /// The order items were put in by hand. This is the default.
+    Position,
+    DueDate,
+    CompletionDate,
+    Name,
+    /// When the item was created.
+    Creation,
+    /// When the item was last changed, for showing what's changed recently.
+    Modification,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SortDirection {
+    Ascending,
+    Descending,
+}
+
+/// Describes a subset of items to fetch, and the order to return them in.
+///
+/// ```ignore
+/// let query = ItemQuery::new()
+///     .completed(false)
+///     .with_any_label(vec!["work".to_string()])
+///     .order_by(ItemOrder::DueDate, SortDirection::Ascending)
+///     .limit(20);
+/// let items = store.fetch_items(&query)?;
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ItemQuery {
+    pub completed: Option<bool>,
+    pub due_after: Option<Timespec>,
+    pub due_before: Option<Timespec>,
+    pub labels: Vec<String>,
+    pub label_match: LabelMatch,
+    pub name_contains: Option<String>,
+    pub list: Option<Uuid>,
+    pub parent: Option<Uuid>,
+    pub top_level_only: bool,
+    pub trash: TrashFilter,
+    pub order: Option<(ItemOrder, SortDirection)>,
+    pub limit: Option<u64>,
+}
+
+/// The Datalog that an `ItemQuery` compiles to. `clauses` bind `?eid` to each matching
+/// item, and may refer to `inputs` by name.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct ItemSelection {
+    pub clauses: Vec<String>,
+    pub inputs: Vec<(String, TypedValue)>,
+    pub order: Vec<String>,
+    pub limit: Option<u64>,
+}
+
+impl ItemSelection {
+    pub fn new(clauses: &str, inputs: Vec<(&str, TypedValue)>) -> ItemSelection {
+        ItemSelection {
+            clauses: vec![clauses.to_string()],
+            inputs: inputs.into_iter().map(|(name, value)| (name.to_string(), value)).collect(),
+            order: vec![],
+            limit: None,
+        }
+    }
+
+    fn input<T: ToTypedValue>(&mut self, name: &str, value: &T) {
+        self.inputs.push((name.to_string(), value.to_typed_value()));
+    }
+
+    /// Orders the items by `order`, then by when they were created.
+    pub fn order_by(&mut self, order: ItemOrder, direction: SortDirection) {
+        let (no_date, no_position) = match direction {
+            SortDirection::Ascending => (LATEST_DATE, LAST_POSITION),
+            SortDirection::Descending => (EARLIEST_DATE, FIRST_POSITION),
+        };
+        let (sort_by, missing) = match order {
+            ItemOrder::Position => ("?sort_position", Some((":todo/position", no_position.to_typed_value()))),
+            ItemOrder::DueDate => ("?sort_due_date", Some((":todo/due_date", no_date.to_typed_value()))),
+            ItemOrder::CompletionDate => ("?sort_completion_date", Some((":todo/completion_date", no_date.to_typed_value()))),
+            ItemOrder::Name => ("?item_name", None),
+            ItemOrder::Creation => ("?sort_created_at", Some((":todo/created_at", no_date.to_typed_value()))),
+            ItemOrder::Modification => ("?sort_modified_at", Some((":todo/modified_at", no_date.to_typed_value()))),
+        };
+        if let Some((attribute, default)) = missing {
+            self.clauses.push(format!("[(get-else $ ?eid {} ?missing) {}]", attribute, sort_by));
+            self.inputs.push(("?missing".to_string(), default));
+        }
+        let direction = match direction {
+            SortDirection::Ascending => "asc",
+            SortDirection::Descending => "desc",
+        };
+        self.order.push(format!("({} {})", direction, sort_by));
+        if order == ItemOrder::Position {
+            // Items moved to the same place on different devices end up with the same
+            // position, so they're told apart by something that's the same everywhere.
+            self.order.push("(asc ?item_uuid)".to_string());
+        }
+        // Entids only ever increase, so they double as a stable tiebreaker.
+        self.order.push("(asc ?eid)".to_string());
+    }
+}
+
+impl ItemQuery {
+    pub fn new() -> ItemQuery {
+        ItemQuery::default()
+    }
+
+    /// Only match completed items if `completed` is true, or incomplete items otherwise.
+    pub fn completed(mut self, completed: bool) -> ItemQuery {
+        self.completed = Some(completed);
+        self
+    }
+
+    /// Only match items due at or after `date`.
+    pub fn due_after(mut self, date: Timespec) -> ItemQuery {
+        self.due_after = Some(date);
+        self
+    }
+
+    /// Only match items due strictly before `date`.
+    pub fn due_before(mut self, date: Timespec) -> ItemQuery {
+        self.due_before = Some(date);
+        self
+    }
+
+    /// Only match items that have at least one of the named labels.
+    pub fn with_any_label(mut self, names: Vec<String>) -> ItemQuery {
+        self.labels = names;
+        self.label_match = LabelMatch::Any;
+        self
+    }
+
+    /// Only match items that have every one of the named labels.
+    pub fn with_all_labels(mut self, names: Vec<String>) -> ItemQuery {
+        self.labels = names;
+        self.label_match = LabelMatch::All;
+        self
+    }
+
+    /// Only match items with `text` somewhere in their name, ignoring case.
+    pub fn name_contains(mut self, text: String) -> ItemQuery {
+        self.name_contains = Some(text);
+        self
+    }
+
+    /// Only match items in the list with the given UUID.
+    pub fn in_list(mut self, uuid: Uuid) -> ItemQuery {
+        self.list = Some(uuid);
+        self
+    }
+
+    /// Only match the subtasks of the item with the given UUID.
+    pub fn subtasks_of(mut self, uuid: Uuid) -> ItemQuery {
+        self.parent = Some(uuid);
+        self
+    }
+
+    /// Only match items that aren't a subtask of another item.
+    pub fn top_level_only(mut self) -> ItemQuery {
+        self.top_level_only = true;
+        self
+    }
+
+    /// Match items in the trash as well as those that aren't, which are all that are matched
+    /// by default.
+    pub fn including_trashed(mut self) -> ItemQuery {
+        self.trash = TrashFilter::Include;
+        self
+    }
+
+    /// Only match items in the trash.
+    pub fn trashed_only(mut self) -> ItemQuery {
+        self.trash = TrashFilter::Only;
+        self
+    }
+
+    pub fn order_by(mut self, order: ItemOrder, direction: SortDirection) -> ItemQuery {
+        self.order = Some((order, direction));
+        self
+    }
+
+    /// Mentat's queries can take a `:limit` but have no offset, so there's no way to skip
+    /// the first few matches. To page through items, narrow the next query past the last item
+    /// fetched instead, for example with `due_after`.
+    pub fn limit(mut self, limit: u64) -> ItemQuery {
+        self.limit = Some(limit);
+        self
+    }
+
+    pub(crate) fn to_selection(&self) -> ItemSelection {
+        let mut selection = ItemSelection::default();
+
+        match self.completed {
+            Some(true) => selection.clauses.push("[?eid :todo/completion_date _]".to_string()),
+            Some(false) => selection.clauses.push("(not [?eid :todo/completion_date _])".to_string()),
+            None => {},
+        }
+
+        match self.trash {
+            TrashFilter::Exclude => selection.clauses.push("(not [?eid :todo/deleted_at _])".to_string()),
+            TrashFilter::Only => selection.clauses.push("[?eid :todo/deleted_at _]".to_string()),
+            TrashFilter::Include => {},
+        }
+
+        if let Some(ref uuid) = self.list {
+            selection.clauses.push("[?in_list :list/uuid ?list_uuid]".to_string());
+            selection.clauses.push("[?eid :todo/list ?in_list]".to_string());
+            selection.input("?list_uuid", uuid);
+        }
+
+        if let Some(ref uuid) = self.parent {
+            selection.clauses.push("[?of_parent :todo/uuid ?parent_uuid]".to_string());
+            selection.clauses.push("[?eid :todo/parent ?of_parent]".to_string());
+            selection.input("?parent_uuid", uuid);
+        }
+
+        if self.top_level_only {
+            selection.clauses.push("(not [?eid :todo/parent _])".to_string());
+        }
+
+        if self.due_after.is_some() || self.due_before.is_some() {
+            selection.clauses.push("[?eid :todo/due_date ?due]".to_string());
+        }
+        if let Some(date) = self.due_after {
+            selection.clauses.push("[(>= ?due ?due_after)]".to_string());
+            selection.input("?due_after", &date);
+        }
+        if let Some(date) = self.due_before {
+            selection.clauses.push("[(< ?due ?due_before)]".to_string());
+            selection.input("?due_before", &date);
+        }
+
+        if !self.labels.is_empty() {
+            match self.label_match {
+                LabelMatch::Any => {
+                    let branches: Vec<String> = (0..self.labels.len())
+                        .map(|i| format!("[?any_label :label/name ?label_{}]", i))
+                        .collect();
+                    selection.clauses.push("[?eid :todo/label ?any_label]".to_string());
+                    selection.clauses.push(format!("(or-join [?any_label] {})", branches.join(" ")));
+                },
+                LabelMatch::All => {
+                    for i in 0..self.labels.len() {
+                        selection.clauses.push(format!("[?eid :todo/label ?all_label_{}]", i));
+                        selection.clauses.push(format!("[?all_label_{} :label/name ?label_{}]", i, i));
+                    }
+                },
+            }
+            for (i, name) in self.labels.iter().enumerate() {
+                selection.input(&format!("?label_{}", i), name);
+            }
+        }
+
+        if let Some(expression) = self.name_contains.as_ref().and_then(|text| search::substring_expression(text)) {
+            selection.clauses.push("[(fulltext $ :todo/name_trigrams ?name_contains) [[?eid _ _ _]]]".to_string());
+            selection.input("?name_contains", &expression);
+        }
+
+        let (order, direction) = self.order.unwrap_or((ItemOrder::Position, SortDirection::Ascending));
+        selection.order_by(order, direction);
+
+        selection.limit = self.limit;
+
+        selection
+    }
+}
@@ -0,0 +1,439 @@
+// Copyright 2018 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! Recurrence rules for repeating items.
+//!
+//! Rules are a subset of iCalendar's RRULE, and are stored in that form: `FREQ`,
+//! `INTERVAL`, `BYDAY` (without ordinals), `UNTIL` and `COUNT`. Occurrences are computed in
+//! UTC, starting from the item's due date.
+
+use std::fmt;
+use std::str::FromStr;
+
+use time::{
+    self,
+    Timespec,
+    Tm,
+};
+
+use errors::{
+    Error,
+    ErrorKind,
+};
+
+use items::{
+    Item,
+};
+
+const SECONDS_PER_DAY: i64 = 86_400;
+const UNTIL_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Weekday {
+    Monday,
+    Tuesday,
+    Wednesday,
+    Thursday,
+    Friday,
+    Saturday,
+    Sunday,
+}
+
+const WEEKDAYS: [(Weekday, &str); 7] = [
+    (Weekday::Monday, "MO"),
+    (Weekday::Tuesday, "TU"),
+    (Weekday::Wednesday, "WE"),
+    (Weekday::Thursday, "TH"),
+    (Weekday::Friday, "FR"),
+    (Weekday::Saturday, "SA"),
+    (Weekday::Sunday, "SU"),
+];
+
+impl Weekday {
+    fn of(date: Timespec) -> Weekday {
+        // 1970-01-01 was a Thursday.
+        let days = days_since_epoch(date);
+        WEEKDAYS[(((days + 3) % 7 + 7) % 7) as usize].0
+    }
+
+    fn code(&self) -> &'static str {
+        WEEKDAYS.iter().find(|&&(day, _)| day == *self).map_or("", |&(_, code)| code)
+    }
+}
+
+/// How an item repeats. `by_weekday` narrows daily and weekly rules to particular days of
+/// the week; `count` is the number of occurrences left, including the current one.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Recurrence {
+    pub frequency: Frequency,
+    pub interval: u32,
+    pub by_weekday: Vec<Weekday>,
+    pub until: Option<Timespec>,
+    pub count: Option<u32>,
+}
+
+/// One occurrence of an item, as returned by `Toodle::fetch_occurrences`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Occurrence {
+    pub item: Item,
+    pub due_date: Timespec,
+}
+
+impl Recurrence {
+    pub fn new(frequency: Frequency) -> Recurrence {
+        Recurrence {
+            frequency: frequency,
+            interval: 1,
+            by_weekday: vec![],
+            until: None,
+            count: None,
+        }
+    }
+
+    /// Returns the occurrences of this rule, starting with `start` itself.
+    pub fn occurrences(&self, start: Timespec) -> Occurrences {
+        Occurrences {
+            recurrence: self.clone(),
+            start: start,
+            step: 0,
+            emitted: 0,
+        }
+    }
+
+    /// Returns the first occurrence after `start`, if the rule hasn't run out by then.
+    pub fn next_after(&self, start: Timespec) -> Option<Timespec> {
+        self.occurrences(start).nth(1)
+    }
+
+    /// Whether the rule repeats at all after `start`, regardless of `until` and `count`. Some
+    /// rules never do, like every seventh day but only on Mondays, starting on a Wednesday.
+    pub fn repeats_from(&self, start: Timespec) -> bool {
+        let occurrences = Recurrence {
+            until: None,
+            count: None,
+            ..self.clone()
+        }.occurrences(start);
+        (1..occurrences.search_limit() + 1).any(|step| occurrences.candidate(step).is_some())
+    }
+
+    /// The rule that applies once the current occurrence is done.
+    pub fn advanced(&self) -> Recurrence {
+        let mut next = self.clone();
+        next.count = self.count.map(|count| count.saturating_sub(1));
+        next
+    }
+}
+
+impl fmt::Display for Recurrence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let frequency = match self.frequency {
+            Frequency::Daily => "DAILY",
+            Frequency::Weekly => "WEEKLY",
+            Frequency::Monthly => "MONTHLY",
+            Frequency::Yearly => "YEARLY",
+        };
+        write!(f, "FREQ={}", frequency)?;
+        if self.interval != 1 {
+            write!(f, ";INTERVAL={}", self.interval)?;
+        }
+        if !self.by_weekday.is_empty() {
+            let days: Vec<&str> = self.by_weekday.iter().map(|day| day.code()).collect();
+            write!(f, ";BYDAY={}", days.join(","))?;
+        }
+        if let Some(until) = self.until {
+            let until = time::strftime(UNTIL_FORMAT, &time::at_utc(until)).map_err(|_| fmt::Error)?;
+            write!(f, ";UNTIL={}", until)?;
+        }
+        if let Some(count) = self.count {
+            write!(f, ";COUNT={}", count)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Recurrence {
+    type Err = Error;
+
+    fn from_str(rule: &str) -> Result<Recurrence, Error> {
+        let invalid = || -> Error { ErrorKind::InvalidRecurrence(rule.to_string()).into() };
+        let mut recurrence: Option<Recurrence> = None;
+        let mut interval = 1;
+        let mut by_weekday = vec![];
+        let mut until = None;
+        let mut count = None;
+        for part in rule.trim_left_matches("RRULE:").split(';').filter(|part| !part.is_empty()) {
+            let mut pair = part.splitn(2, '=');
+            let (name, value) = match (pair.next(), pair.next()) {
+                (Some(name), Some(value)) => (name.to_uppercase(), value),
+                _ => return Err(invalid()),
+            };
+            match name.as_str() {
+                "FREQ" => {
+                    let frequency = match value.to_uppercase().as_str() {
+                        "DAILY" => Frequency::Daily,
+                        "WEEKLY" => Frequency::Weekly,
+                        "MONTHLY" => Frequency::Monthly,
+                        "YEARLY" => Frequency::Yearly,
+                        _ => return Err(invalid()),
+                    };
+                    recurrence = Some(Recurrence::new(frequency));
+                },
+                "INTERVAL" => {
+                    interval = value.parse().map_err(|_| invalid())?;
+                    if interval == 0 {
+                        return Err(invalid());
+                    }
+                },
+                "BYDAY" => {
+                    for code in value.split(',') {
+                        let day = WEEKDAYS.iter()
+                                          .find(|&&(_, c)| c.eq_ignore_ascii_case(code))
+                                          .map(|&(day, _)| day)
+                                          .ok_or_else(|| invalid())?;
+                        if !by_weekday.contains(&day) {
+                            by_weekday.push(day);
+                        }
+                    }
+                },
+                "UNTIL" => {
+                    let tm = time::strptime(value, UNTIL_FORMAT).map_err(|_| invalid())?;
+                    until = Some(tm.to_timespec());
+                },
+                "COUNT" => {
+                    let value: u32 = value.parse().map_err(|_| invalid())?;
+                    if value == 0 {
+                        return Err(invalid());
+                    }
+                    count = Some(value);
+                },
+                // Leave anything else to clients that understand it.
+                _ => {},
+            }
+        }
+        let mut recurrence = recurrence.ok_or_else(|| invalid())?;
+        recurrence.interval = interval;
+        recurrence.by_weekday = by_weekday;
+        recurrence.until = until;
+        recurrence.count = count;
+        Ok(recurrence)
+    }
+}
+
+pub struct Occurrences {
+    recurrence: Recurrence,
+    start: Timespec,
+    step: i64,
+    emitted: u32,
+}
+
+impl Occurrences {
+    /// How many candidates in a row may fail to match before the rule is taken to have run
+    /// out. Daily and weekly rules that repeat at all do so within `interval` weeks, and
+    /// monthly and yearly ones within a 400-year cycle of the calendar.
+    fn search_limit(&self) -> i64 {
+        match self.recurrence.frequency {
+            Frequency::Daily | Frequency::Weekly => 7 * (self.recurrence.interval as i64 + 1),
+            Frequency::Monthly | Frequency::Yearly => 400,
+        }
+    }
+
+    /// The `step`th candidate date, which may not match the rule. Monthly and yearly rules
+    /// skip months that don't have the start's day of the month, as RRULE does.
+    fn candidate(&self, step: i64) -> Option<Timespec> {
+        let interval = self.recurrence.interval as i64;
+        let by_weekday = &self.recurrence.by_weekday;
+        match self.recurrence.frequency {
+            Frequency::Daily if by_weekday.is_empty() => Some(add_days(self.start, step * interval)),
+            Frequency::Daily => {
+                let date = add_days(self.start, step);
+                if step % interval == 0 && by_weekday.contains(&Weekday::of(date)) { Some(date) } else { None }
+            },
+            Frequency::Weekly if by_weekday.is_empty() => Some(add_days(self.start, step * 7 * interval)),
+            Frequency::Weekly => {
+                // Weeks start on Monday, and only every `interval`th week counts.
+                let date = add_days(self.start, step);
+                let week = (WEEKDAYS.iter().position(|&(day, _)| day == Weekday::of(self.start)).unwrap_or(0) as i64 + step) / 7;
+                if week % interval == 0 && by_weekday.contains(&Weekday::of(date)) { Some(date) } else { None }
+            },
+            Frequency::Monthly => add_months(self.start, step * interval),
+            Frequency::Yearly => add_months(self.start, step * interval * 12),
+        }
+    }
+}
+
+impl Iterator for Occurrences {
+    type Item = Timespec;
+
+    fn next(&mut self) -> Option<Timespec> {
+        if self.recurrence.count.map_or(false, |count| self.emitted >= count) {
+            return None;
+        }
+        // The start is always the first occurrence.
+        let occurrence = if self.step == 0 {
+            self.step = 1;
+            self.start
+        } else {
+            // Rules narrowed to weekdays step a day at a time, so they can stop as soon as the
+            // days pass `until` instead of searching on for a match.
+            let by_day = !self.recurrence.by_weekday.is_empty() &&
+                         (self.recurrence.frequency == Frequency::Daily || self.recurrence.frequency == Frequency::Weekly);
+            let limit = self.step + self.search_limit();
+            loop {
+                if self.step >= limit {
+                    return None;
+                }
+                let step = self.step;
+                if by_day && self.recurrence.until.map_or(false, |until| add_days(self.start, step) > until) {
+                    return None;
+                }
+                self.step += 1;
+                if let Some(date) = self.candidate(step) {
+                    break date;
+                }
+            }
+        };
+        if self.recurrence.until.map_or(false, |until| occurrence > until) {
+            return None;
+        }
+        self.emitted += 1;
+        Some(occurrence)
+    }
+}
+
+fn days_since_epoch(date: Timespec) -> i64 {
+    let days = date.sec / SECONDS_PER_DAY;
+    if date.sec % SECONDS_PER_DAY < 0 { days - 1 } else { days }
+}
+
+fn add_days(date: Timespec, days: i64) -> Timespec {
+    Timespec::new(date.sec + days * SECONDS_PER_DAY, date.nsec)
+}
+
+fn days_in_month(year: i32, month: i32) -> i32 {
+    match month {
+        1 => if (year % 4 == 0 && year % 100 != 0) || year % 400 == 0 { 29 } else { 28 },
+        3 | 5 | 8 | 10 => 30,
+        _ => 31,
+    }
+}
+
+/// Adds `months` to `date`, keeping the day of the month and time of day, or returns `None`
+/// if the resulting month is too short.
+fn add_months(date: Timespec, months: i64) -> Option<Timespec> {
+    let tm = time::at_utc(date);
+    let month_index = tm.tm_mon as i64 + months;
+    let year = tm.tm_year + 1900 + (month_index / 12) as i32;
+    let month = (month_index % 12) as i32;
+    if tm.tm_mday > days_in_month(year, month) {
+        return None;
+    }
+    let shifted = Tm {
+        tm_year: year - 1900,
+        tm_mon: month,
+        ..tm
+    };
+    Some(shifted.to_timespec())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        Frequency,
+        Recurrence,
+        Weekday,
+    };
+
+    use time::Timespec;
+
+    // 2018-01-31T09:00:00Z, a Wednesday.
+    const START: i64 = 1517389200;
+    const DAY: i64 = 86_400;
+
+    fn dates(recurrence: &Recurrence, count: usize) -> Vec<i64> {
+        recurrence.occurrences(Timespec::new(START, 0)).take(count).map(|date| (date.sec - START) / DAY).collect()
+    }
+
+    #[test]
+    fn test_parse_and_format() {
+        let rule = "FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,FR;UNTIL=20181231T000000Z;COUNT=5";
+        let recurrence: Recurrence = rule.parse().expect("expected a rule");
+        assert_eq!(recurrence.frequency, Frequency::Weekly);
+        assert_eq!(recurrence.interval, 2);
+        assert_eq!(recurrence.by_weekday, vec![Weekday::Monday, Weekday::Friday]);
+        assert_eq!(recurrence.until, Some(Timespec::new(1546214400, 0)));
+        assert_eq!(recurrence.count, Some(5));
+        assert_eq!(recurrence.to_string(), rule);
+
+        assert!("FREQ=HOURLY".parse::<Recurrence>().is_err());
+        assert!("INTERVAL=2".parse::<Recurrence>().is_err());
+        assert!("FREQ=DAILY;INTERVAL=0".parse::<Recurrence>().is_err());
+        assert!("FREQ=DAILY;COUNT=0".parse::<Recurrence>().is_err());
+    }
+
+    #[test]
+    fn test_daily_and_weekly() {
+        let mut daily = Recurrence::new(Frequency::Daily);
+        daily.interval = 3;
+        assert_eq!(dates(&daily, 3), vec![0, 3, 6]);
+
+        let weekly = Recurrence::new(Frequency::Weekly);
+        assert_eq!(dates(&weekly, 3), vec![0, 7, 14]);
+
+        // Starting on a Wednesday, every other week on Monday and Friday.
+        let mut weekdays = Recurrence::new(Frequency::Weekly);
+        weekdays.interval = 2;
+        weekdays.by_weekday = vec![Weekday::Monday, Weekday::Friday];
+        assert_eq!(dates(&weekdays, 4), vec![0, 2, 12, 16]);
+    }
+
+    #[test]
+    fn test_rules_that_never_repeat() {
+        let start = Timespec::new(START, 0);
+        let never: Recurrence = "FREQ=DAILY;INTERVAL=7;BYDAY=MO".parse().expect("expected a rule");
+        assert!(!never.repeats_from(start));
+        assert_eq!(dates(&never, 3), vec![0]);
+
+        let wednesdays: Recurrence = "FREQ=DAILY;INTERVAL=7;BYDAY=WE".parse().expect("expected a rule");
+        assert!(wednesdays.repeats_from(start));
+        assert_eq!(dates(&wednesdays, 3), vec![0, 7, 14]);
+    }
+
+    #[test]
+    fn test_monthly_skips_short_months() {
+        let monthly = Recurrence::new(Frequency::Monthly);
+        // January 31st, then March 31st, May 31st.
+        assert_eq!(dates(&monthly, 3), vec![0, 59, 120]);
+    }
+
+    #[test]
+    fn test_until_and_count() {
+        let mut daily = Recurrence::new(Frequency::Daily);
+        daily.count = Some(2);
+        assert_eq!(dates(&daily, 5), vec![0, 1]);
+        assert_eq!(daily.advanced().count, Some(1));
+        assert_eq!(daily.advanced().next_after(Timespec::new(START, 0)), None);
+
+        daily.count = None;
+        daily.until = Some(Timespec::new(START + 2 * DAY, 0));
+        assert_eq!(dates(&daily, 5), vec![0, 1, 2]);
+
+        let mut never: Recurrence = "FREQ=DAILY;INTERVAL=7;BYDAY=MO".parse().expect("expected a rule");
+        never.until = Some(Timespec::new(START + 30 * DAY, 0));
+        assert_eq!(dates(&never, 3), vec![0]);
+    }
+}
@@ -0,0 +1,122 @@
+// Copyright 2018 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+use time::{
+    Duration,
+    Timespec,
+};
+
+use mentat::{
+    Uuid,
+};
+
+use items::{
+    Item,
+};
+
+use utils::{
+    Entity,
+};
+
+/// When a reminder goes off: at a fixed time, or some time before its item is due.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReminderTrigger {
+    At(Timespec),
+    BeforeDue(Duration),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Reminder {
+    pub id: Option<Entity>,    // id should not be leaked outside of the library
+    pub uuid: Uuid,
+    pub item: Uuid,
+    pub trigger: ReminderTrigger,
+    pub snoozed_until: Option<Timespec>,
+    /// The time the reminder was due to go off when it was last acknowledged.
+    pub acknowledged: Option<Timespec>,
+}
+
+/// A reminder that's due to go off, as returned by `Toodle::pending_reminders`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PendingReminder {
+    pub reminder: Reminder,
+    pub item: Item,
+    pub fire_at: Timespec,
+}
+
+impl Reminder {
+    /// The time the trigger points at, ignoring any snooze. Reminders relative to the due
+    /// date never go off for an item that doesn't have one.
+    pub fn scheduled_at(&self, due_date: Option<Timespec>) -> Option<Timespec> {
+        match self.trigger {
+            ReminderTrigger::At(date) => Some(date),
+            ReminderTrigger::BeforeDue(offset) => due_date.map(|due| due - offset),
+        }
+    }
+
+    /// When the reminder should next go off, or `None` if it's been dealt with. A reminder
+    /// relative to the due date of a recurring item comes back each time the item moves on
+    /// to a new due date.
+    pub fn fire_at(&self, due_date: Option<Timespec>) -> Option<Timespec> {
+        let scheduled = self.scheduled_at(due_date)?;
+        if self.acknowledged == Some(scheduled) {
+            return None;
+        }
+        Some(self.snoozed_until.unwrap_or(scheduled))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        Reminder,
+        ReminderTrigger,
+    };
+
+    use time::{
+        Duration,
+        Timespec,
+    };
+
+    use mentat::{
+        Uuid,
+    };
+
+    fn reminder(trigger: ReminderTrigger) -> Reminder {
+        Reminder {
+            id: None,
+            uuid: Uuid::nil(),
+            item: Uuid::nil(),
+            trigger: trigger,
+            snoozed_until: None,
+            acknowledged: None,
+        }
+    }
+
+    #[test]
+    fn test_fire_at() {
+        let due = Timespec::new(1_000_000, 0);
+        let at = reminder(ReminderTrigger::At(Timespec::new(500, 0)));
+        assert_eq!(at.fire_at(None), Some(Timespec::new(500, 0)));
+
+        let mut before = reminder(ReminderTrigger::BeforeDue(Duration::minutes(10)));
+        assert_eq!(before.fire_at(None), None);
+        assert_eq!(before.fire_at(Some(due)), Some(Timespec::new(999_400, 0)));
+
+        before.snoozed_until = Some(Timespec::new(999_700, 0));
+        assert_eq!(before.fire_at(Some(due)), Some(Timespec::new(999_700, 0)));
+
+        // Acknowledging only covers the due date it was acknowledged for.
+        before.snoozed_until = None;
+        before.acknowledged = Some(Timespec::new(999_400, 0));
+        assert_eq!(before.fire_at(Some(due)), None);
+        assert_eq!(before.fire_at(Some(due + Duration::days(1))), Some(Timespec::new(1_085_800, 0)));
+    }
+}
@@ -0,0 +1,691 @@
+// Copyright 2018 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! The `:toodle/list` vocabulary, and the migrations between its versions.
+//!
+//! Each `Migration` lists only the attributes its version introduces or changes, along with
+//! hooks that transform existing data on the way. Mentat only runs a definition's hooks once
+//! per upgrade, however many versions it skips, so `ensure` steps through each intermediate
+//! version in turn: a store at version 1 upgrading to version 3 runs 2's hooks, then 3's.
+
+use std::collections::BTreeMap;
+
+use time::Timespec;
+
+use mentat::{
+    Attribute,
+    Entid,
+    InProgress,
+    IntoResult,
+    Keyword,
+    Queryable,
+    TypedValue,
+    Uuid,
+    ValueType,
+};
+
+use mentat::errors::{
+    Result,
+};
+
+use mentat::entity_builder::{
+    BuildTerms,
+    TermBuilder,
+};
+
+use mentat::vocabulary::{
+    AttributeBuilder,
+    Definition,
+    HasVocabularies,
+    Version,
+    VersionedStore,
+    Vocabulary,
+};
+
+use mentat::vocabulary::attribute::{
+    Unique
+};
+
+use mentat_core::{
+    KnownEntid,
+};
+
+use lists::{
+    DEFAULT_LIST_COLOR,
+    DEFAULT_LIST_NAME,
+};
+
+use position;
+
+use search;
+
+use utils::{
+    attribute_entid,
+    Entity,
+    ToInner,
+    ToTypedValue,
+};
+
+type Hook = fn(&mut InProgress, &Vocabulary) -> Result<()>;
+
+pub struct Migration {
+    pub version: Version,
+    pub attributes: fn() -> Vec<(Keyword, Attribute)>,
+    pub pre: Hook,
+    pub post: Hook,
+}
+
+/// Every version of the vocabulary, oldest first.
+pub static MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        attributes: v1_attributes,
+        pre: Definition::no_op,
+        post: Definition::no_op,
+    },
+    Migration {
+        version: 2,
+        attributes: v2_attributes,
+        pre: Definition::no_op,
+        post: index_item_names,
+    },
+    Migration {
+        version: 3,
+        attributes: v3_attributes,
+        pre: Definition::no_op,
+        post: Definition::no_op,
+    },
+    Migration {
+        version: 4,
+        attributes: v4_attributes,
+        pre: Definition::no_op,
+        post: Definition::no_op,
+    },
+    Migration {
+        version: 5,
+        attributes: v5_attributes,
+        pre: Definition::no_op,
+        post: move_items_to_inbox,
+    },
+    Migration {
+        version: 6,
+        attributes: v6_attributes,
+        pre: Definition::no_op,
+        post: Definition::no_op,
+    },
+    Migration {
+        version: 7,
+        attributes: v7_attributes,
+        pre: Definition::no_op,
+        post: Definition::no_op,
+    },
+    Migration {
+        version: 8,
+        attributes: v8_attributes,
+        pre: Definition::no_op,
+        post: Definition::no_op,
+    },
+    Migration {
+        version: 9,
+        attributes: v9_attributes,
+        pre: Definition::no_op,
+        post: Definition::no_op,
+    },
+    Migration {
+        version: 10,
+        attributes: v10_attributes,
+        pre: Definition::no_op,
+        post: Definition::no_op,
+    },
+    Migration {
+        version: 11,
+        attributes: v11_attributes,
+        pre: Definition::no_op,
+        post: position_items,
+    },
+    Migration {
+        version: 12,
+        attributes: v12_attributes,
+        pre: Definition::no_op,
+        post: Definition::no_op,
+    },
+    Migration {
+        version: 13,
+        attributes: v13_attributes,
+        pre: Definition::no_op,
+        post: stamp_items,
+    },
+    Migration {
+        version: 14,
+        attributes: v14_attributes,
+        pre: Definition::no_op,
+        post: index_item_trigrams,
+    },
+    Migration {
+        version: 15,
+        attributes: v15_attributes,
+        pre: Definition::no_op,
+        post: clear_undo_stacks,
+    },
+    Migration {
+        version: 16,
+        attributes: v16_attributes,
+        pre: Definition::no_op,
+        post: clear_local_marks,
+    },
+];
+
+pub fn current_version() -> Version {
+    MIGRATIONS.last().map_or(0, |migration| migration.version)
+}
+
+/// Builds the complete definition of `version`, with every attribute introduced up to and
+/// including it. Later versions' attribute definitions replace earlier ones.
+pub fn definition(version: Version) -> Definition {
+    let mut attributes: Vec<(Keyword, Attribute)> = vec![];
+    let mut pre: Hook = Definition::no_op;
+    let mut post: Hook = Definition::no_op;
+    for migration in MIGRATIONS.iter().take_while(|migration| migration.version <= version) {
+        for (keyword, attribute) in (migration.attributes)() {
+            attributes.retain(|&(ref k, _)| k != &keyword);
+            attributes.push((keyword, attribute));
+        }
+        pre = migration.pre;
+        post = migration.post;
+    }
+    Definition {
+        name: kw!(:toodle/list),
+        version: version,
+        attributes: attributes,
+        pre: pre,
+        post: post,
+    }
+}
+
+/// Brings the vocabulary up to `version`. A new store gets that version's definition
+/// directly; an existing one is upgraded a version at a time.
+pub fn ensure(in_progress: &mut InProgress, version: Version) -> Result<()> {
+    let installed = in_progress
+        .read_vocabulary_named(&kw!(:toodle/list))?
+        .map(|vocabulary| vocabulary.version);
+    match installed {
+        None => {
+            in_progress.ensure_vocabulary(&definition(version))?;
+        },
+        Some(installed) => {
+            for migration in MIGRATIONS.iter().filter(|m| m.version > installed && m.version <= version) {
+                in_progress.ensure_vocabulary(&definition(migration.version))?;
+            }
+        },
+    }
+    // Lists arrived in version 5, and from then on there's always one to put new items in.
+    if version >= 5 {
+        ensure_inbox(in_progress)?;
+    }
+    Ok(())
+}
+
+/// Looks up the list that items go in when they aren't given one: the first list, which
+/// starts out as the inbox.
+pub fn default_list<Q: Queryable>(queryable: &Q) -> Result<Option<KnownEntid>> {
+    let query = r#"[:find ?l ?sort_order
+                    :where
+                    [?l :list/sort_order ?sort_order]
+                    :order (asc ?sort_order) (asc ?l)
+                    :limit 1
+    ]"#;
+    let list: Option<Entity> = queryable
+        .q_once(query, None)
+        .into_rel_result()?
+        .into_iter()
+        .next()
+        .and_then(|row| row.into_iter().next())
+        .and_then(|binding| binding.val())
+        .and_then(|v| v.to_inner());
+    Ok(list.map(|list| KnownEntid(list.id)))
+}
+
+/// Creates the inbox if there are no lists, as in a new store, and returns the default list.
+fn ensure_inbox(in_progress: &mut InProgress) -> Result<KnownEntid> {
+    if let Some(list) = default_list(in_progress)? {
+        return Ok(list);
+    }
+    let mut builder = TermBuilder::new();
+    let inbox = builder.named_tempid("inbox".to_string());
+    builder.add(inbox.clone(), attribute_entid(in_progress, kw!(:list/uuid))?, TypedValue::Uuid(Uuid::new_v4()))?;
+    builder.add(inbox.clone(), attribute_entid(in_progress, kw!(:list/name))?, TypedValue::typed_string(DEFAULT_LIST_NAME))?;
+    builder.add(inbox.clone(), attribute_entid(in_progress, kw!(:list/color))?, TypedValue::typed_string(DEFAULT_LIST_COLOR))?;
+    builder.add(inbox, attribute_entid(in_progress, kw!(:list/sort_order))?, TypedValue::Long(0))?;
+    in_progress.transact_builder(builder)?;
+    default_list(in_progress)?.ok_or_else(|| "expected the new inbox".into())
+}
+
+fn v1_attributes() -> Vec<(Keyword, Attribute)> {
+    vec![
+        (kw!(:todo/uuid),
+        AttributeBuilder::default()
+            .value_type(ValueType::Uuid)
+            .multival(false)
+            .unique(Unique::Value)
+            .index(true)
+            .build()),
+        (kw!(:todo/name),
+        AttributeBuilder::default()
+            .value_type(ValueType::String)
+            .multival(false)
+            .build()),
+        (kw!(:todo/due_date),
+        AttributeBuilder::default()
+            .value_type(ValueType::Instant)
+            .multival(false)
+            .build()),
+        (kw!(:todo/completion_date),
+        AttributeBuilder::default()
+            .value_type(ValueType::Instant)
+            .multival(false)
+            .build()),
+        (kw!(:todo/label),
+        AttributeBuilder::default()
+            .value_type(ValueType::Ref)
+            .multival(true)
+            .build()),
+        (kw!(:label/name),
+        AttributeBuilder::default()
+            .value_type(ValueType::String)
+            .multival(false)
+            .unique(Unique::Identity)
+            .index(true)
+            .fulltext(true)
+            .build()),
+        (kw!(:label/color),
+        AttributeBuilder::default()
+            .value_type(ValueType::String)
+            .multival(false)
+            .build()),
+    ]
+}
+
+fn v2_attributes() -> Vec<(Keyword, Attribute)> {
+    vec![
+        // Mentat can't make an existing attribute fulltext, so we index a copy of each
+        // item's name instead.
+        (kw!(:todo/name_fulltext),
+        AttributeBuilder::default()
+            .value_type(ValueType::String)
+            .multival(false)
+            .fulltext(true)
+            .build()),
+    ]
+}
+
+fn v3_attributes() -> Vec<(Keyword, Attribute)> {
+    vec![
+        (kw!(:todo/notes),
+        AttributeBuilder::default()
+            .value_type(ValueType::String)
+            .multival(false)
+            .fulltext(true)
+            .build()),
+    ]
+}
+
+fn v4_attributes() -> Vec<(Keyword, Attribute)> {
+    vec![
+        (kw!(:todo/parent),
+        AttributeBuilder::default()
+            .value_type(ValueType::Ref)
+            .multival(false)
+            .index(true)
+            .build()),
+    ]
+}
+
+fn v5_attributes() -> Vec<(Keyword, Attribute)> {
+    vec![
+        (kw!(:list/uuid),
+        AttributeBuilder::default()
+            .value_type(ValueType::Uuid)
+            .multival(false)
+            .unique(Unique::Value)
+            .index(true)
+            .build()),
+        (kw!(:list/name),
+        AttributeBuilder::default()
+            .value_type(ValueType::String)
+            .multival(false)
+            .build()),
+        (kw!(:list/color),
+        AttributeBuilder::default()
+            .value_type(ValueType::String)
+            .multival(false)
+            .build()),
+        (kw!(:list/sort_order),
+        AttributeBuilder::default()
+            .value_type(ValueType::Long)
+            .multival(false)
+            .build()),
+        (kw!(:todo/list),
+        AttributeBuilder::default()
+            .value_type(ValueType::Ref)
+            .multival(false)
+            .index(true)
+            .build()),
+    ]
+}
+
+fn v6_attributes() -> Vec<(Keyword, Attribute)> {
+    vec![
+        // An RRULE, such as `FREQ=WEEKLY;BYDAY=MO`.
+        (kw!(:todo/recurrence),
+        AttributeBuilder::default()
+            .value_type(ValueType::String)
+            .multival(false)
+            .build()),
+        (kw!(:todo/completions),
+        AttributeBuilder::default()
+            .value_type(ValueType::Instant)
+            .multival(true)
+            .build()),
+    ]
+}
+
+fn v7_attributes() -> Vec<(Keyword, Attribute)> {
+    vec![
+        (kw!(:reminder/uuid),
+        AttributeBuilder::default()
+            .value_type(ValueType::Uuid)
+            .multival(false)
+            .unique(Unique::Value)
+            .index(true)
+            .build()),
+        (kw!(:reminder/item),
+        AttributeBuilder::default()
+            .value_type(ValueType::Ref)
+            .multival(false)
+            .index(true)
+            .build()),
+        (kw!(:reminder/at),
+        AttributeBuilder::default()
+            .value_type(ValueType::Instant)
+            .multival(false)
+            .build()),
+        // Seconds before the item's due date.
+        (kw!(:reminder/offset),
+        AttributeBuilder::default()
+            .value_type(ValueType::Long)
+            .multival(false)
+            .build()),
+        (kw!(:reminder/snoozed_until),
+        AttributeBuilder::default()
+            .value_type(ValueType::Instant)
+            .multival(false)
+            .build()),
+        (kw!(:reminder/acknowledged),
+        AttributeBuilder::default()
+            .value_type(ValueType::Instant)
+            .multival(false)
+            .build()),
+    ]
+}
+
+fn v8_attributes() -> Vec<(Keyword, Attribute)> {
+    vec![
+        // The id of a transaction that can be undone, or redone. No longer used since version
+        // 15; the stacks are kept on the device instead. See `local`.
+        (kw!(:undo/tx),
+        AttributeBuilder::default()
+            .value_type(ValueType::Long)
+            .multival(false)
+            .unique(Unique::Value)
+            .build()),
+        (kw!(:undo/redo),
+        AttributeBuilder::default()
+            .value_type(ValueType::Boolean)
+            .multival(false)
+            .build()),
+    ]
+}
+
+fn v9_attributes() -> Vec<(Keyword, Attribute)> {
+    vec![
+        // Asserted about each transaction made on this device, as opposed to arriving by sync.
+        // No longer used since version 16.
+        (kw!(:toodle.tx/local),
+        AttributeBuilder::default()
+            .value_type(ValueType::Boolean)
+            .multival(false)
+            .build()),
+    ]
+}
+
+fn v10_attributes() -> Vec<(Keyword, Attribute)> {
+    vec![
+        // When the item was put in the trash. Trashed items are left out of fetches until
+        // they're restored, or removed for good when the trash is emptied.
+        (kw!(:todo/deleted_at),
+        AttributeBuilder::default()
+            .value_type(ValueType::Instant)
+            .multival(false)
+            .index(true)
+            .build()),
+    ]
+}
+
+fn v11_attributes() -> Vec<(Keyword, Attribute)> {
+    vec![
+        // Where the item goes when items are put in order by hand. See `position`.
+        (kw!(:todo/position),
+        AttributeBuilder::default()
+            .value_type(ValueType::String)
+            .multival(false)
+            .index(true)
+            .build()),
+    ]
+}
+
+fn v12_attributes() -> Vec<(Keyword, Attribute)> {
+    vec![
+        // The ids of the transactions that are undone, or redone, together. Changes made
+        // through `Toodle::transact` can take several. No longer used since version 15.
+        (kw!(:undo/tx),
+        AttributeBuilder::default()
+            .value_type(ValueType::Long)
+            .multival(true)
+            .unique(Unique::Value)
+            .build()),
+    ]
+}
+
+fn v13_attributes() -> Vec<(Keyword, Attribute)> {
+    vec![
+        // The instant of the first transaction to touch the item.
+        (kw!(:todo/created_at),
+        AttributeBuilder::default()
+            .value_type(ValueType::Instant)
+            .multival(false)
+            .index(true)
+            .build()),
+        // The instant of the latest transaction to touch the item.
+        (kw!(:todo/modified_at),
+        AttributeBuilder::default()
+            .value_type(ValueType::Instant)
+            .multival(false)
+            .index(true)
+            .build()),
+    ]
+}
+
+fn v14_attributes() -> Vec<(Keyword, Attribute)> {
+    vec![
+        // The trigrams of each item's name, as `search::trigrams` splits them, so that
+        // `ItemQuery::name_contains` can match in the middle of a word.
+        (kw!(:todo/name_trigrams),
+        AttributeBuilder::default()
+            .value_type(ValueType::String)
+            .multival(false)
+            .fulltext(true)
+            .build()),
+    ]
+}
+
+fn v15_attributes() -> Vec<(Keyword, Attribute)> {
+    // Nothing new; this version only moves the undo and redo stacks out of the store.
+    vec![]
+}
+
+fn v16_attributes() -> Vec<(Keyword, Attribute)> {
+    // Nothing new; this version only moves the record of which transactions were made on
+    // this device out of the store.
+    vec![]
+}
+
+/// Copies the names of items created before version 2 into `:todo/name_fulltext`, so that
+/// they show up in searches.
+fn index_item_names(in_progress: &mut InProgress, _from: &Vocabulary) -> Result<()> {
+    let query = r#"[:find ?eid ?name
+                    :where
+                    [?eid :todo/name ?name]
+    ]"#;
+    let rows = in_progress.q_once(query, None).into_rel_result()?;
+    let name_fulltext = attribute_entid(in_progress, kw!(:todo/name_fulltext))?;
+    let mut builder = TermBuilder::new();
+    for row in rows.into_iter() {
+        let entity: Option<Entity> = row[0].clone().val().and_then(|v| v.to_inner());
+        if let (Some(entity), Some(name)) = (entity, row[1].clone().val()) {
+            builder.add(KnownEntid(entity.id), name_fulltext, name)?;
+        }
+    }
+    in_progress.transact_builder(builder).and(Ok(()))
+}
+
+/// Items created before version 5 don't belong to a list, so we gather them into an inbox.
+fn move_items_to_inbox(in_progress: &mut InProgress, _from: &Vocabulary) -> Result<()> {
+    let query = r#"[:find [?eid ...]
+                    :where
+                    [?eid :todo/uuid _]
+                    (not [?eid :todo/list _])
+    ]"#;
+    let items = in_progress.q_once(query, None).into_coll_result()?;
+    if items.is_empty() {
+        return Ok(());
+    }
+    let inbox = ensure_inbox(in_progress)?;
+    let todo_list = attribute_entid(in_progress, kw!(:todo/list))?;
+    let mut builder = TermBuilder::new();
+    for item in items {
+        let entity: Option<Entity> = item.val().and_then(|v| v.to_inner());
+        if let Some(entity) = entity {
+            builder.add(KnownEntid(entity.id), todo_list, TypedValue::Ref(inbox.0))?;
+        }
+    }
+    in_progress.transact_builder(builder).and(Ok(()))
+}
+
+/// Items from before version 11 were shown in the order they were created, so that's the
+/// order they're put in.
+fn position_items(in_progress: &mut InProgress, _from: &Vocabulary) -> Result<()> {
+    let query = r#"[:find [?eid ...]
+                    :where
+                    [?eid :todo/uuid _]
+                    :order (asc ?eid)
+    ]"#;
+    let items = in_progress.q_once(query, None).into_coll_result()?;
+    let todo_position = attribute_entid(in_progress, kw!(:todo/position))?;
+    let mut builder = TermBuilder::new();
+    let mut last: Option<String> = None;
+    for item in items {
+        let entity: Option<Entity> = item.val().and_then(|v| v.to_inner());
+        if let Some(entity) = entity {
+            let next = position::between(last.as_ref().map(|p| p.as_str()), None);
+            builder.add(KnownEntid(entity.id), todo_position, TypedValue::typed_string(&next))?;
+            last = Some(next);
+        }
+    }
+    in_progress.transact_builder(builder).and(Ok(()))
+}
+
+/// Items from before version 13 weren't stamped as they changed, so their stamps are read back
+/// out of the transaction log.
+fn stamp_items(in_progress: &mut InProgress, _from: &Vocabulary) -> Result<()> {
+    let query = r#"[:find ?eid ?instant
+                    :where
+                    [?tx :db/txInstant ?instant]
+                    [(tx-data $ ?tx) [[?eid _ _ _ _]]]
+                    [?eid :todo/uuid _]
+    ]"#;
+    let rows = in_progress.q_once(query, None).into_rel_result()?;
+    let mut stamps: BTreeMap<Entid, (Timespec, Timespec)> = BTreeMap::new();
+    for row in rows.into_iter() {
+        let entity: Option<Entity> = row[0].clone().val().and_then(|v| v.to_inner());
+        let instant: Option<Timespec> = row[1].clone().val().and_then(|v| v.to_inner());
+        if let (Some(entity), Some(instant)) = (entity, instant) {
+            let stamp = stamps.entry(entity.id).or_insert((instant, instant));
+            stamp.0 = stamp.0.min(instant);
+            stamp.1 = stamp.1.max(instant);
+        }
+    }
+    let created_at = attribute_entid(in_progress, kw!(:todo/created_at))?;
+    let modified_at = attribute_entid(in_progress, kw!(:todo/modified_at))?;
+    let mut builder = TermBuilder::new();
+    for (entid, (created, modified)) in stamps {
+        builder.add(KnownEntid(entid), created_at, created.to_typed_value())?;
+        builder.add(KnownEntid(entid), modified_at, modified.to_typed_value())?;
+    }
+    in_progress.transact_builder(builder).and(Ok(()))
+}
+
+/// Indexes the trigrams of the names of items created before version 14.
+fn index_item_trigrams(in_progress: &mut InProgress, _from: &Vocabulary) -> Result<()> {
+    let query = r#"[:find ?eid ?name
+                    :where
+                    [?eid :todo/name ?name]
+    ]"#;
+    let rows = in_progress.q_once(query, None).into_rel_result()?;
+    let name_trigrams = attribute_entid(in_progress, kw!(:todo/name_trigrams))?;
+    let mut builder = TermBuilder::new();
+    for row in rows.into_iter() {
+        let entity: Option<Entity> = row[0].clone().val().and_then(|v| v.to_inner());
+        let name: Option<String> = row[1].clone().val().and_then(|v| v.to_inner());
+        if let (Some(entity), Some(name)) = (entity, name) {
+            builder.add(KnownEntid(entity.id), name_trigrams, TypedValue::typed_string(&search::trigrams(&name)))?;
+        }
+    }
+    in_progress.transact_builder(builder).and(Ok(()))
+}
+
+/// Before version 15 the undo and redo stacks were kept in the store, where they synced to
+/// devices whose transaction ids don't match. They're kept on the device now, so what was in
+/// the store is retracted.
+fn clear_undo_stacks(in_progress: &mut InProgress, _from: &Vocabulary) -> Result<()> {
+    retract_all(in_progress, &[kw!(:undo/tx), kw!(:undo/redo)])
+}
+
+/// Before version 16 transactions made on this device were marked with `:toodle.tx/local`, but
+/// the marks synced with them, so every device took every change for its own. Which
+/// transactions were made here is kept on the device now, so the marks are retracted.
+fn clear_local_marks(in_progress: &mut InProgress, _from: &Vocabulary) -> Result<()> {
+    retract_all(in_progress, &[kw!(:toodle.tx/local)])
+}
+
+/// Retracts every value of each of `attributes`.
+fn retract_all(in_progress: &mut InProgress, attributes: &[Keyword]) -> Result<()> {
+    let mut builder = TermBuilder::new();
+    for attribute in attributes.iter() {
+        let query = format!("[:find ?entry ?value :where [?entry {} ?value]]", attribute);
+        let entid = attribute_entid(in_progress, attribute.clone())?;
+        for row in in_progress.q_once(&query, None).into_rel_result()?.into_iter() {
+            let entry: Option<Entity> = row[0].clone().val().and_then(|v| v.to_inner());
+            if let (Some(entry), Some(value)) = (entry, row[1].clone().val()) {
+                builder.retract(KnownEntid(entry.id), entid, value)?;
+            }
+        }
+    }
+    in_progress.transact_builder(builder).and(Ok(()))
+}
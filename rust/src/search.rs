@@ -0,0 +1,160 @@
+// Copyright 2018 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+use items::{
+    Item,
+};
+
+/// How many characters of context to keep on either side of a match in a snippet.
+const SNIPPET_CONTEXT: usize = 30;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchResult {
+    pub item: Item,
+    pub score: f64,
+    pub snippet: String,
+}
+
+/// Splits user input into lowercase search terms, dropping anything SQLite's full-text
+/// query syntax would treat specially.
+pub fn terms(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|term| !term.is_empty())
+        .map(|term| term.to_lowercase())
+        .collect()
+}
+
+/// Builds a full-text `MATCH` expression that finds every term as a word prefix, so that
+/// partially typed words match as the user types.
+pub fn match_expression(terms: &[String]) -> String {
+    terms.iter().map(|term| format!("{}*", term)).collect::<Vec<_>>().join(" ")
+}
+
+/// Breaks `text` into what `:todo/name_trigrams` indexes, so that names can be matched by
+/// substring: every run of three characters, and the shorter runs at the end. Each run is
+/// lowercased and written as hex, which SQLite's tokenizer keeps as a single word.
+pub fn trigrams(text: &str) -> String {
+    let chars: Vec<char> = text.to_lowercase().chars().collect();
+    (0..chars.len()).map(|i| hex(&chars[i..(i + 3).min(chars.len())]))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+}
+
+/// Builds a full-text `MATCH` expression that finds `text` anywhere in something indexed by
+/// `trigrams`: as a phrase of its own runs of three characters, or as a prefix of a run if
+/// it's shorter than that.
+pub fn substring_expression(text: &str) -> Option<String> {
+    let chars: Vec<char> = text.to_lowercase().chars().collect();
+    match chars.len() {
+        0 => None,
+        1 | 2 => Some(format!("{}*", hex(&chars))),
+        _ => Some(format!("\"{}\"", chars.windows(3).map(hex).collect::<Vec<_>>().join(" "))),
+    }
+}
+
+fn hex(chars: &[char]) -> String {
+    chars.iter().collect::<String>().bytes().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Mentat doesn't score full-text matches yet, so we rank them ourselves: every word in
+/// `text` that starts with a search term counts once, and a match at the very start of the
+/// text gets a bonus.
+pub fn score(text: &str, terms: &[String]) -> f64 {
+    let words: Vec<String> = text.split(|c: char| !c.is_alphanumeric())
+                                 .filter(|word| !word.is_empty())
+                                 .map(|word| word.to_lowercase())
+                                 .collect();
+    let matches = words.iter()
+                       .filter(|word| terms.iter().any(|term| word.starts_with(term.as_str())))
+                       .count();
+    let leading = words.first()
+                       .map_or(false, |word| terms.iter().any(|term| word.starts_with(term.as_str())));
+    matches as f64 + if leading { 0.5 } else { 0.0 }
+}
+
+/// Returns the part of `text` around the first match of any term, with an ellipsis
+/// wherever it's been cut short.
+pub fn snippet(text: &str, terms: &[String]) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let lowercase: Vec<char> = text.to_lowercase().chars().collect();
+    if chars.len() != lowercase.len() {
+        // Lowercasing changed the length, so positions don't line up; fall back to the
+        // start of the text.
+        return truncate(&chars, 0);
+    }
+    let position = terms.iter()
+                        .filter_map(|term| {
+                            let term: Vec<char> = term.chars().collect();
+                            lowercase.windows(term.len()).position(|window| window == &term[..])
+                        })
+                        .min()
+                        .unwrap_or(0);
+    truncate(&chars, position)
+}
+
+fn truncate(chars: &[char], position: usize) -> String {
+    let start = position.saturating_sub(SNIPPET_CONTEXT);
+    let end = (position + SNIPPET_CONTEXT * 2).min(chars.len());
+    let mut snippet = String::new();
+    if start > 0 {
+        snippet.push('…');
+    }
+    snippet.extend(chars[start..end].iter());
+    if end < chars.len() {
+        snippet.push('…');
+    }
+    snippet
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        match_expression,
+        score,
+        snippet,
+        substring_expression,
+        terms,
+        trigrams,
+    };
+
+    #[test]
+    fn test_terms() {
+        assert_eq!(terms("Buy  \"milk\" OR*"), vec!["buy", "milk", "or"]);
+        assert_eq!(match_expression(&terms("buy mi")), "buy* mi*");
+        assert!(terms("  -- ").is_empty());
+    }
+
+    #[test]
+    fn test_trigrams() {
+        assert_eq!(trigrams("Port"), "706f72 6f7274 7274 74");
+        assert_eq!(substring_expression("POR"), Some("\"706f72\"".to_string()));
+        assert_eq!(substring_expression("port"), Some("\"706f72 6f7274\"".to_string()));
+        assert_eq!(substring_expression("rt"), Some("7274*".to_string()));
+        assert_eq!(substring_expression(""), None);
+    }
+
+    #[test]
+    fn test_score() {
+        let query = terms("milk");
+        assert!(score("Milk and more milk", &query) > score("Buy milk", &query));
+        assert!(score("Milk run", &query) > score("Buy milk", &query));
+        assert_eq!(score("Buy bread", &query), 0.0);
+    }
+
+    #[test]
+    fn test_snippet() {
+        let query = terms("milk");
+        assert_eq!(snippet("Buy milk", &query), "Buy milk");
+
+        let text = format!("{}milk{}", "a".repeat(40), "b".repeat(100));
+        let expected = format!("…{}milk{}…", "a".repeat(30), "b".repeat(56));
+        assert_eq!(snippet(&text, &query), expected);
+    }
+}
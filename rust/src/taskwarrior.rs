@@ -0,0 +1,196 @@
+// Copyright 2018 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! Taskwarrior import and export, in the JSON format of `task export` and `task import`.
+//!
+//! A task's `uuid` is the item's UUID, `description` its name, `due` its due date and `tags`
+//! its labels. Completed tasks have `status` `completed` and their completion date as `end`;
+//! every other item is `pending`. `entry` and `modified` are when the item was created and
+//! last changed.
+//!
+//! Taskwarrior times only go down to the second, so an item whose time is within the same
+//! second as the one it's given on import keeps its own. Importing merges by UUID and keeps
+//! `entry` as the creation date; `modified` is left to Toodle, and notes, recurrences, lists
+//! and parents, which Taskwarrior keeps differently, are left alone. Deleted tasks and
+//! recurring task templates aren't imported.
+
+use std::collections::BTreeMap;
+
+use serde_json;
+
+use time::{
+    self,
+    Timespec,
+};
+
+use mentat::{
+    Uuid,
+};
+
+use errors::{
+    ErrorKind,
+    Result,
+};
+
+use json::{
+    self,
+    Export,
+    ItemInfo,
+    LabelInfo,
+    EXPORT_VERSION,
+};
+
+use query::{
+    ItemQuery,
+};
+
+use Store;
+
+use Toodle;
+
+const DATE_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Task {
+    uuid: String,
+
+    description: String,
+
+    status: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    entry: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    modified: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    due: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    end: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+}
+
+pub fn export_taskwarrior(store: &mut Store) -> Result<String> {
+    let mut tasks = vec![];
+    for item in store.fetch_items(&ItemQuery::new())?.vec {
+        let status = if item.completion_date.is_some() { "completed" } else { "pending" };
+        tasks.push(Task {
+            uuid: item.uuid.hyphenated().to_string(),
+            description: item.name.clone(),
+            status: status.to_string(),
+            entry: item.created_at.as_ref().map(format_date),
+            modified: item.modified_at.as_ref().map(format_date),
+            due: item.due_date.as_ref().map(format_date),
+            end: item.completion_date.as_ref().map(format_date),
+            tags: item.labels.iter().map(|label| label.name.clone()).collect(),
+        });
+    }
+    serde_json::to_string_pretty(&tasks).map_err(|e| ErrorKind::InvalidTaskwarrior(e.to_string()).into())
+}
+
+pub fn import_taskwarrior(store: &mut Store, json: &str) -> Result<()> {
+    let invalid = |e: serde_json::Error| ErrorKind::InvalidTaskwarrior(e.to_string());
+    // `task export` writes an array, and older versions a task per line.
+    let tasks: Vec<Task> = if json.trim_left().starts_with('[') {
+        serde_json::from_str(json).map_err(invalid)?
+    } else {
+        serde_json::Deserializer::from_str(json).into_iter::<Task>().collect::<::std::result::Result<_, _>>().map_err(invalid)?
+    };
+
+    let mut todos: BTreeMap<String, ItemInfo> = BTreeMap::new();
+    for task in tasks {
+        let uuid = Uuid::parse_str(&task.uuid)
+            .map_err(|_| ErrorKind::InvalidTaskwarrior(format!("invalid UUID {}", task.uuid)))?;
+        let existing = store.fetch_item(&uuid)?;
+        let current_completion_date = existing.as_ref().and_then(|item| item.completion_date);
+        let completion_date = match &task.status[..] {
+            "pending" | "waiting" => None,
+            "completed" => match task.end {
+                Some(ref end) => Some(keep_time(parse_date(end)?, current_completion_date)),
+                None => Some(current_completion_date.unwrap_or_else(|| time::now_utc().to_timespec())),
+            },
+            "deleted" | "recurring" => continue,
+            status => bail!(ErrorKind::InvalidTaskwarrior(format!("unknown status {}", status))),
+        };
+        let due_date = match task.due {
+            Some(ref due) => Some(keep_time(parse_date(due)?, existing.as_ref().and_then(|item| item.due_date))),
+            None => None,
+        };
+        let created_at = match task.entry {
+            Some(ref entry) => Some(keep_time(parse_date(entry)?, existing.as_ref().and_then(|item| item.created_at))),
+            None => None,
+        };
+        let labels = task.tags.into_iter().map(|name| LabelInfo { name: name, color: None }).collect();
+        todos.insert(uuid.hyphenated().to_string(), ItemInfo {
+            uuid: None,
+            name: task.description,
+            notes: None,
+            due_date: due_date.as_ref().map(json::to_millis),
+            completion_date: completion_date.as_ref().map(json::to_millis),
+            recurrence: None,
+            list: None,
+            parent: None,
+            position: None,
+            created_at: created_at.as_ref().map(json::to_millis),
+            modified_at: None,
+            child_count: 0,
+            labels: Some(labels),
+        });
+    }
+    let export = Export {
+        version: EXPORT_VERSION,
+        labels: vec![],
+        lists: BTreeMap::new(),
+        todos: todos,
+    };
+    json::import(store, export, &[kw!(:todo/notes), kw!(:todo/recurrence), kw!(:todo/list), kw!(:todo/parent)])
+}
+
+/// `date`, unless `current` is the same to the second.
+fn keep_time(date: Timespec, current: Option<Timespec>) -> Timespec {
+    match current {
+        Some(current) if current.sec == date.sec => current,
+        _ => date,
+    }
+}
+
+fn format_date(date: &Timespec) -> String {
+    let tm = time::at_utc(*date);
+    format!("{:04}{:02}{:02}T{:02}{:02}{:02}Z",
+            tm.tm_year + 1900, tm.tm_mon + 1, tm.tm_mday, tm.tm_hour, tm.tm_min, tm.tm_sec)
+}
+
+fn parse_date(date: &str) -> Result<Timespec> {
+    let tm = time::strptime(date, DATE_FORMAT)
+        .map_err(|_| ErrorKind::InvalidTaskwarrior(format!("invalid date {}", date)))?;
+    Ok(tm.to_timespec())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        format_date,
+        parse_date,
+    };
+
+    use time::Timespec;
+
+    #[test]
+    fn test_dates() {
+        let date = Timespec::new(1_517_389_200, 0);
+        assert_eq!(format_date(&date), "20180131T090000Z");
+        assert_eq!(parse_date("20180131T090000Z").expect("expected a date"), date);
+        assert!(parse_date("2018-01-31").is_err());
+    }
+}
@@ -0,0 +1,284 @@
+// Copyright 2018 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! todo.txt import and export.
+//!
+//! Each item is a line:
+//!
+//! ```text
+//! x 2018-01-31 2018-01-20 Write report +work @office due:2018-02-01 uuid:<todo uuid>
+//! ```
+//!
+//! A leading `x` and the date after it are the completion date, the date after those, or
+//! after the priority, is the creation date, `due:` is the due date, and `+project`s and
+//! `@context`s are labels. Projects are labelled with their name and contexts keep their
+//! `@`, so that each is written back the way it was read. Everything else, priorities and
+//! other `key:value` extensions included, stays in the item's name just as it was.
+//!
+//! todo.txt dates have no time, and are read and written in UTC. Importing merges by the
+//! `uuid:` each item is exported with. Lines without one are matched by their text, so that
+//! importing a hand-written file again doesn't duplicate it. An item whose date is on the day
+//! it already has keeps its time. Notes, recurrences, lists and parents have no equivalent in
+//! todo.txt, so they're left alone.
+
+use std::collections::{
+    BTreeMap,
+    HashMap,
+};
+
+use time::{
+    self,
+    Timespec,
+};
+
+use uuid;
+
+use mentat::{
+    Uuid,
+};
+
+use errors::{
+    Result,
+};
+
+use items::{
+    Item,
+};
+
+use json::{
+    self,
+    Export,
+    ItemInfo,
+    LabelInfo,
+    EXPORT_VERSION,
+};
+
+use query::{
+    ItemQuery,
+};
+
+use Store;
+
+use Toodle;
+
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+pub fn export_todo_txt(store: &mut Store) -> Result<String> {
+    let mut text = String::new();
+    for item in store.fetch_items(&ItemQuery::new())?.vec.iter() {
+        text.push_str(&format_item(item));
+        text.push('\n');
+    }
+    Ok(text)
+}
+
+pub fn import_todo_txt(store: &mut Store, text: &str) -> Result<()> {
+    let mut todos: BTreeMap<String, ItemInfo> = BTreeMap::new();
+    // How many times each line without a UUID has been seen, so that repeated lines stay
+    // separate items.
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    for line in text.lines().filter(|line| !line.trim().is_empty()) {
+        let task = parse_line(line);
+        let uuid = match task.uuid {
+            Some(uuid) => uuid,
+            None => {
+                let count = seen.entry(task.name.clone()).or_insert(0);
+                *count += 1;
+                Uuid::new_v5(&uuid::NAMESPACE_URL, &format!("todo.txt {} {}", count, task.name))
+            },
+        };
+        let existing = store.fetch_item(&uuid)?;
+        let keep_time = |date: Option<Timespec>, current: Option<Timespec>| match (date, current) {
+            (Some(date), Some(current)) if format_date(&date) == format_date(&current) => Some(current),
+            (date, _) => date,
+        };
+        let current_completion_date = existing.as_ref().and_then(|item| item.completion_date);
+        let completion_date = match (task.completed, task.completion_date) {
+            (false, _) => None,
+            (true, Some(date)) => keep_time(Some(date), current_completion_date),
+            (true, None) => Some(current_completion_date.unwrap_or_else(|| time::now_utc().to_timespec())),
+        };
+        let due_date = keep_time(task.due_date, existing.as_ref().and_then(|item| item.due_date));
+        let created_at = keep_time(task.created_at, existing.as_ref().and_then(|item| item.created_at));
+        let labels = task.labels.into_iter().map(|name| LabelInfo { name: name, color: None }).collect();
+        todos.insert(uuid.hyphenated().to_string(), ItemInfo {
+            uuid: None,
+            name: task.name,
+            notes: None,
+            due_date: due_date.as_ref().map(json::to_millis),
+            completion_date: completion_date.as_ref().map(json::to_millis),
+            recurrence: None,
+            list: None,
+            parent: None,
+            position: None,
+            created_at: created_at.as_ref().map(json::to_millis),
+            modified_at: None,
+            child_count: 0,
+            labels: Some(labels),
+        });
+    }
+    let export = Export {
+        version: EXPORT_VERSION,
+        labels: vec![],
+        lists: BTreeMap::new(),
+        todos: todos,
+    };
+    json::import(store, export, &[kw!(:todo/notes), kw!(:todo/recurrence), kw!(:todo/list), kw!(:todo/parent)])
+}
+
+/// What a line of todo.txt says about an item.
+#[derive(Debug, PartialEq)]
+struct Task {
+    uuid: Option<Uuid>,
+    name: String,
+    completed: bool,
+    completion_date: Option<Timespec>,
+    created_at: Option<Timespec>,
+    due_date: Option<Timespec>,
+    labels: Vec<String>,
+}
+
+fn parse_line(line: &str) -> Task {
+    let mut tokens = line.split_whitespace().peekable();
+    let mut task = Task {
+        uuid: None,
+        name: String::new(),
+        completed: false,
+        completion_date: None,
+        created_at: None,
+        due_date: None,
+        labels: vec![],
+    };
+    let mut name: Vec<&str> = vec![];
+    if tokens.peek() == Some(&"x") {
+        tokens.next();
+        task.completed = true;
+        if let Some(date) = tokens.peek().and_then(|token| parse_date(token)) {
+            tokens.next();
+            task.completion_date = Some(date);
+        }
+    } else if tokens.peek().map_or(false, |token| is_priority(token)) {
+        name.extend(tokens.next());
+    }
+    if let Some(date) = tokens.peek().and_then(|token| parse_date(token)) {
+        tokens.next();
+        task.created_at = Some(date);
+    }
+    for token in tokens {
+        if token.len() > 1 && token.starts_with('+') {
+            push_label(&mut task.labels, &token[1..]);
+        } else if token.len() > 1 && token.starts_with('@') {
+            push_label(&mut task.labels, token);
+        } else if token.starts_with("due:") && parse_date(&token[4..]).is_some() {
+            task.due_date = parse_date(&token[4..]);
+        } else if token.starts_with("uuid:") && Uuid::parse_str(&token[5..]).is_ok() {
+            task.uuid = Uuid::parse_str(&token[5..]).ok();
+        } else {
+            name.push(token);
+        }
+    }
+    task.name = name.join(" ");
+    task
+}
+
+fn format_item(item: &Item) -> String {
+    let mut tokens = vec![];
+    let mut name = item.name.as_str();
+    if let Some(ref completion_date) = item.completion_date {
+        tokens.push("x".to_string());
+        tokens.push(format_date(completion_date));
+    } else if let Some(index) = name.find(' ') {
+        // The creation date goes after the priority.
+        if is_priority(&name[..index]) {
+            tokens.push(name[..index].to_string());
+            name = &name[index + 1..];
+        }
+    }
+    if let Some(ref created_at) = item.created_at {
+        tokens.push(format_date(created_at));
+    }
+    if !name.is_empty() {
+        tokens.push(name.to_string());
+    }
+    for label in item.labels.iter() {
+        // Tokens can't have spaces in them.
+        let name = label.name.split_whitespace().collect::<Vec<&str>>().join("_");
+        if name.starts_with('@') {
+            tokens.push(name);
+        } else {
+            tokens.push(format!("+{}", name));
+        }
+    }
+    if let Some(ref due_date) = item.due_date {
+        tokens.push(format!("due:{}", format_date(due_date)));
+    }
+    tokens.push(format!("uuid:{}", item.uuid.hyphenated()));
+    tokens.join(" ")
+}
+
+fn push_label(labels: &mut Vec<String>, name: &str) {
+    if !labels.iter().any(|label| label == name) {
+        labels.push(name.to_string());
+    }
+}
+
+fn is_priority(token: &str) -> bool {
+    let bytes = token.as_bytes();
+    bytes.len() == 3 && bytes[0] == b'(' && bytes[1].is_ascii_uppercase() && bytes[2] == b')'
+}
+
+fn parse_date(token: &str) -> Option<Timespec> {
+    if token.len() != 10 {
+        return None;
+    }
+    time::strptime(token, DATE_FORMAT).ok().map(|tm| tm.to_timespec())
+}
+
+fn format_date(date: &Timespec) -> String {
+    let tm = time::at_utc(*date);
+    format!("{:04}-{:02}-{:02}", tm.tm_year + 1900, tm.tm_mon + 1, tm.tm_mday)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        parse_line,
+        Task,
+    };
+
+    use mentat::Uuid;
+
+    use time::Timespec;
+
+    #[test]
+    fn test_parse_line() {
+        let uuid = Uuid::new_v4();
+        let line = format!("x 2018-01-31 2018-01-20 (A) Call Mom +family @phone due:2018-02-01 t:2018-01-25 uuid:{}", uuid.hyphenated());
+        assert_eq!(parse_line(&line), Task {
+            uuid: Some(uuid),
+            name: "(A) Call Mom t:2018-01-25".to_string(),
+            completed: true,
+            completion_date: Some(Timespec::new(1_517_356_800, 0)),
+            created_at: Some(Timespec::new(1_516_406_400, 0)),
+            due_date: Some(Timespec::new(1_517_443_200, 0)),
+            labels: vec!["family".to_string(), "@phone".to_string()],
+        });
+
+        assert_eq!(parse_line("(B) 2018-01-20 Pay rent due:soon http://example.com"), Task {
+            uuid: None,
+            name: "(B) Pay rent due:soon http://example.com".to_string(),
+            completed: false,
+            completion_date: None,
+            created_at: Some(Timespec::new(1_516_406_400, 0)),
+            due_date: None,
+            labels: vec![],
+        });
+    }
+}
@@ -0,0 +1,526 @@
+// Copyright 2018 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! Several changes made together.
+//!
+//! `Toodle::transact` hands its closure a `Transaction`, which makes the same changes `Toodle`
+//! does, inside one SQLite transaction. Each change sees the ones made before it, but nothing
+//! is committed until the closure returns successfully, and then it's all undone in one go.
+//!
+//! If the closure fails, everything is rolled back. So is everything if one of the changes
+//! fails part way through, and the transaction can't be used any more; its error should be
+//! passed on. Lookups that fail before anything is changed, such as for an item that isn't
+//! there, leave the transaction as it was.
+
+use mentat::{
+    Entid,
+    InProgress,
+    IntoResult,
+    Queryable,
+    QueryInputs,
+    TypedValue,
+    Uuid,
+};
+
+use mentat::entity_builder::{
+    BuildTerms,
+    InProgressBuilder,
+};
+
+use mentat_core::{
+    KnownEntid,
+};
+
+use mentat_ffi::utils::log;
+
+use time::Timespec;
+
+use errors::{
+    ErrorKind,
+    Result,
+};
+
+use items::{
+    Item,
+};
+
+use labels::{
+    Label,
+};
+
+use local::{
+    Local,
+};
+
+use lists::{
+    DEFAULT_LIST_COLOR,
+    DEFAULT_LIST_NAME,
+};
+
+use query::{
+    ItemQuery,
+};
+
+use recurrence::{
+    Recurrence,
+};
+
+use schema;
+
+use search;
+
+use undo;
+
+use utils::{
+    Entity,
+    ToInner,
+    ToTypedValue,
+};
+
+pub struct Transaction<'a, 'c> {
+    /// Taken while a change is being built, and only put back once it's been transacted.
+    in_progress: Option<InProgress<'a, 'c>>,
+    txs: Vec<Entid>,
+}
+
+impl<'a, 'c> Transaction<'a, 'c> {
+    pub(crate) fn new(in_progress: InProgress<'a, 'c>) -> Transaction<'a, 'c> {
+        Transaction {
+            in_progress: Some(in_progress),
+            txs: vec![],
+        }
+    }
+
+    /// Commits every change, as one entry on the undo stack.
+    pub(crate) fn commit(self, local: &mut Local) -> Result<()> {
+        let in_progress = self.in_progress.ok_or(ErrorKind::TransactionAborted)?;
+        undo::commit_all(local, in_progress, &self.txs)
+    }
+
+    fn in_progress(&self) -> Result<&InProgress<'a, 'c>> {
+        self.in_progress.as_ref().ok_or_else(|| ErrorKind::TransactionAborted.into())
+    }
+
+    fn builder(&mut self) -> Result<InProgressBuilder<'a, 'c>> {
+        self.in_progress.take().map(|in_progress| in_progress.builder()).ok_or_else(|| ErrorKind::TransactionAborted.into())
+    }
+
+    /// Transacts `builder` without committing it. If it fails, its `InProgress` is dropped,
+    /// rolling everything back.
+    fn transact(&mut self, builder: InProgressBuilder<'a, 'c>) -> Result<()> {
+        let (in_progress, report) = builder.transact();
+        let report = report?;
+        self.in_progress = Some(in_progress);
+        self.txs.push(report.tx_id);
+        Ok(())
+    }
+
+    pub fn fetch_label(&self, name: &str) -> Result<Option<Label>> {
+        ::fetch_label_named(self.in_progress()?, name)
+    }
+
+    pub fn fetch_labels(&self) -> Result<Vec<Label>> {
+        ::fetch_all_labels(self.in_progress()?)
+    }
+
+    pub fn fetch_item(&self, uuid: &Uuid) -> Result<Option<Item>> {
+        ::fetch_item_with_uuid(self.in_progress()?, uuid)
+    }
+
+    pub fn create_label(&mut self, name: String, color: String) -> Result<Option<Label>> {
+        let mut builder = self.builder()?;
+        let label = builder.named_tempid("label".to_string());
+        builder.add_kw(label.clone(), &kw!(:label/name), TypedValue::typed_string(&name))?;
+        builder.add_kw(label, &kw!(:label/color), TypedValue::typed_string(&color))?;
+        self.transact(builder)?;
+        self.fetch_label(&name)
+    }
+
+    pub fn rename_label(&mut self, label: &Label, name: String) -> Result<Option<Label>> {
+        let entid = KnownEntid(label.id.to_owned().ok_or_else(|| ErrorKind::LabelNotFound(label.name.clone()))?.id);
+        if label.name == name {
+            return self.fetch_label(&name);
+        }
+        // `:label/name` is unique, so asserting a name that's already taken would
+        // silently upsert into the other label.
+        if self.fetch_label(&name)?.is_some() {
+            bail!(ErrorKind::LabelAlreadyExists(name));
+        }
+        let mut builder = self.builder()?;
+        builder.add_kw(entid, &kw!(:label/name), TypedValue::typed_string(&name))?;
+        self.transact(builder)?;
+        self.fetch_label(&name)
+    }
+
+    pub fn update_label_color(&mut self, label: &Label, color: String) -> Result<Option<Label>> {
+        let entid = KnownEntid(label.id.to_owned().ok_or_else(|| ErrorKind::LabelNotFound(label.name.clone()))?.id);
+        let mut builder = self.builder()?;
+        builder.add_kw(entid, &kw!(:label/color), TypedValue::typed_string(&color))?;
+        self.transact(builder)?;
+        self.fetch_label(&label.name)
+    }
+
+    pub fn delete_label(&mut self, name: &str) -> Result<()> {
+        let items_query = r#"[:find [?item ...]
+                              :in ?label
+                              :where
+                              [?item :todo/label ?label]
+        ]"#;
+        let label = self.fetch_label(name)?.ok_or_else(|| ErrorKind::LabelNotFound(name.to_string()))?;
+        let label_id = label.id.clone().ok_or_else(|| ErrorKind::LabelNotFound(name.to_string()))?;
+        let args = QueryInputs::with_value_sequence(vec![(var!(?label), label_id.to_typed_value())]);
+        let items = self.in_progress()?
+            .q_once(items_query, args)
+            .into_coll_result()?;
+
+        let mut builder = self.builder()?;
+        let item_labels_kw = kw!(:todo/label);
+        for item in items {
+            let item: Option<Entity> = item.val().and_then(|v| v.to_inner());
+            if let Some(item) = item {
+                builder.retract_kw(KnownEntid(item.id), &item_labels_kw, label_id.to_typed_value())?;
+            }
+        }
+        builder.retract_kw(KnownEntid(label_id.id), &kw!(:label/name), TypedValue::typed_string(&label.name))?;
+        builder.retract_kw(KnownEntid(label_id.id), &kw!(:label/color), TypedValue::typed_string(&label.color))?;
+        self.transact(builder)
+    }
+
+    pub fn create_item(&mut self, item: &Item) -> Result<Uuid> {
+        let item_uuid = ::create_uuid();
+        log::d(&format!("create_item item_uuid: {:?}", item_uuid));
+        let parent = match item.parent {
+            Some(ref parent) => Some(::item_entid(self.in_progress()?, parent)?),
+            None => None,
+        };
+        let list = match (item.list, parent) {
+            // Subtasks always go in their parent's list.
+            (_, Some(parent)) => ::item_list_entid(self.in_progress()?, parent)?,
+            (Some(list), None) => Some(::list_entid(self.in_progress()?, &list)?),
+            (None, None) => schema::default_list(self.in_progress()?)?,
+        };
+        if let Some(ref recurrence) = item.recurrence {
+            check_recurrence(recurrence, item.due_date)?;
+        }
+        let position = ::last_position(self.in_progress()?)?;
+        let labels = item.labels.iter().map(Label::entid).collect::<Result<Vec<KnownEntid>>>()?;
+        let mut builder = self.builder()?;
+        let entity = builder.named_tempid("item".to_string());
+        log::d(&format!("create_item builder"));
+        builder.add_kw(entity.clone(), &kw!(:todo/uuid), TypedValue::Uuid(item_uuid))?;
+        builder.add_kw(entity.clone(), &kw!(:todo/name), TypedValue::typed_string(&item.name))?;
+        builder.add_kw(entity.clone(), &kw!(:todo/name_fulltext), TypedValue::typed_string(&item.name))?;
+        builder.add_kw(entity.clone(), &kw!(:todo/name_trigrams), TypedValue::typed_string(&search::trigrams(&item.name)))?;
+        if let Some(ref notes) = item.notes {
+            builder.add_kw(entity.clone(), &kw!(:todo/notes), TypedValue::typed_string(notes))?;
+        }
+        if let Some(due_date) = item.due_date {
+            builder.add_kw(entity.clone(), &kw!(:todo/due_date), due_date.to_typed_value())?;
+        }
+        if let Some(completion_date) = item.completion_date {
+            builder.add_kw(entity.clone(), &kw!(:todo/completion_date), completion_date.to_typed_value())?;
+        }
+        if let Some(ref recurrence) = item.recurrence {
+            builder.add_kw(entity.clone(), &kw!(:todo/recurrence), TypedValue::typed_string(&recurrence.to_string()))?;
+        }
+        match list {
+            Some(list) => {
+                builder.add_kw(entity.clone(), &kw!(:todo/list), TypedValue::Ref(list.0))?;
+            },
+            None if parent.is_none() => {
+                // Every list has been deleted, so start a new inbox.
+                let inbox = builder.named_tempid("inbox".to_string());
+                builder.add_kw(inbox.clone(), &kw!(:list/uuid), TypedValue::Uuid(::create_uuid()))?;
+                builder.add_kw(inbox.clone(), &kw!(:list/name), TypedValue::typed_string(DEFAULT_LIST_NAME))?;
+                builder.add_kw(inbox.clone(), &kw!(:list/color), TypedValue::typed_string(DEFAULT_LIST_COLOR))?;
+                builder.add_kw(inbox.clone(), &kw!(:list/sort_order), TypedValue::Long(0))?;
+                builder.add_kw(entity.clone(), &kw!(:todo/list), inbox)?;
+            },
+            None => {},
+        }
+        if let Some(parent) = parent {
+            builder.add_kw(entity.clone(), &kw!(:todo/parent), TypedValue::Ref(parent.0))?;
+        }
+        builder.add_kw(entity.clone(), &kw!(:todo/position), TypedValue::typed_string(&position))?;
+
+        for label in labels {
+            builder.add_kw(entity.clone(), &kw!(:todo/label), TypedValue::Ref(label.0))?;
+        }
+
+        log::d(&format!("create_item builder pre transact"));
+        self.transact(builder)?;
+        Ok(item_uuid)
+    }
+
+    pub fn update_item(&mut self,
+                       item: &Item,
+                       name: Option<String>,
+                       due_date: Option<Timespec>,
+                       completion_date: Option<Timespec>,
+                       labels: Option<&Vec<Label>>) -> Result<()> {
+        let entid = KnownEntid(item.id.to_owned().ok_or_else(|| ErrorKind::ItemNotFound(item.uuid.hyphenated().to_string()))?.id);
+        let existing_labels = ::fetch_item_labels(self.in_progress()?, &item.uuid)?;
+        let new_label_entids = match labels {
+            Some(labels) => labels.iter().map(Label::entid).collect::<Result<Vec<KnownEntid>>>()?,
+            None => vec![],
+        };
+
+        let mut builder = self.builder()?;
+
+        if let Some(name) = name {
+            if item.name != name {
+                builder.add_kw(entid, &kw!(:todo/name), TypedValue::typed_string(&name))?;
+                builder.add_kw(entid, &kw!(:todo/name_fulltext), TypedValue::typed_string(&name))?;
+                builder.add_kw(entid, &kw!(:todo/name_trigrams), TypedValue::typed_string(&search::trigrams(&name)))?;
+            }
+        }
+
+        ::add_date_terms(&mut builder, entid, item, due_date, completion_date)?;
+
+        if let Some(new_labels) = labels {
+            let item_labels_kw = kw!(:todo/label);
+            for label in new_label_entids {
+                builder.add_kw(entid, &item_labels_kw, TypedValue::Ref(label.0))?;
+            }
+            for label in existing_labels {
+                if !new_labels.contains(&label) {
+                    builder.retract_kw(entid, &item_labels_kw, TypedValue::Ref(label.entid()?.0))?;
+                }
+            }
+        }
+        self.transact(builder)
+    }
+
+    pub fn update_item_notes(&mut self, item: &Item, notes: Option<String>) -> Result<()> {
+        let entid = KnownEntid(item.id.to_owned().ok_or_else(|| ErrorKind::ItemNotFound(item.uuid.hyphenated().to_string()))?.id);
+        if item.notes == notes {
+            return Ok(());
+        }
+        let mut builder = self.builder()?;
+        let notes_kw = kw!(:todo/notes);
+        if let Some(notes) = notes {
+            builder.add_kw(entid, &notes_kw, TypedValue::typed_string(&notes))?;
+        } else if let Some(ref notes) = item.notes {
+            builder.retract_kw(entid, &notes_kw, TypedValue::typed_string(notes))?;
+        }
+        self.transact(builder)
+    }
+
+    pub fn update_item_recurrence(&mut self, item: &Item, recurrence: Option<Recurrence>) -> Result<()> {
+        let entid = KnownEntid(item.id.to_owned().ok_or_else(|| ErrorKind::ItemNotFound(item.uuid.hyphenated().to_string()))?.id);
+        if item.recurrence == recurrence {
+            return Ok(());
+        }
+        if let Some(ref recurrence) = recurrence {
+            check_recurrence(recurrence, item.due_date)?;
+        }
+        let mut builder = self.builder()?;
+        let recurrence_kw = kw!(:todo/recurrence);
+        if let Some(recurrence) = recurrence {
+            builder.add_kw(entid, &recurrence_kw, TypedValue::typed_string(&recurrence.to_string()))?;
+        } else if let Some(ref recurrence) = item.recurrence {
+            builder.retract_kw(entid, &recurrence_kw, TypedValue::typed_string(&recurrence.to_string()))?;
+        }
+        self.transact(builder)
+    }
+
+    pub fn move_item(&mut self, uuid: &Uuid, parent: Option<&Uuid>) -> Result<()> {
+        let (entid, current_parent, new_parent, list, to_move) = {
+            let in_progress = self.in_progress()?;
+            let entid = ::item_entid(in_progress, uuid)?;
+            let current_parent = ::ancestor_entids(in_progress, entid)?.first().cloned();
+            let new_parent = match parent {
+                Some(parent) => {
+                    let parent = ::item_entid(in_progress, parent)?;
+                    if parent == entid || ::ancestor_entids(in_progress, parent)?.contains(&entid.0) {
+                        bail!(ErrorKind::ItemCycle(uuid.hyphenated().to_string()));
+                    }
+                    Some(parent.0)
+                },
+                None => None,
+            };
+            // Subtasks always share their parent's list, so the item and everything under it
+            // follow the new parent.
+            let list = match new_parent {
+                Some(parent) => ::item_list_entid(in_progress, KnownEntid(parent))?,
+                None => None,
+            };
+            let mut to_move = vec![];
+            if list.is_some() && list != ::item_list_entid(in_progress, entid)? {
+                to_move.push(entid.0);
+                let mut pending = ::child_entids(in_progress, entid)?;
+                while let Some(child) = pending.pop() {
+                    pending.extend(::child_entids(in_progress, KnownEntid(child))?);
+                    to_move.push(child);
+                }
+            }
+            (entid, current_parent, new_parent, list, to_move)
+        };
+        if new_parent == current_parent {
+            return Ok(());
+        }
+
+        let mut builder = self.builder()?;
+        let parent_kw = kw!(:todo/parent);
+        if let Some(parent) = new_parent {
+            builder.add_kw(entid, &parent_kw, TypedValue::Ref(parent))?;
+        } else if let Some(parent) = current_parent {
+            builder.retract_kw(entid, &parent_kw, TypedValue::Ref(parent))?;
+        }
+        if let Some(list) = list {
+            let list_kw = kw!(:todo/list);
+            for entid in to_move {
+                builder.add_kw(KnownEntid(entid), &list_kw, TypedValue::Ref(list.0))?;
+            }
+        }
+        self.transact(builder)
+    }
+
+    pub fn move_item_to_list(&mut self, uuid: &Uuid, list: &Uuid) -> Result<()> {
+        let (entid, list, parent, to_move) = {
+            let in_progress = self.in_progress()?;
+            let list = ::list_entid(in_progress, list)?;
+            let entid = ::item_entid(in_progress, uuid)?;
+            // A subtask moved out of its parent's list leaves its parent behind.
+            let parent = match ::ancestor_entids(in_progress, entid)?.first() {
+                Some(&parent) if ::item_list_entid(in_progress, KnownEntid(parent))? != Some(list) => Some(parent),
+                _ => None,
+            };
+            // Subtasks follow their parent.
+            let mut to_move = vec![entid.0];
+            let mut pending = ::child_entids(in_progress, entid)?;
+            while let Some(child) = pending.pop() {
+                pending.extend(::child_entids(in_progress, KnownEntid(child))?);
+                to_move.push(child);
+            }
+            (entid, list, parent, to_move)
+        };
+
+        let mut builder = self.builder()?;
+        if let Some(parent) = parent {
+            builder.retract_kw(entid, &kw!(:todo/parent), TypedValue::Ref(parent))?;
+        }
+        let list_kw = kw!(:todo/list);
+        for entid in to_move {
+            builder.add_kw(KnownEntid(entid), &list_kw, TypedValue::Ref(list.0))?;
+        }
+        self.transact(builder)
+    }
+
+    pub fn complete_item(&mut self, uuid: &Uuid, completion_date: Timespec, cascade: bool) -> Result<()> {
+        let to_complete = {
+            let in_progress = self.in_progress()?;
+            let item = ::fetch_item_with_uuid(in_progress, uuid)?
+                .ok_or_else(|| ErrorKind::ItemNotFound(uuid.hyphenated().to_string()))?;
+            let mut to_complete = vec![item];
+            if cascade {
+                // Walk the whole subtree, leaving out what's in the trash, but leave subtasks
+                // that are already done with the date they were actually completed on.
+                let mut pending = vec![*uuid];
+                while let Some(parent) = pending.pop() {
+                    for child in ::fetch_items_where(in_progress, ItemQuery::new().subtasks_of(parent).to_selection())? {
+                        pending.push(child.uuid);
+                        if child.completion_date.is_none() {
+                            to_complete.push(child);
+                        }
+                    }
+                }
+            }
+            to_complete
+        };
+
+        // Recurring items roll on to their next occurrence rather than being completed.
+        let mut builder = self.builder()?;
+        for item in to_complete.iter() {
+            let entid = KnownEntid(item.id.to_owned().ok_or_else(|| ErrorKind::ItemNotFound(item.uuid.hyphenated().to_string()))?.id);
+            ::add_date_terms(&mut builder, entid, item, item.due_date, Some(completion_date))?;
+        }
+        self.transact(builder)
+    }
+
+    pub fn complete_items(&mut self, uuids: &[Uuid], completion_date: Timespec) -> Result<()> {
+        let items = ::fetch_items_with_uuids(self.in_progress()?, uuids)?;
+        let mut builder = self.builder()?;
+        for item in items.iter().filter(|item| item.completion_date.is_none()) {
+            let entid = KnownEntid(item.id.to_owned().ok_or_else(|| ErrorKind::ItemNotFound(item.uuid.hyphenated().to_string()))?.id);
+            ::add_date_terms(&mut builder, entid, item, item.due_date, Some(completion_date))?;
+        }
+        self.transact(builder)
+    }
+
+    pub fn uncomplete_items(&mut self, uuids: &[Uuid]) -> Result<()> {
+        let items = ::fetch_items_with_uuids(self.in_progress()?, uuids)?;
+        let mut builder = self.builder()?;
+        for item in items.iter() {
+            let entid = KnownEntid(item.id.to_owned().ok_or_else(|| ErrorKind::ItemNotFound(item.uuid.hyphenated().to_string()))?.id);
+            ::add_date_terms(&mut builder, entid, item, item.due_date, None)?;
+        }
+        self.transact(builder)
+    }
+
+    pub fn set_due_date_for_items(&mut self, uuids: &[Uuid], due_date: Option<Timespec>) -> Result<()> {
+        let items = ::fetch_items_with_uuids(self.in_progress()?, uuids)?;
+        let mut builder = self.builder()?;
+        for item in items.iter() {
+            let entid = KnownEntid(item.id.to_owned().ok_or_else(|| ErrorKind::ItemNotFound(item.uuid.hyphenated().to_string()))?.id);
+            ::add_date_terms(&mut builder, entid, item, due_date, item.completion_date)?;
+        }
+        self.transact(builder)
+    }
+
+    pub fn add_label_to_items(&mut self, uuids: &[Uuid], label: &Label) -> Result<()> {
+        let label_entid = label.id.as_ref().ok_or_else(|| ErrorKind::LabelNotFound(label.name.clone()))?.id;
+        let items = ::fetch_items_with_uuids(self.in_progress()?, uuids)?;
+        let mut builder = self.builder()?;
+        let label_kw = kw!(:todo/label);
+        for item in items.iter().filter(|item| !item.labels.iter().any(|l| l.name == label.name)) {
+            let entid = KnownEntid(item.id.to_owned().ok_or_else(|| ErrorKind::ItemNotFound(item.uuid.hyphenated().to_string()))?.id);
+            builder.add_kw(entid, &label_kw, TypedValue::Ref(label_entid))?;
+        }
+        self.transact(builder)
+    }
+
+    pub fn remove_label_from_items(&mut self, uuids: &[Uuid], label: &Label) -> Result<()> {
+        let label_entid = label.id.as_ref().ok_or_else(|| ErrorKind::LabelNotFound(label.name.clone()))?.id;
+        let items = ::fetch_items_with_uuids(self.in_progress()?, uuids)?;
+        let mut builder = self.builder()?;
+        let label_kw = kw!(:todo/label);
+        for item in items.iter().filter(|item| item.labels.iter().any(|l| l.name == label.name)) {
+            let entid = KnownEntid(item.id.to_owned().ok_or_else(|| ErrorKind::ItemNotFound(item.uuid.hyphenated().to_string()))?.id);
+            builder.retract_kw(entid, &label_kw, TypedValue::Ref(label_entid))?;
+        }
+        self.transact(builder)
+    }
+
+    pub fn delete_item(&mut self, uuid: &Uuid) -> Result<()> {
+        self.delete_items(&[*uuid])
+    }
+
+    pub fn delete_items(&mut self, uuids: &[Uuid]) -> Result<()> {
+        let mut to_delete = vec![];
+        for uuid in uuids {
+            let entid = ::item_entid(self.in_progress()?, uuid)?.0;
+            if !to_delete.contains(&entid) {
+                to_delete.push(entid);
+            }
+        }
+        let in_progress = self.in_progress.take().ok_or(ErrorKind::TransactionAborted)?;
+        let builder = ::delete_entities(in_progress, &to_delete)?;
+        self.transact(builder)
+    }
+}
+
+/// Rejects rules that would never bring the item around again from its due date.
+fn check_recurrence(recurrence: &Recurrence, due_date: Option<Timespec>) -> Result<()> {
+    if due_date.map_or(false, |due_date| !recurrence.repeats_from(due_date)) {
+        bail!(ErrorKind::InvalidRecurrence(recurrence.to_string()));
+    }
+    Ok(())
+}
@@ -0,0 +1,242 @@
+// Copyright 2018 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! Undo and redo, on top of the Mentat transaction log.
+//!
+//! Every transaction Toodle commits is pushed onto an undo stack. Undoing one reads its datoms
+//! back out of the log and transacts their inverse, and it's that inverse transaction that goes
+//! on the redo stack, so redoing is just undoing an undo. The stacks refer to transactions by
+//! id, which only mean anything on this device, so they're kept in the `toodle_undo` table (see
+//! `local`) rather than in the store, where they'd sync. They're changed once the change they
+//! record has been committed.
+//!
+//! Changes made together with `Toodle::transact` are several Mentat transactions in one SQLite
+//! transaction. They're pushed as a single entry, and undone with a single inverse.
+//!
+//! Committing a change also stamps the items it touched with `:todo/modified_at`, and new ones
+//! with `:todo/created_at`, in one more transaction that's pushed along with it. Undoing and
+//! redoing aren't stamped; they put back the stamps the items had.
+
+use std::collections::BTreeMap;
+
+use rusqlite::{
+    Connection,
+};
+
+use mentat::{
+    Entid,
+    InProgress,
+    IntoResult,
+    Queryable,
+    QueryInputs,
+    TxReport,
+    TypedValue,
+};
+
+use mentat::entity_builder::{
+    BuildTerms,
+    InProgressBuilder,
+    TermBuilder,
+};
+
+use mentat_core::{
+    KnownEntid,
+};
+
+use errors::{
+    Result,
+};
+
+use history;
+
+use local::{
+    Local,
+};
+
+use utils::{
+    Entity,
+    ToInner,
+};
+
+use Store;
+
+/// The number of changes that can be undone. Older ones fall off the bottom of the stack.
+pub const UNDO_LIMIT: usize = 100;
+
+/// Transacts and commits `builder`, recording the transaction so that it can be undone. Once
+/// something new has been done, whatever was undone before can't be redone.
+pub fn commit(local: &mut Local, builder: InProgressBuilder) -> Result<TxReport> {
+    let (in_progress, report) = builder.transact();
+    let report = report?;
+    commit_all(local, in_progress, &[report.tx_id])?;
+    Ok(report)
+}
+
+/// Commits `in_progress`, recording `txs`, the transactions already made in it, so that they
+/// can be undone together.
+pub fn commit_all(local: &mut Local, mut in_progress: InProgress, txs: &[Entid]) -> Result<()> {
+    if txs.is_empty() {
+        in_progress.commit()?;
+        return Ok(());
+    }
+    // The items' stamps are part of the change, so that undoing it puts them back too.
+    let mut txs = txs.to_vec();
+    let mut stamps = TermBuilder::new();
+    if history::stamp_items(&in_progress, &mut stamps, &txs)? {
+        txs.push(in_progress.transact_builder(stamps)?.tx_id);
+    }
+    in_progress.commit()?;
+
+    let stacks = local.conn.transaction()?;
+    history::mark_local(&stacks, &txs)?;
+    stacks.execute("DELETE FROM toodle_undo WHERE redo = ?", &[&true])?;
+    push(&stacks, &txs, false)?;
+    for (entry, _) in entries(&stacks, false)?.into_iter().skip(UNDO_LIMIT) {
+        stacks.execute("DELETE FROM toodle_undo WHERE entry = ? AND redo = ?", &[&entry, &false])?;
+    }
+    stacks.commit()?;
+    Ok(())
+}
+
+/// Whether there's anything on the redo stack if `redo` is true, or on the undo stack if not.
+pub fn can_revert(store: &mut Store, redo: bool) -> Result<bool> {
+    let in_progress_read = store.mentat.begin_read()?;
+    Ok(top(&store.local.conn, &in_progress_read, redo)?.is_some())
+}
+
+/// Reverts the most recent transaction on the redo stack if `redo` is true, or on the undo
+/// stack if not, moving it across to the other one. Returns false if the stack was empty.
+///
+/// Entries whose transactions aren't in the log, as when the store has been replaced since
+/// they were pushed, are passed over and dropped, since there's nothing left to revert.
+pub fn revert(store: &mut Store, redo: bool) -> Result<bool> {
+    let in_progress = store.mentat.begin_transaction()?;
+    let (entry, txs) = match top(&store.local.conn, &in_progress, redo)? {
+        Some(top) => top,
+        None => {
+            // The stacks share the database, so Mentat's transaction has to end before they
+            // can be written to.
+            drop(in_progress);
+            store.local.conn.execute("DELETE FROM toodle_undo WHERE redo = ?", &[&redo])?;
+            return Ok(false);
+        },
+    };
+    // What the transactions changed between them, oldest first, so that a datom asserted by
+    // one and retracted by a later one cancels out.
+    let mut changes: BTreeMap<(Entid, Entid, TypedValue), bool> = BTreeMap::new();
+    for &tx in txs.iter().rev() {
+        for (e, a, v, added) in tx_datoms(&in_progress, tx)? {
+            let key = (e.0, a.0, v);
+            match changes.get(&key).cloned() {
+                Some(earlier) if earlier != added => {
+                    changes.remove(&key);
+                },
+                _ => {
+                    changes.insert(key, added);
+                },
+            }
+        }
+    }
+    let mut builder = in_progress.builder();
+    for ((e, a, v), added) in changes {
+        if added {
+            builder.retract(KnownEntid(e), KnownEntid(a), v)?;
+        } else {
+            builder.add(KnownEntid(e), KnownEntid(a), v)?;
+        }
+    }
+    let (in_progress, report) = builder.transact();
+    let report = report?;
+    in_progress.commit()?;
+
+    // The entry and any passed over on the way to it are the most recent on the stack.
+    let stacks = store.local.conn.transaction()?;
+    history::mark_local(&stacks, &[report.tx_id])?;
+    stacks.execute("DELETE FROM toodle_undo WHERE entry >= ? AND redo = ?", &[&entry, &redo])?;
+    push(&stacks, &[report.tx_id], !redo)?;
+    stacks.commit()?;
+    Ok(true)
+}
+
+/// Pushes an entry for `txs` onto the redo stack if `redo` is true, or the undo stack if not.
+pub fn push(conn: &Connection, txs: &[Entid], redo: bool) -> Result<()> {
+    for &tx in txs.iter() {
+        conn.execute("INSERT INTO toodle_undo (entry, tx, redo) VALUES (?, ?, ?)", &[&txs[0], &tx, &redo])?;
+    }
+    Ok(())
+}
+
+/// The entries on one of the stacks, most recent first, each with its transactions, most
+/// recent first.
+fn entries(conn: &Connection, redo: bool) -> Result<Vec<(Entid, Vec<Entid>)>> {
+    let mut statement = conn.prepare("SELECT entry, tx FROM toodle_undo WHERE redo = ? ORDER BY entry DESC, tx DESC")?;
+    let rows = statement.query_map(&[&redo], |row| {
+        let entry: Entid = row.get(0);
+        let tx: Entid = row.get(1);
+        (entry, tx)
+    })?;
+    let mut entries: Vec<(Entid, Vec<Entid>)> = vec![];
+    for row in rows {
+        let (entry, tx) = row?;
+        match entries.iter().position(|&(e, _)| e == entry) {
+            Some(index) => entries[index].1.push(tx),
+            None => entries.push((entry, vec![tx])),
+        }
+    }
+    Ok(entries)
+}
+
+/// The most recent entry on one of the stacks whose transactions are all in the log.
+fn top<Q: Queryable>(conn: &Connection, queryable: &Q, redo: bool) -> Result<Option<(Entid, Vec<Entid>)>> {
+    let query = r#"[:find ?instant .
+                    :in ?tx
+                    :where
+                    [?tx :db/txInstant ?instant]
+    ]"#;
+    for (entry, txs) in entries(conn, redo)? {
+        let mut known = true;
+        for &tx in txs.iter() {
+            let args = QueryInputs::with_value_sequence(vec![(var!(?tx), TypedValue::Ref(tx))]);
+            known = known && queryable.q_once(query, args).into_scalar_result()?.is_some();
+        }
+        if known {
+            return Ok(Some((entry, txs)));
+        }
+    }
+    Ok(None)
+}
+
+/// Everything asserted or retracted by `tx`, apart from the transaction's own metadata.
+fn tx_datoms<Q: Queryable>(queryable: &Q, tx: Entid) -> Result<Vec<(KnownEntid, KnownEntid, TypedValue, bool)>> {
+    let query = r#"[:find ?e ?a ?v ?added
+                    :in ?tx
+                    :where
+                    [(tx-data $ ?tx) [[?e ?a ?v _ ?added]]]
+    ]"#;
+    let args = QueryInputs::with_value_sequence(vec![(var!(?tx), TypedValue::Ref(tx))]);
+    Ok(queryable
+        .q_once(query, args)
+        .into_rel_result()?
+        .into_iter()
+        .filter_map(|row| {
+            let e: Option<Entity> = row[0].clone().val().and_then(|v| v.to_inner());
+            let a: Option<Entity> = row[1].clone().val().and_then(|v| v.to_inner());
+            let added = match row[3].clone().val() {
+                Some(TypedValue::Boolean(added)) => added,
+                _ => return None,
+            };
+            match (e, a, row[2].clone().val()) {
+                (Some(ref e), _, _) if e.id == tx => None,
+                (Some(e), Some(a), Some(v)) => Some((KnownEntid(e.id), KnownEntid(a.id), v, added)),
+                _ => None,
+            }
+        })
+        .collect())
+}
@@ -21,12 +21,32 @@ use std::fmt::{
 use time::Timespec;
 
 use mentat::{
+    Binding,
+    DateTime,
+    HasSchema,
     Keyword,
     Entid,
     TypedValue,
+    Utc,
     Uuid,
 };
 
+use mentat_core::{
+    KnownEntid,
+};
+
+use errors::{
+    self,
+    ErrorKind,
+};
+
+/// Looks up the entid of `keyword`, for building terms against an attribute directly.
+pub(crate) fn attribute_entid<S: HasSchema>(schema: &S, keyword: Keyword) -> errors::Result<KnownEntid> {
+    schema
+        .get_entid(&keyword)
+        .ok_or_else(|| ErrorKind::UnknownAttribute(keyword).into())
+}
+
 pub trait ToTypedValue {
     fn to_typed_value(&self) -> TypedValue;
 }
@@ -109,6 +129,11 @@ impl ToTypedValue for Uuid {
     }
 }
 
+/// Instants are stored to the microsecond, so they always fit in a `Timespec` exactly.
+fn instant_to_timespec(instant: &DateTime<Utc>) -> Timespec {
+    Timespec::new(instant.timestamp(), (instant.timestamp_subsec_micros() * 1_000) as i32)
+}
+
 pub trait ToInner<T> {
     fn to_inner(self) -> T;
 }
@@ -131,20 +156,20 @@ impl ToInner<Option<i64>> for TypedValue {
     }
 }
 
-impl ToInner<String> for TypedValue {
-    fn to_inner(self) -> String {
+impl ToInner<Option<String>> for TypedValue {
+    fn to_inner(self) -> Option<String> {
         match self {
-            TypedValue::String(s) => s.to_string(),
-            _ => String::new(),
+            TypedValue::String(s) => Some(s.to_string()),
+            _ => None,
         }
     }
 }
 
-impl ToInner<Uuid> for TypedValue {
-    fn to_inner(self) -> Uuid {
+impl ToInner<Option<Uuid>> for TypedValue {
+    fn to_inner(self) -> Option<Uuid> {
         match self {
-            TypedValue::Uuid(u) => u,
-            _ => Uuid::nil(),
+            TypedValue::Uuid(u) => Some(u),
+            _ => None,
         }
     }
 }
@@ -152,10 +177,7 @@ impl ToInner<Uuid> for TypedValue {
 impl ToInner<Option<Timespec>> for TypedValue {
     fn to_inner(self) -> Option<Timespec> {
         match self {
-            TypedValue::Instant(v) => {
-                let timestamp = v.timestamp();
-                Some(Timespec::new(timestamp, 0))
-            },
+            TypedValue::Instant(v) => Some(instant_to_timespec(&v)),
             _ => None,
         }
     }
@@ -164,31 +186,46 @@ impl ToInner<Option<Timespec>> for TypedValue {
 impl<'a> ToInner<Option<Timespec>> for Option<&'a TypedValue> {
     fn to_inner(self) -> Option<Timespec> {
         match self {
-            Some(&TypedValue::Instant(v)) => {
-                let timestamp = v.timestamp();
-                Some(Timespec::new(timestamp, 0))
-            },
+            Some(&TypedValue::Instant(ref v)) => Some(instant_to_timespec(v)),
             _ => None,
         }
     }
 }
 
-
-impl<'a> ToInner<Uuid> for &'a TypedValue {
-    fn to_inner(self) -> Uuid {
+impl<'a> ToInner<Option<Uuid>> for &'a TypedValue {
+    fn to_inner(self) -> Option<Uuid> {
         match self {
-            &TypedValue::Uuid(u) => u,
-            _ => Uuid::nil(),
+            &TypedValue::Uuid(u) => Some(u),
+            _ => None,
         }
     }
 }
 
+/// Reads the value in column `index` of a query result row, failing with `MalformedRow`
+/// rather than panicking if it's missing or of the wrong type, as a datom from a newer
+/// client or a bad sync could be.
+pub fn column<T>(row: &[Binding], index: usize) -> errors::Result<T> where TypedValue: ToInner<Option<T>> {
+    row.get(index)
+       .and_then(|binding| binding.clone().val())
+       .and_then(|value| value.to_inner())
+       .ok_or_else(|| ErrorKind::MalformedRow(format!("unexpected value in column {} of {:?}", index, row)).into())
+}
+
 #[cfg(test)]
 mod test {
     use super::{
+        column,
+        Binding,
+        ToInner,
         ToTypedValue,
         TypedValue,
         Timespec,
+        Uuid,
+    };
+
+    use errors::{
+        Error,
+        ErrorKind,
     };
 
     #[test]
@@ -200,4 +237,37 @@ mod test {
         let typed_value: TypedValue = timespec.to_typed_value();
         assert_eq!(typed_value, TypedValue::instant(1518434618740993));
     }
+
+    #[test]
+    fn test_timespec_round_trip() {
+        let timespec = Timespec::new(1518434618, 740993000);
+        let round_tripped: Option<Timespec> = timespec.to_typed_value().to_inner();
+        assert_eq!(round_tripped, Some(timespec));
+        let round_tripped: Option<Timespec> = Some(&timespec.to_typed_value()).to_inner();
+        assert_eq!(round_tripped, Some(timespec));
+
+        // Anything finer than a microsecond is dropped.
+        let round_tripped: Option<Timespec> = Timespec::new(1518434618, 740993537).to_typed_value().to_inner();
+        assert_eq!(round_tripped, Some(timespec));
+
+        let before_epoch = Timespec::new(-2, 250000000);
+        let round_tripped: Option<Timespec> = before_epoch.to_typed_value().to_inner();
+        assert_eq!(round_tripped, Some(before_epoch));
+    }
+
+    #[test]
+    fn test_column() {
+        let row = vec![Binding::Scalar(TypedValue::Long(7)), Binding::Scalar(TypedValue::typed_string("seven"))];
+        let number: i64 = column(&row, 0).expect("expected a number");
+        assert_eq!(number, 7);
+        let text: String = column(&row, 1).expect("expected a string");
+        assert_eq!(text, "seven");
+
+        // The wrong type, or no value at all, is an error rather than a panic or a default.
+        match column::<Uuid>(&row, 1) {
+            Err(Error(ErrorKind::MalformedRow(_), _)) => {},
+            result => panic!("expected a malformed row error, got {:?}", result),
+        }
+        assert!(column::<i64>(&row, 2).is_err());
+    }
 }
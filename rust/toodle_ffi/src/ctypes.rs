@@ -16,6 +16,7 @@ use time::{
 };
 
 use mentat::{
+    TypedValue,
     Uuid,
 };
 
@@ -24,25 +25,60 @@ use mentat_ffi::utils::strings::{
     string_to_c_char,
 };
 
+use toodle::events::{
+    ChangeEvent,
+};
+use toodle::history::{
+    ChangeOrigin,
+    ItemChange,
+};
 use toodle::items::{
     Item,
     Items,
 };
+use toodle::lists::{
+    List,
+};
+use toodle::recurrence::{
+    Occurrence,
+    Recurrence,
+};
+use toodle::reminders::{
+    PendingReminder,
+    Reminder,
+    ReminderTrigger,
+};
+use toodle::search::{
+    SearchResult,
+};
+
+use utils::time::{
+    from_micros,
+    to_micros,
+};
 
 #[repr(C)]
 #[derive(Debug, Clone)]
 pub struct ItemC {
     pub uuid: *mut c_char,
     pub name: *mut c_char,
+    pub notes: *mut c_char,
     pub due_date: *mut i64,
     pub completion_date: *mut i64,
+    pub recurrence: *mut c_char,
+    pub list: *mut c_char,
+    pub parent: *mut c_char,
+    pub child_count: i64,
+    pub deleted_at: *mut i64,
+    pub created_at: *mut i64,
+    pub modified_at: *mut i64,
 }
 
 impl From<Item> for ItemC {
     fn from(item: Item) -> Self {
         let due = match item.due_date {
             Some(date) => {
-                Box::into_raw(Box::new(date.sec))
+                Box::into_raw(Box::new(to_micros(&date)))
             },
             None => {
                 ptr::null_mut()
@@ -50,7 +86,7 @@ impl From<Item> for ItemC {
         };
         let completion = match item.completion_date {
             Some(date) => {
-                Box::into_raw(Box::new(date.sec))
+                Box::into_raw(Box::new(to_micros(&date)))
             },
             None => {
                 ptr::null_mut()
@@ -59,8 +95,16 @@ impl From<Item> for ItemC {
         ItemC {
             uuid: string_to_c_char(item.uuid.hyphenated().to_string()),
             name: string_to_c_char(item.name.clone()),
+            notes: item.notes.clone().map_or(ptr::null_mut(), string_to_c_char),
             due_date: due,
             completion_date: completion,
+            recurrence: item.recurrence.as_ref().map_or(ptr::null_mut(), |recurrence| string_to_c_char(recurrence.to_string())),
+            list: item.list.map_or(ptr::null_mut(), |list| string_to_c_char(list.hyphenated().to_string())),
+            parent: item.parent.map_or(ptr::null_mut(), |parent| string_to_c_char(parent.hyphenated().to_string())),
+            child_count: item.child_count as i64,
+            deleted_at: item.deleted_at.as_ref().map_or(ptr::null_mut(), |date| Box::into_raw(Box::new(to_micros(date)))),
+            created_at: item.created_at.as_ref().map_or(ptr::null_mut(), |date| Box::into_raw(Box::new(to_micros(date)))),
+            modified_at: item.modified_at.as_ref().map_or(ptr::null_mut(), |date| Box::into_raw(Box::new(to_micros(date)))),
         }
     }
 }
@@ -98,22 +142,55 @@ impl From<ItemC> for Item {
         let uuid = Uuid::parse_str(&c_char_to_string(item_c.uuid)).unwrap_or(Uuid::default());
         let due: Option<Timespec>;
         if !item_c.due_date.is_null() {
-            due = Some(Timespec::new(item_c.due_date as i64, 0));
+            due = Some(from_micros(unsafe { *item_c.due_date }));
         } else {
             due = None;
         }
         let completion: Option<Timespec>;
         if !item_c.completion_date.is_null() {
-            completion = Some(Timespec::new(item_c.completion_date as i64, 0));
+            completion = Some(from_micros(unsafe { *item_c.completion_date }));
         } else {
             completion = None;
         }
+        let recurrence: Option<Recurrence>;
+        if !item_c.recurrence.is_null() {
+            recurrence = c_char_to_string(item_c.recurrence).parse().ok();
+        } else {
+            recurrence = None;
+        }
+        let list: Option<Uuid>;
+        if !item_c.list.is_null() {
+            list = Uuid::parse_str(&c_char_to_string(item_c.list)).ok();
+        } else {
+            list = None;
+        }
+        let parent: Option<Uuid>;
+        if !item_c.parent.is_null() {
+            parent = Uuid::parse_str(&c_char_to_string(item_c.parent)).ok();
+        } else {
+            parent = None;
+        }
+        let notes: Option<String>;
+        if !item_c.notes.is_null() {
+            notes = Some(c_char_to_string(item_c.notes).to_string());
+        } else {
+            notes = None;
+        }
         Item {
             id: None,
             uuid: uuid,
             name: c_char_to_string(item_c.name).to_string(),
+            notes: notes,
             due_date: due,
             completion_date: completion,
+            deleted_at: None,
+            position: None,
+            created_at: None,
+            modified_at: None,
+            recurrence: recurrence,
+            list: list,
+            parent: parent,
+            child_count: item_c.child_count as usize,
             labels: vec![]
         }
     }
@@ -125,3 +202,242 @@ pub struct ItemCList {
     pub items: Box<[ItemC]>,
     pub len: usize
 }
+
+#[repr(C)]
+#[derive(Debug, Clone)]
+pub struct SearchResultC {
+    pub item: ItemC,
+    pub score: f64,
+    pub snippet: *mut c_char,
+}
+
+impl From<SearchResult> for SearchResultC {
+    fn from(result: SearchResult) -> Self {
+        SearchResultC {
+            item: result.item.into(),
+            score: result.score,
+            snippet: string_to_c_char(result.snippet),
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug)]
+pub struct SearchResultCList {
+    pub results: Box<[SearchResultC]>,
+    pub len: usize
+}
+
+#[repr(C)]
+#[derive(Debug, Clone)]
+pub struct ListC {
+    pub uuid: *mut c_char,
+    pub name: *mut c_char,
+    pub color: *mut c_char,
+    pub sort_order: i64,
+}
+
+impl From<List> for ListC {
+    fn from(list: List) -> Self {
+        ListC {
+            uuid: string_to_c_char(list.uuid.hyphenated().to_string()),
+            name: string_to_c_char(list.name),
+            color: string_to_c_char(list.color),
+            sort_order: list.sort_order,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug)]
+pub struct ListCList {
+    pub lists: Box<[ListC]>,
+    pub len: usize
+}
+
+#[repr(C)]
+#[derive(Debug, Clone)]
+pub struct OccurrenceC {
+    pub item: ItemC,
+    pub due_date: i64,
+}
+
+impl From<Occurrence> for OccurrenceC {
+    fn from(occurrence: Occurrence) -> Self {
+        OccurrenceC {
+            due_date: to_micros(&occurrence.due_date),
+            item: occurrence.item.into(),
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug)]
+pub struct OccurrenceCList {
+    pub occurrences: Box<[OccurrenceC]>,
+    pub len: usize
+}
+
+/// Exactly one of `at` and `offset` is set. `offset` is in seconds before the item's due date.
+#[repr(C)]
+#[derive(Debug, Clone)]
+pub struct ReminderC {
+    pub uuid: *mut c_char,
+    pub item: *mut c_char,
+    pub at: *mut i64,
+    pub offset: *mut i64,
+    pub snoozed_until: *mut i64,
+}
+
+impl From<Reminder> for ReminderC {
+    fn from(reminder: Reminder) -> Self {
+        let (at, offset) = match reminder.trigger {
+            ReminderTrigger::At(date) => (Box::into_raw(Box::new(to_micros(&date))), ptr::null_mut()),
+            ReminderTrigger::BeforeDue(offset) => (ptr::null_mut(), Box::into_raw(Box::new(offset.num_seconds()))),
+        };
+        ReminderC {
+            uuid: string_to_c_char(reminder.uuid.hyphenated().to_string()),
+            item: string_to_c_char(reminder.item.hyphenated().to_string()),
+            at: at,
+            offset: offset,
+            snoozed_until: reminder.snoozed_until.map_or(ptr::null_mut(), |date| Box::into_raw(Box::new(to_micros(&date)))),
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone)]
+pub struct PendingReminderC {
+    pub reminder: ReminderC,
+    pub item: ItemC,
+    pub fire_at: i64,
+}
+
+impl From<PendingReminder> for PendingReminderC {
+    fn from(pending: PendingReminder) -> Self {
+        PendingReminderC {
+            fire_at: to_micros(&pending.fire_at),
+            item: pending.item.into(),
+            reminder: pending.reminder.into(),
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug)]
+pub struct PendingReminderCList {
+    pub reminders: Box<[PendingReminderC]>,
+    pub len: usize
+}
+
+/// Renders a value from an item's history as a string. Instants are given in seconds since the
+/// epoch, like the rest of this API.
+fn typed_value_to_c_char(value: TypedValue) -> *mut c_char {
+    let string = match value {
+        TypedValue::String(s) => s.to_string(),
+        TypedValue::Uuid(u) => u.hyphenated().to_string(),
+        TypedValue::Instant(instant) => instant.timestamp().to_string(),
+        TypedValue::Long(l) => l.to_string(),
+        TypedValue::Ref(r) => r.to_string(),
+        TypedValue::Boolean(b) => b.to_string(),
+        TypedValue::Double(d) => d.0.to_string(),
+        TypedValue::Keyword(k) => k.to_string(),
+    };
+    string_to_c_char(string)
+}
+
+pub const ORIGIN_LOCAL: i32 = 0;
+pub const ORIGIN_SYNC: i32 = 1;
+pub const ORIGIN_UNKNOWN: i32 = 2;
+
+/// `old_value` and `new_value` are null when there wasn't one. `origin` is one of the
+/// `ORIGIN_` constants: whether the change was made on this device, arrived by sync, or was
+/// made before this device kept track.
+#[repr(C)]
+#[derive(Debug, Clone)]
+pub struct ItemChangeC {
+    pub attribute: *mut c_char,
+    pub old_value: *mut c_char,
+    pub new_value: *mut c_char,
+    pub tx_instant: i64,
+    pub origin: i32,
+}
+
+impl From<ItemChange> for ItemChangeC {
+    fn from(change: ItemChange) -> Self {
+        ItemChangeC {
+            attribute: string_to_c_char(change.attribute.to_string()),
+            old_value: change.old_value.map_or(ptr::null_mut(), typed_value_to_c_char),
+            new_value: change.new_value.map_or(ptr::null_mut(), typed_value_to_c_char),
+            tx_instant: to_micros(&change.tx_instant),
+            origin: match change.origin {
+                ChangeOrigin::Local => ORIGIN_LOCAL,
+                ChangeOrigin::Sync => ORIGIN_SYNC,
+                ChangeOrigin::Unknown => ORIGIN_UNKNOWN,
+            },
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug)]
+pub struct ItemChangeCList {
+    pub changes: Box<[ItemChangeC]>,
+    pub len: usize
+}
+
+pub const CHANGE_ITEM_CREATED: i32 = 0;
+pub const CHANGE_ITEM_UPDATED: i32 = 1;
+pub const CHANGE_ITEM_DELETED: i32 = 2;
+pub const CHANGE_LABEL_CREATED: i32 = 3;
+pub const CHANGE_LABEL_RENAMED: i32 = 4;
+pub const CHANGE_LABEL_CHANGED: i32 = 5;
+pub const CHANGE_LABEL_DELETED: i32 = 6;
+pub const CHANGE_LIST_CREATED: i32 = 7;
+pub const CHANGE_LIST_CHANGED: i32 = 8;
+pub const CHANGE_LIST_DELETED: i32 = 9;
+
+/// `kind` is one of the `CHANGE_` constants, and `id` is the UUID of the item or list or the
+/// name of the label. `previous_name` is only set for renamed labels, and `fields` only for
+/// updated items, as their changed attributes separated by spaces.
+#[repr(C)]
+#[derive(Debug, Clone)]
+pub struct ChangeEventC {
+    pub kind: i32,
+    pub id: *mut c_char,
+    pub previous_name: *mut c_char,
+    pub fields: *mut c_char,
+}
+
+impl From<ChangeEvent> for ChangeEventC {
+    fn from(event: ChangeEvent) -> Self {
+        let (kind, id, previous_name, fields) = match event {
+            ChangeEvent::ItemCreated(uuid) => (CHANGE_ITEM_CREATED, uuid.hyphenated().to_string(), None, None),
+            ChangeEvent::ItemUpdated(uuid, fields) => {
+                let fields: Vec<String> = fields.iter().map(|field| field.to_string()).collect();
+                (CHANGE_ITEM_UPDATED, uuid.hyphenated().to_string(), None, Some(fields.join(" ")))
+            },
+            ChangeEvent::ItemDeleted(uuid) => (CHANGE_ITEM_DELETED, uuid.hyphenated().to_string(), None, None),
+            ChangeEvent::LabelCreated(name) => (CHANGE_LABEL_CREATED, name, None, None),
+            ChangeEvent::LabelRenamed(old, new) => (CHANGE_LABEL_RENAMED, new, Some(old), None),
+            ChangeEvent::LabelChanged(name) => (CHANGE_LABEL_CHANGED, name, None, None),
+            ChangeEvent::LabelDeleted(name) => (CHANGE_LABEL_DELETED, name, None, None),
+            ChangeEvent::ListCreated(uuid) => (CHANGE_LIST_CREATED, uuid.hyphenated().to_string(), None, None),
+            ChangeEvent::ListChanged(uuid) => (CHANGE_LIST_CHANGED, uuid.hyphenated().to_string(), None, None),
+            ChangeEvent::ListDeleted(uuid) => (CHANGE_LIST_DELETED, uuid.hyphenated().to_string(), None, None),
+        };
+        ChangeEventC {
+            kind: kind,
+            id: string_to_c_char(id),
+            previous_name: previous_name.map_or(ptr::null_mut(), string_to_c_char),
+            fields: fields.map_or(ptr::null_mut(), string_to_c_char),
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug)]
+pub struct ChangeEventCList {
+    pub events: Box<[ChangeEventC]>,
+    pub len: usize
+}
@@ -17,7 +17,7 @@ extern crate toodle;
 mod ctypes;
 mod utils;
 
-use libc::{ c_int, size_t, time_t };
+use libc::{ c_int, size_t };
 use std::ffi::CString;
 use std::os::raw::{
     c_char,
@@ -26,7 +26,6 @@ use std::os::raw::{
 use time::Timespec;
 
 pub use mentat::{
-    Store,
     Uuid,
     Syncable,
 };
@@ -45,16 +44,47 @@ use mentat_ffi::utils::strings::{
 };
 
 use toodle::{
+    ChangeObserver,
+    Duration,
     Item,
+    ItemOrder,
+    ItemQuery,
     Label,
+    LabelMatch,
+    SortDirection,
+    Store,
     Toodle,
+    TrashFilter,
+};
+use toodle::errors::{
+    ErrorKind,
+};
+use toodle::recurrence::{
+    Recurrence,
+};
+use toodle::reminders::{
+    ReminderTrigger,
 };
 use ctypes::{
+    ChangeEventC,
+    ChangeEventCList,
     ItemC,
+    ItemChangeC,
+    ItemChangeCList,
     ItemsC,
     ItemCList,
+    ListC,
+    ListCList,
+    OccurrenceC,
+    OccurrenceCList,
+    PendingReminderC,
+    PendingReminderCList,
+    ReminderC,
+    SearchResultC,
+    SearchResultCList,
 };
 use utils::time::{
+    from_micros,
     optional_timespec,
 };
 
@@ -98,7 +128,7 @@ pub unsafe extern "C" fn toodle_get_all_labels(manager: *mut Store) -> *mut Vec<
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn toodle_create_item(manager: *mut Store, name: *const c_char, due_date: *const time_t) -> *mut ItemC {
+pub unsafe extern "C" fn toodle_create_item(manager: *mut Store, name: *const c_char, due_date: *const i64) -> *mut ItemC {
     let name = c_char_to_string(name).to_string();
     log::d(&format!("Creating item: {:?}, {:?}, {:?}", name, due_date, manager)[..]);
 
@@ -110,33 +140,673 @@ pub unsafe extern "C" fn toodle_create_item(manager: *mut Store, name: *const c_
     item.name = name;
     let due: Option<Timespec>;
     if !due_date.is_null() {
-        let due_date = *due_date as i64;
-        due = Some(Timespec::new(due_date, 0));
+        due = Some(from_micros(*due_date));
     } else {
         due = None;
     }
     item.due_date = due;
     log::d(&format!("toodle_create_item due item: {:?}", item));
-    let item = manager.create_and_fetch_item(&item).expect("expected an item");
+    let item = manager.create_and_fetch_item(&item);
     log::d(&format!("toodle_create_item create_and_fetch_item: {:?}", item));
-    if let Some(i) = item {
+    if let Ok(Some(i)) = item {
         return Box::into_raw(Box::new(i.into()));
     }
     return std::ptr::null_mut();
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn toodle_create_subitem(manager: *mut Store, parent: *const c_char, name: *const c_char, due_date: *const i64) -> *mut ItemC {
+    let manager = &mut*manager;
+    let parent = match Uuid::parse_str(&c_char_to_string(parent).to_string()) {
+        Ok(parent) => parent,
+        Err(_) => return std::ptr::null_mut(),
+    };
+    let mut item = Item::default();
+    item.name = c_char_to_string(name).to_string();
+    item.due_date = optional_timespec(due_date);
+    item.parent = Some(parent);
+    match manager.create_and_fetch_item(&item) {
+        Ok(Some(i)) => Box::into_raw(Box::new(i.into())),
+        _ => std::ptr::null_mut(),
+    }
+}
+
+/// On success, the result holds an `ItemCList` of the item's subtasks.
+#[no_mangle]
+pub unsafe extern "C" fn toodle_fetch_children(manager: *mut Store, uuid: *const c_char) -> *mut ExternResult {
+    let manager = &mut *manager;
+    let uuid_string = c_char_to_string(uuid).to_string();
+    let result = Uuid::parse_str(&uuid_string)
+        .map_err(|e| e.into())
+        .and_then(|uuid| manager.fetch_children(&uuid))
+        .map(|items| {
+            let items: ItemsC = items.into();
+            let count = items.vec.len();
+            ItemCList {
+                items: items.vec.into_boxed_slice(),
+                len: count,
+            }
+        });
+    Box::into_raw(Box::new(result.into()))
+}
+
+/// Pass a null `parent` to make the item top-level.
+#[no_mangle]
+pub unsafe extern "C" fn toodle_move_item(manager: *mut Store, uuid: *const c_char, parent: *const c_char) -> *mut ExternResult {
+    let manager = &mut*manager;
+    let uuid_string = c_char_to_string(uuid).to_string();
+    let parent_string = if parent.is_null() { None } else { Some(c_char_to_string(parent).to_string()) };
+    let result = Uuid::parse_str(&uuid_string)
+        .map_err(|e| e.into())
+        .and_then(|uuid| {
+            match parent_string {
+                Some(parent) => Uuid::parse_str(&parent)
+                    .map_err(|e| e.into())
+                    .and_then(|parent| manager.move_item(&uuid, Some(&parent))),
+                None => manager.move_item(&uuid, None),
+            }
+        });
+    Box::into_raw(Box::new(result.into()))
+}
+
+/// Pass a null `previous` or `next` to leave that side open.
+#[no_mangle]
+pub unsafe extern "C" fn toodle_reorder_item(manager: *mut Store, uuid: *const c_char, previous: *const c_char, next: *const c_char) -> *mut ExternResult {
+    let manager = &mut*manager;
+    let uuid_string = c_char_to_string(uuid).to_string();
+    let previous_string = if previous.is_null() { None } else { Some(c_char_to_string(previous).to_string()) };
+    let next_string = if next.is_null() { None } else { Some(c_char_to_string(next).to_string()) };
+    let parse_optional = |string: Option<String>| match string {
+        Some(string) => Uuid::parse_str(&string).map(Some),
+        None => Ok(None),
+    };
+    let result = Uuid::parse_str(&uuid_string)
+        .and_then(|uuid| parse_optional(previous_string).map(|previous| (uuid, previous)))
+        .and_then(|(uuid, previous)| parse_optional(next_string).map(|next| (uuid, previous, next)))
+        .map_err(|e| e.into())
+        .and_then(|(uuid, previous, next)| manager.reorder_item(&uuid, previous.as_ref(), next.as_ref()));
+    Box::into_raw(Box::new(result.into()))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn toodle_complete_item(manager: *mut Store, uuid: *const c_char, completion_date: i64, cascade: c_int) -> *mut ExternResult {
+    let manager = &mut*manager;
+    let uuid_string = c_char_to_string(uuid).to_string();
+    let result = Uuid::parse_str(&uuid_string)
+        .map_err(|e| e.into())
+        .and_then(|uuid| manager.complete_item(&uuid, from_micros(completion_date), cascade != 0));
+    Box::into_raw(Box::new(result.into()))
+}
+
+/// `rule` is an RRULE such as `FREQ=WEEKLY;BYDAY=MO`, or null to stop the item repeating.
+#[no_mangle]
+pub unsafe extern "C" fn toodle_update_item_recurrence(manager: *mut Store, uuid: *const c_char, rule: *const c_char) -> *mut ExternResult {
+    let manager = &mut*manager;
+    let uuid_string = c_char_to_string(uuid).to_string();
+    let rule = if rule.is_null() { None } else { Some(c_char_to_string(rule).to_string()) };
+    let result = rule.map_or(Ok(None), |rule| rule.parse::<Recurrence>().map(Some))
+        .and_then(|recurrence| {
+            Uuid::parse_str(&uuid_string)
+                .map_err(|e| e.into())
+                .and_then(|uuid| manager.fetch_item(&uuid))
+                .and_then(|item| item.ok_or_else(|| ErrorKind::ItemNotFound(uuid_string.clone()).into()))
+                .and_then(|item| manager.update_item_recurrence(&item, recurrence))
+        });
+    Box::into_raw(Box::new(result.into()))
+}
+
+/// On success, the result holds an `OccurrenceCList`.
+#[no_mangle]
+pub unsafe extern "C" fn toodle_fetch_occurrences(manager: *mut Store, start: i64, end: i64) -> *mut ExternResult {
+    let manager = &mut *manager;
+    let result = manager.fetch_occurrences(from_micros(start), from_micros(end)).map(|occurrences| {
+        let occurrences: Vec<OccurrenceC> = occurrences.into_iter().map(|occurrence| occurrence.into()).collect();
+        let count = occurrences.len();
+        OccurrenceCList {
+            occurrences: occurrences.into_boxed_slice(),
+            len: count,
+        }
+    });
+    Box::into_raw(Box::new(result.into()))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn occurrence_list_entry_at(occurrence_list: *mut OccurrenceCList, index: c_int) -> *const OccurrenceC {
+    let occurrence_list = &*occurrence_list;
+    let index = index as usize;
+    let occurrence = Box::new(occurrence_list.occurrences[index].clone());
+    Box::into_raw(occurrence)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn occurrence_list_count(occurrence_list: *mut OccurrenceCList) -> c_int {
+    let occurrence_list = &*occurrence_list;
+    occurrence_list.len as c_int
+}
+
+/// Frees the list and everything in it. Entries returned by `occurrence_list_entry_at` share
+/// its strings, so they can't be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn occurrence_list_destroy(occurrence_list: *mut OccurrenceCList) {
+    let occurrence_list = Box::from_raw(occurrence_list);
+    for occurrence in occurrence_list.occurrences.iter() {
+        release_item_c(&occurrence.item);
+    }
+}
+
+unsafe fn add_reminder(manager: &mut Store, item: *const c_char, trigger: ReminderTrigger) -> *mut ReminderC {
+    let item = c_char_to_string(item).to_string();
+    match Uuid::parse_str(&item).map_err(|e| e.into()).and_then(|item| manager.add_reminder(&item, trigger)) {
+        Ok(reminder) => Box::into_raw(Box::new(reminder.into())),
+        Err(_) => std::ptr::null_mut(),
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn toodle_add_reminder_at(manager: *mut Store, item: *const c_char, at: i64) -> *mut ReminderC {
+    let manager = &mut*manager;
+    add_reminder(manager, item, ReminderTrigger::At(from_micros(at)))
+}
+
+/// `offset` is the number of seconds before the item's due date that the reminder goes off.
+#[no_mangle]
+pub unsafe extern "C" fn toodle_add_reminder_before_due(manager: *mut Store, item: *const c_char, offset: i64) -> *mut ReminderC {
+    let manager = &mut*manager;
+    add_reminder(manager, item, ReminderTrigger::BeforeDue(Duration::seconds(offset)))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn reminder_c_destroy(reminder: *mut ReminderC) {
+    let reminder = Box::from_raw(reminder);
+    let _ = CString::from_raw(reminder.uuid);
+    let _ = CString::from_raw(reminder.item);
+    if !reminder.at.is_null() {
+        let _ = Box::from_raw(reminder.at);
+    }
+    if !reminder.offset.is_null() {
+        let _ = Box::from_raw(reminder.offset);
+    }
+    if !reminder.snoozed_until.is_null() {
+        let _ = Box::from_raw(reminder.snoozed_until);
+    }
+}
+
+/// Returns the reminders that go off before `now + window`, including any that were missed.
+/// On success, the result holds a `PendingReminderCList`.
+#[no_mangle]
+pub unsafe extern "C" fn toodle_pending_reminders(manager: *mut Store, now: i64, window: i64) -> *mut ExternResult {
+    let manager = &mut *manager;
+    let result = manager.pending_reminders(from_micros(now), Duration::seconds(window)).map(|reminders| {
+        let reminders: Vec<PendingReminderC> = reminders.into_iter().map(|reminder| reminder.into()).collect();
+        let count = reminders.len();
+        PendingReminderCList {
+            reminders: reminders.into_boxed_slice(),
+            len: count,
+        }
+    });
+    Box::into_raw(Box::new(result.into()))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn pending_reminder_list_entry_at(reminder_list: *mut PendingReminderCList, index: c_int) -> *const PendingReminderC {
+    let reminder_list = &*reminder_list;
+    let index = index as usize;
+    let reminder = Box::new(reminder_list.reminders[index].clone());
+    Box::into_raw(reminder)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn pending_reminder_list_count(reminder_list: *mut PendingReminderCList) -> c_int {
+    let reminder_list = &*reminder_list;
+    reminder_list.len as c_int
+}
+
+/// Frees the list and everything in it. Entries returned by `pending_reminder_list_entry_at`
+/// share its strings, so they can't be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn pending_reminder_list_destroy(reminder_list: *mut PendingReminderCList) {
+    let reminder_list = Box::from_raw(reminder_list);
+    for pending in reminder_list.reminders.iter() {
+        release_item_c(&pending.item);
+        let _ = CString::from_raw(pending.reminder.uuid);
+        let _ = CString::from_raw(pending.reminder.item);
+        for date in &[pending.reminder.at, pending.reminder.offset, pending.reminder.snoozed_until] {
+            if !date.is_null() {
+                let _ = Box::from_raw(*date);
+            }
+        }
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn toodle_acknowledge_reminder(manager: *mut Store, uuid: *const c_char) -> *mut ExternResult {
+    let manager = &mut*manager;
+    let uuid_string = c_char_to_string(uuid).to_string();
+    let result = Uuid::parse_str(&uuid_string)
+        .map_err(|e| e.into())
+        .and_then(|uuid| manager.acknowledge_reminder(&uuid));
+    Box::into_raw(Box::new(result.into()))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn toodle_snooze_reminder(manager: *mut Store, uuid: *const c_char, until: i64) -> *mut ExternResult {
+    let manager = &mut*manager;
+    let uuid_string = c_char_to_string(uuid).to_string();
+    let result = Uuid::parse_str(&uuid_string)
+        .map_err(|e| e.into())
+        .and_then(|uuid| manager.snooze_reminder(&uuid, from_micros(until)));
+    Box::into_raw(Box::new(result.into()))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn toodle_delete_reminder(manager: *mut Store, uuid: *const c_char) -> *mut ExternResult {
+    let manager = &mut*manager;
+    let uuid_string = c_char_to_string(uuid).to_string();
+    let result = Uuid::parse_str(&uuid_string)
+        .map_err(|e| e.into())
+        .and_then(|uuid| manager.delete_reminder(&uuid));
+    Box::into_raw(Box::new(result.into()))
+}
+
+/// On success, the result holds an `ItemChangeCList`.
+#[no_mangle]
+pub unsafe extern "C" fn toodle_fetch_item_history(manager: *mut Store, uuid: *const c_char) -> *mut ExternResult {
+    let manager = &mut *manager;
+    let uuid_string = c_char_to_string(uuid).to_string();
+    let result = Uuid::parse_str(&uuid_string)
+        .map_err(|e| e.into())
+        .and_then(|uuid| manager.fetch_item_history(&uuid))
+        .map(|changes| {
+            let changes: Vec<ItemChangeC> = changes.into_iter().map(|change| change.into()).collect();
+            let count = changes.len();
+            ItemChangeCList {
+                changes: changes.into_boxed_slice(),
+                len: count,
+            }
+        });
+    Box::into_raw(Box::new(result.into()))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn item_change_list_entry_at(change_list: *mut ItemChangeCList, index: c_int) -> *const ItemChangeC {
+    let change_list = &*change_list;
+    let index = index as usize;
+    let change = Box::new(change_list.changes[index].clone());
+    Box::into_raw(change)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn item_change_list_count(change_list: *mut ItemChangeCList) -> c_int {
+    let change_list = &*change_list;
+    change_list.len as c_int
+}
+
+/// Frees the list and everything in it. Entries returned by `item_change_list_entry_at` share
+/// its strings, so they can't be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn item_change_list_destroy(change_list: *mut ItemChangeCList) {
+    let change_list = Box::from_raw(change_list);
+    for change in change_list.changes.iter() {
+        for string in &[change.attribute, change.old_value, change.new_value] {
+            if !string.is_null() {
+                let _ = CString::from_raw(*string);
+            }
+        }
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn toodle_can_undo(manager: *mut Store) -> c_int {
+    let manager = &mut*manager;
+    manager.can_undo().unwrap_or(false) as c_int
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn toodle_can_redo(manager: *mut Store) -> c_int {
+    let manager = &mut*manager;
+    manager.can_redo().unwrap_or(false) as c_int
+}
+
+/// On success, the result holds whether there was anything to undo.
+#[no_mangle]
+pub unsafe extern "C" fn toodle_undo(manager: *mut Store) -> *mut ExternResult {
+    let manager = &mut*manager;
+    Box::into_raw(Box::new(manager.undo().into()))
+}
+
+/// On success, the result holds whether there was anything to redo.
+#[no_mangle]
+pub unsafe extern "C" fn toodle_redo(manager: *mut Store) -> *mut ExternResult {
+    let manager = &mut*manager;
+    Box::into_raw(Box::new(manager.redo().into()))
+}
+
+/// Returns null if the store couldn't be exported.
+#[no_mangle]
+pub unsafe extern "C" fn toodle_export_json(manager: *mut Store) -> *mut c_char {
+    let manager = &mut *manager;
+    match manager.export_json() {
+        Ok(json) => string_to_c_char(json),
+        Err(e) => {
+            log::d(&format!("export_json error: {:?}", e));
+            std::ptr::null_mut()
+        },
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn toodle_import_json(manager: *mut Store, json: *const c_char) -> *mut ExternResult {
+    let manager = &mut *manager;
+    let json = c_char_to_string(json);
+    Box::into_raw(Box::new(manager.import_json(&json).into()))
+}
+
+/// Returns null if the store couldn't be exported.
+#[no_mangle]
+pub unsafe extern "C" fn toodle_export_ics(manager: *mut Store) -> *mut c_char {
+    let manager = &mut *manager;
+    match manager.export_ics() {
+        Ok(ics) => string_to_c_char(ics),
+        Err(e) => {
+            log::d(&format!("export_ics error: {:?}", e));
+            std::ptr::null_mut()
+        },
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn toodle_import_ics(manager: *mut Store, ics: *const c_char) -> *mut ExternResult {
+    let manager = &mut *manager;
+    let ics = c_char_to_string(ics);
+    Box::into_raw(Box::new(manager.import_ics(&ics).into()))
+}
+
+/// Returns null if the store couldn't be exported.
+#[no_mangle]
+pub unsafe extern "C" fn toodle_export_todo_txt(manager: *mut Store) -> *mut c_char {
+    let manager = &mut *manager;
+    match manager.export_todo_txt() {
+        Ok(text) => string_to_c_char(text),
+        Err(e) => {
+            log::d(&format!("export_todo_txt error: {:?}", e));
+            std::ptr::null_mut()
+        },
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn toodle_import_todo_txt(manager: *mut Store, text: *const c_char) -> *mut ExternResult {
+    let manager = &mut *manager;
+    let text = c_char_to_string(text);
+    Box::into_raw(Box::new(manager.import_todo_txt(&text).into()))
+}
+
+/// Returns null if the store couldn't be exported.
+#[no_mangle]
+pub unsafe extern "C" fn toodle_export_taskwarrior(manager: *mut Store) -> *mut c_char {
+    let manager = &mut *manager;
+    match manager.export_taskwarrior() {
+        Ok(json) => string_to_c_char(json),
+        Err(e) => {
+            log::d(&format!("export_taskwarrior error: {:?}", e));
+            std::ptr::null_mut()
+        },
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn toodle_import_taskwarrior(manager: *mut Store, json: *const c_char) -> *mut ExternResult {
+    let manager = &mut *manager;
+    let json = c_char_to_string(json);
+    Box::into_raw(Box::new(manager.import_taskwarrior(&json).into()))
+}
+
+/// Calls `callback` with `key`, from another thread, whenever items, labels or lists change.
+/// The changes are then read with `toodle_take_change_events`. Returns null if the observer
+/// couldn't be registered.
+#[no_mangle]
+pub unsafe extern "C" fn toodle_register_change_observer(manager: *mut Store, key: *const c_char, callback: extern fn(key: *const c_char)) -> *mut ChangeObserver {
+    let manager = &mut *manager;
+    let key = c_char_to_string(key).to_string();
+    let observer = manager.register_change_observer(key, move |key| {
+        if let Ok(key) = CString::new(key) {
+            callback(key.as_ptr());
+        }
+    });
+    match observer {
+        Ok(observer) => Box::into_raw(Box::new(observer)),
+        Err(e) => {
+            log::d(&format!("register_change_observer error: {:?}", e));
+            std::ptr::null_mut()
+        },
+    }
+}
+
+/// On success, the result holds a `ChangeEventCList` of the changes since the last call.
+#[no_mangle]
+pub unsafe extern "C" fn toodle_take_change_events(manager: *mut Store, observer: *const ChangeObserver) -> *mut ExternResult {
+    let manager = &mut *manager;
+    let observer = &*observer;
+    let result = manager.take_change_events(observer).map(|events| {
+        let events: Vec<ChangeEventC> = events.into_iter().map(|event| event.into()).collect();
+        let count = events.len();
+        ChangeEventCList {
+            events: events.into_boxed_slice(),
+            len: count,
+        }
+    });
+    Box::into_raw(Box::new(result.into()))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn change_event_list_entry_at(event_list: *mut ChangeEventCList, index: c_int) -> *const ChangeEventC {
+    let event_list = &*event_list;
+    let index = index as usize;
+    let event = Box::new(event_list.events[index].clone());
+    Box::into_raw(event)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn change_event_list_count(event_list: *mut ChangeEventCList) -> c_int {
+    let event_list = &*event_list;
+    event_list.len as c_int
+}
+
+/// Frees the list and everything in it. Entries returned by `change_event_list_entry_at`
+/// share its strings, so they can't be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn change_event_list_destroy(event_list: *mut ChangeEventCList) {
+    let event_list = Box::from_raw(event_list);
+    for event in event_list.events.iter() {
+        for string in &[event.id, event.previous_name, event.fields] {
+            if !string.is_null() {
+                let _ = CString::from_raw(*string);
+            }
+        }
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn toodle_unregister_change_observer(manager: *mut Store, observer: *mut ChangeObserver) {
+    let manager = &mut *manager;
+    let observer = Box::from_raw(observer);
+    manager.unregister_change_observer(*observer);
+}
+
 // TODO: figure out callbacks in swift such that we can use `toodle_all_items` instead.
+/// On success, the result holds an `ItemCList`.
+#[no_mangle]
+pub unsafe extern "C" fn toodle_get_all_items(manager: *mut Store) -> *mut ExternResult {
+    let manager = &mut *manager;
+    let result = manager.fetch_items(&ItemQuery::new()).map(|items| {
+        let items: ItemsC = items.into();
+        let count = items.vec.len();
+        ItemCList {
+            items: items.vec.into_boxed_slice(),
+            len: count,
+        }
+    });
+    Box::into_raw(Box::new(result.into()))
+}
+
+/// On success, the result holds an `ItemCList`.
+#[no_mangle]
+pub unsafe extern "C" fn toodle_query_items(manager: *mut Store, query: *const ItemQuery) -> *mut ExternResult {
+    let manager = &mut *manager;
+    let query = &*query;
+    let result = manager.fetch_items(query).map(|items| {
+        let items: ItemsC = items.into();
+        let count = items.vec.len();
+        ItemCList {
+            items: items.vec.into_boxed_slice(),
+            len: count,
+        }
+    });
+    Box::into_raw(Box::new(result.into()))
+}
+
+/// On success, the result holds a `SearchResultCList`.
 #[no_mangle]
-pub unsafe extern "C" fn toodle_get_all_items(manager: *mut Store) -> *mut ItemCList {
+pub unsafe extern "C" fn toodle_search_items(manager: *mut Store, text: *const c_char) -> *mut ExternResult {
     let manager = &mut *manager;
-    let items: ItemsC = manager.fetch_items().map(|item| item.into()).expect("all items");
-    let count = items.vec.len();
-    let item_list = ItemCList {
-        items: items.vec.into_boxed_slice(),
-        len: count,
+    let text = c_char_to_string(text).to_string();
+    let result = manager.search_items(&text).map(|results| {
+        let results: Vec<SearchResultC> = results.into_iter().map(|result| result.into()).collect();
+        let count = results.len();
+        SearchResultCList {
+            results: results.into_boxed_slice(),
+            len: count,
+        }
+    });
+    Box::into_raw(Box::new(result.into()))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn search_result_list_entry_at(result_list: *mut SearchResultCList, index: c_int) -> *const SearchResultC {
+    let result_list = &*result_list;
+    let index = index as usize;
+    let result = Box::new(result_list.results[index].clone());
+    Box::into_raw(result)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn search_result_list_count(result_list: *mut SearchResultCList) -> c_int {
+    let result_list = &*result_list;
+    result_list.len as c_int
+}
+
+/// Frees the list and everything in it. Entries returned by `search_result_list_entry_at`
+/// share its strings, so they can't be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn search_result_list_destroy(result_list: *mut SearchResultCList) {
+    let result_list = Box::from_raw(result_list);
+    for result in result_list.results.iter() {
+        release_item_c(&result.item);
+        let _ = CString::from_raw(result.snippet);
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn item_query_new() -> *mut ItemQuery {
+    Box::into_raw(Box::new(ItemQuery::new()))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn item_query_destroy(query: *mut ItemQuery) {
+    let _ = Box::from_raw(query);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn item_query_set_completed(query: *mut ItemQuery, completed: c_int) {
+    let query = &mut*query;
+    query.completed = Some(completed != 0);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn item_query_set_due_after(query: *mut ItemQuery, due_after: *const i64) {
+    let query = &mut*query;
+    query.due_after = optional_timespec(due_after);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn item_query_set_due_before(query: *mut ItemQuery, due_before: *const i64) {
+    let query = &mut*query;
+    query.due_before = optional_timespec(due_before);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn item_query_add_label(query: *mut ItemQuery, name: *const c_char) {
+    let query = &mut*query;
+    query.labels.push(c_char_to_string(name).to_string());
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn item_query_set_match_all_labels(query: *mut ItemQuery, all: c_int) {
+    let query = &mut*query;
+    query.label_match = if all != 0 { LabelMatch::All } else { LabelMatch::Any };
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn item_query_set_name_contains(query: *mut ItemQuery, text: *const c_char) {
+    let query = &mut*query;
+    query.name_contains = Some(c_char_to_string(text).to_string());
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn item_query_set_list(query: *mut ItemQuery, uuid: *const c_char) {
+    let query = &mut*query;
+    query.list = Uuid::parse_str(&c_char_to_string(uuid).to_string()).ok();
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn item_query_set_parent(query: *mut ItemQuery, uuid: *const c_char) {
+    let query = &mut*query;
+    query.parent = Uuid::parse_str(&c_char_to_string(uuid).to_string()).ok();
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn item_query_set_top_level_only(query: *mut ItemQuery, top_level_only: c_int) {
+    let query = &mut*query;
+    query.top_level_only = top_level_only != 0;
+}
+
+/// `trash` is 0 to leave out items in the trash, 1 to include them and 2 for only those.
+#[no_mangle]
+pub unsafe extern "C" fn item_query_set_trash(query: *mut ItemQuery, trash: c_int) {
+    let query = &mut*query;
+    query.trash = match trash {
+        1 => TrashFilter::Include,
+        2 => TrashFilter::Only,
+        _ => TrashFilter::Exclude,
     };
+}
 
-    Box::into_raw(Box::new(item_list))
+/// `order` is 0 for due date, 1 for completion date, 2 for name, 3 for creation date, 4 for
+/// position and 5 for modification date. Anything else goes back to the default, which is
+/// ascending by position.
+#[no_mangle]
+pub unsafe extern "C" fn item_query_set_order(query: *mut ItemQuery, order: c_int, descending: c_int) {
+    let query = &mut*query;
+    let order = match order {
+        0 => ItemOrder::DueDate,
+        1 => ItemOrder::CompletionDate,
+        2 => ItemOrder::Name,
+        3 => ItemOrder::Creation,
+        4 => ItemOrder::Position,
+        5 => ItemOrder::Modification,
+        _ => {
+            query.order = None;
+            return;
+        },
+    };
+    let direction = if descending != 0 { SortDirection::Descending } else { SortDirection::Ascending };
+    query.order = Some((order, direction));
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn item_query_set_limit(query: *mut ItemQuery, limit: size_t) {
+    let query = &mut*query;
+    query.limit = Some(limit as u64);
 }
 
 #[no_mangle]
@@ -153,27 +823,31 @@ pub unsafe extern "C" fn item_list_count(item_list: *mut ItemCList) -> c_int {
     item_list.len as c_int
 }
 
+/// Calls `callback` with the items if they could be fetched, and otherwise returns the error.
 #[no_mangle]
-pub unsafe extern "C" fn toodle_all_items(manager: *mut Store, callback: extern "C" fn(Option<&ItemCList>)) {
+pub unsafe extern "C" fn toodle_all_items(manager: *mut Store, callback: extern "C" fn(Option<&ItemCList>)) -> *mut ExternResult {
     let manager = &mut*manager;
-    let items: ItemsC = manager.fetch_items().map(|item| item.into()).expect("all items");
+    let result = manager.fetch_items(&ItemQuery::new()).map(|items| {
+        let items: ItemsC = items.into();
 
-    // TODO there's bound to be a better way. Ideally this should just return an empty set,
-    // but I ran into problems while doing that.
-    let count = items.vec.len();
+        // TODO there's bound to be a better way. Ideally this should just return an empty set,
+        // but I ran into problems while doing that.
+        let count = items.vec.len();
 
-    let set = ItemCList {
-        items: items.vec.into_boxed_slice(),
-        len: count,
-    };
+        let set = ItemCList {
+            items: items.vec.into_boxed_slice(),
+            len: count,
+        };
 
-    let res = match count > 0 {
-        // NB: we're lending a set, it will be cleaned up automatically once 'callback' returns
-        true => Some(&set),
-        false => None
-    };
+        let res = match count > 0 {
+            // NB: we're lending a set, it will be cleaned up automatically once 'callback' returns
+            true => Some(&set),
+            false => None
+        };
 
-    callback(res);
+        callback(res);
+    });
+    Box::into_raw(Box::new(result.into()))
 }
 
 
@@ -186,16 +860,48 @@ pub unsafe extern "C" fn item_c_destroy(item: *mut ItemC) -> *mut ItemC {
     // Reclaim our strings and let Rust clear up their memory.
     let _ = CString::from_raw(item.uuid);
     let _ = CString::from_raw(item.name);
+    if !item.notes.is_null() {
+        let _ = CString::from_raw(item.notes);
+    }
+    if !item.recurrence.is_null() {
+        let _ = CString::from_raw(item.recurrence);
+    }
+    if !item.list.is_null() {
+        let _ = CString::from_raw(item.list);
+    }
+    if !item.parent.is_null() {
+        let _ = CString::from_raw(item.parent);
+    }
 
     // Prevent Rust from clearing out item itself. It's already managed by toodle_all_items.
     // If we'll let Rust clean up entirely here, we'll get an NPE in toodle_all_items.
     Box::into_raw(item)
 }
 
+/// Reclaims the strings and dates an `ItemC` embedded in a list owns. The `ItemC` itself is
+/// freed along with the list.
+unsafe fn release_item_c(item: &ItemC) {
+    let _ = CString::from_raw(item.uuid);
+    let _ = CString::from_raw(item.name);
+    for string in &[item.notes, item.recurrence, item.list, item.parent] {
+        if !string.is_null() {
+            let _ = CString::from_raw(*string);
+        }
+    }
+    for date in &[item.due_date, item.completion_date, item.deleted_at, item.created_at, item.modified_at] {
+        if !date.is_null() {
+            let _ = Box::from_raw(*date);
+        }
+    }
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn toodle_item_for_uuid(manager: *mut Store, uuid: *const c_char) -> *mut ItemC {
     let uuid_string = c_char_to_string(uuid).to_string();
-    let uuid = Uuid::parse_str(&uuid_string).unwrap();
+    let uuid = match Uuid::parse_str(&uuid_string) {
+        Ok(uuid) => uuid,
+        Err(_) => return std::ptr::null_mut(),
+    };
     let manager = &mut*manager;
 
     if let Ok(Some(i)) = manager.fetch_item(&uuid) {
@@ -206,7 +912,7 @@ pub unsafe extern "C" fn toodle_item_for_uuid(manager: *mut Store, uuid: *const
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn toodle_update_item(manager: *mut Store, item: *const Item, name: *const c_char, due_date: *const time_t, completion_date: *const time_t, labels: *const Vec<Label>) {
+pub unsafe extern "C" fn toodle_update_item(manager: *mut Store, item: *const Item, name: *const c_char, due_date: *const i64, completion_date: *const i64, labels: *const Vec<Label>) {
     let name = c_char_to_string(name).to_string();
     let manager = &mut*manager;
     let item = &*item;
@@ -221,7 +927,7 @@ pub unsafe extern "C" fn toodle_update_item(manager: *mut Store, item: *const It
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn toodle_update_item_by_uuid(manager: *mut Store, uuid: *const c_char, name: *const c_char, due_date: *const time_t, completion_date: *const time_t) {
+pub unsafe extern "C" fn toodle_update_item_by_uuid(manager: *mut Store, uuid: *const c_char, name: *const c_char, due_date: *const i64, completion_date: *const i64) {
     let name = c_char_to_string(name).to_string();
     let manager = &mut*manager;
     // TODO proper error handling, see https://github.com/mozilla-prototypes/sync-storage-prototype/pull/6
@@ -235,6 +941,116 @@ pub unsafe extern "C" fn toodle_update_item_by_uuid(manager: *mut Store, uuid: *
     // }
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn toodle_update_item_notes(manager: *mut Store, uuid: *const c_char, notes: *const c_char) -> *mut ExternResult {
+    let manager = &mut*manager;
+    let uuid_string = c_char_to_string(uuid).to_string();
+    let notes = if notes.is_null() { None } else { Some(c_char_to_string(notes).to_string()) };
+    let result = Uuid::parse_str(&uuid_string)
+        .map_err(|e| e.into())
+        .and_then(|uuid| manager.fetch_item(&uuid))
+        .and_then(|item| item.ok_or_else(|| ErrorKind::ItemNotFound(uuid_string.clone()).into()))
+        .and_then(|item| manager.update_item_notes(&item, notes));
+    Box::into_raw(Box::new(result.into()))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn toodle_delete_item(manager: *mut Store, uuid: *const c_char) -> *mut ExternResult {
+    let manager = &mut*manager;
+    let uuid_string = c_char_to_string(uuid).to_string();
+    let result = Uuid::parse_str(&uuid_string)
+        .map_err(|e| e.into())
+        .and_then(|uuid| manager.delete_item(&uuid));
+    Box::into_raw(Box::new(result.into()))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn toodle_trash_item(manager: *mut Store, uuid: *const c_char) -> *mut ExternResult {
+    let manager = &mut*manager;
+    let uuid_string = c_char_to_string(uuid).to_string();
+    let result = Uuid::parse_str(&uuid_string)
+        .map_err(|e| e.into())
+        .and_then(|uuid| manager.trash_item(&uuid));
+    Box::into_raw(Box::new(result.into()))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn toodle_restore_item(manager: *mut Store, uuid: *const c_char) -> *mut ExternResult {
+    let manager = &mut*manager;
+    let uuid_string = c_char_to_string(uuid).to_string();
+    let result = Uuid::parse_str(&uuid_string)
+        .map_err(|e| e.into())
+        .and_then(|uuid| manager.restore_item(&uuid));
+    Box::into_raw(Box::new(result.into()))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn toodle_empty_trash(manager: *mut Store, older_than: i64) -> *mut ExternResult {
+    let manager = &mut*manager;
+    Box::into_raw(Box::new(manager.empty_trash(from_micros(older_than)).into()))
+}
+
+/// Parses the `count` UUID strings `uuids` points to.
+unsafe fn uuids_from_c(uuids: *const *const c_char, count: size_t) -> toodle::errors::Result<Vec<Uuid>> {
+    if uuids.is_null() {
+        return Ok(vec![]);
+    }
+    std::slice::from_raw_parts(uuids, count)
+        .iter()
+        .map(|&uuid| Uuid::parse_str(&c_char_to_string(uuid).to_string()).map_err(|e| e.into()))
+        .collect()
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn toodle_complete_items(manager: *mut Store, uuids: *const *const c_char, count: size_t, completion_date: i64) -> *mut ExternResult {
+    let manager = &mut*manager;
+    let result = uuids_from_c(uuids, count)
+        .and_then(|uuids| manager.complete_items(&uuids, from_micros(completion_date)));
+    Box::into_raw(Box::new(result.into()))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn toodle_uncomplete_items(manager: *mut Store, uuids: *const *const c_char, count: size_t) -> *mut ExternResult {
+    let manager = &mut*manager;
+    let result = uuids_from_c(uuids, count)
+        .and_then(|uuids| manager.uncomplete_items(&uuids));
+    Box::into_raw(Box::new(result.into()))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn toodle_set_due_date_for_items(manager: *mut Store, uuids: *const *const c_char, count: size_t, due_date: *const i64) -> *mut ExternResult {
+    let manager = &mut*manager;
+    let result = uuids_from_c(uuids, count)
+        .and_then(|uuids| manager.set_due_date_for_items(&uuids, optional_timespec(due_date)));
+    Box::into_raw(Box::new(result.into()))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn toodle_add_label_to_items(manager: *mut Store, uuids: *const *const c_char, count: size_t, label: *const Label) -> *mut ExternResult {
+    let manager = &mut*manager;
+    let label = &*label;
+    let result = uuids_from_c(uuids, count)
+        .and_then(|uuids| manager.add_label_to_items(&uuids, label));
+    Box::into_raw(Box::new(result.into()))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn toodle_remove_label_from_items(manager: *mut Store, uuids: *const *const c_char, count: size_t, label: *const Label) -> *mut ExternResult {
+    let manager = &mut*manager;
+    let label = &*label;
+    let result = uuids_from_c(uuids, count)
+        .and_then(|uuids| manager.remove_label_from_items(&uuids, label));
+    Box::into_raw(Box::new(result.into()))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn toodle_delete_items(manager: *mut Store, uuids: *const *const c_char, count: size_t) -> *mut ExternResult {
+    let manager = &mut*manager;
+    let result = uuids_from_c(uuids, count)
+        .and_then(|uuids| manager.delete_items(&uuids));
+    Box::into_raw(Box::new(result.into()))
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn toodle_create_label(manager: *mut Store, name: *const c_char, color: *const c_char) -> *mut Option<Label> {
     let manager = &mut*manager;
@@ -244,6 +1060,135 @@ pub unsafe extern "C" fn toodle_create_label(manager: *mut Store, name: *const c
     Box::into_raw(label)
 }
 
+/// On success, the result holds the renamed `Label`. Fails if another label already has
+/// `name`.
+#[no_mangle]
+pub unsafe extern "C" fn toodle_rename_label(manager: *mut Store, label: *const Label, name: *const c_char) -> *mut ExternResult {
+    let manager = &mut*manager;
+    let label = &*label;
+    let name = c_char_to_string(name).to_string();
+    let result = manager.rename_label(label, name.clone())
+        .and_then(|label| label.ok_or_else(|| ErrorKind::LabelNotFound(name).into()));
+    Box::into_raw(Box::new(result.into()))
+}
+
+/// On success, the result holds the updated `Label`.
+#[no_mangle]
+pub unsafe extern "C" fn toodle_update_label_color(manager: *mut Store, label: *const Label, color: *const c_char) -> *mut ExternResult {
+    let manager = &mut*manager;
+    let label = &*label;
+    let color = c_char_to_string(color).to_string();
+    let result = manager.update_label_color(label, color)
+        .and_then(|updated| updated.ok_or_else(|| ErrorKind::LabelNotFound(label.name.clone()).into()));
+    Box::into_raw(Box::new(result.into()))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn toodle_delete_label(manager: *mut Store, name: *const c_char) -> *mut ExternResult {
+    let manager = &mut*manager;
+    let name = c_char_to_string(name).to_string();
+    Box::into_raw(Box::new(manager.delete_label(&name).into()))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn toodle_create_list(manager: *mut Store, name: *const c_char, color: *const c_char) -> *mut ListC {
+    let manager = &mut*manager;
+    let name = c_char_to_string(name).to_string();
+    let color = c_char_to_string(color).to_string();
+    match manager.create_list(name, color) {
+        Ok(list) => Box::into_raw(Box::new(list.into())),
+        Err(_) => std::ptr::null_mut(),
+    }
+}
+
+/// On success, the result holds a `ListCList`.
+#[no_mangle]
+pub unsafe extern "C" fn toodle_get_all_lists(manager: *mut Store) -> *mut ExternResult {
+    let manager = &mut*manager;
+    let result = manager.fetch_lists().map(|lists| {
+        let lists: Vec<ListC> = lists.into_iter().map(|list| list.into()).collect();
+        let count = lists.len();
+        ListCList {
+            lists: lists.into_boxed_slice(),
+            len: count,
+        }
+    });
+    Box::into_raw(Box::new(result.into()))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn list_c_list_entry_at(list_list: *mut ListCList, index: c_int) -> *const ListC {
+    let list_list = &*list_list;
+    let index = index as usize;
+    let list = Box::new(list_list.lists[index].clone());
+    Box::into_raw(list)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn list_c_list_count(list_list: *mut ListCList) -> c_int {
+    let list_list = &*list_list;
+    list_list.len as c_int
+}
+
+/// Frees the list and everything in it. Entries returned by `list_c_list_entry_at` share its
+/// strings, so they can't be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn list_c_list_destroy(list_list: *mut ListCList) {
+    let list_list = Box::from_raw(list_list);
+    for list in list_list.lists.iter() {
+        let _ = CString::from_raw(list.uuid);
+        let _ = CString::from_raw(list.name);
+        let _ = CString::from_raw(list.color);
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn list_c_destroy(list: *mut ListC) {
+    let list = Box::from_raw(list);
+    let _ = CString::from_raw(list.uuid);
+    let _ = CString::from_raw(list.name);
+    let _ = CString::from_raw(list.color);
+}
+
+/// Null `name`, `color` and `sort_order` leave those fields unchanged.
+#[no_mangle]
+pub unsafe extern "C" fn toodle_update_list(manager: *mut Store, uuid: *const c_char, name: *const c_char, color: *const c_char, sort_order: *const i64) -> *mut ExternResult {
+    let manager = &mut*manager;
+    let uuid_string = c_char_to_string(uuid).to_string();
+    let name = if name.is_null() { None } else { Some(c_char_to_string(name).to_string()) };
+    let color = if color.is_null() { None } else { Some(c_char_to_string(color).to_string()) };
+    let sort_order = if sort_order.is_null() { None } else { Some(*sort_order) };
+    let result = Uuid::parse_str(&uuid_string)
+        .map_err(|e| e.into())
+        .and_then(|uuid| manager.fetch_list(&uuid))
+        .and_then(|list| list.ok_or_else(|| ErrorKind::ListNotFound(uuid_string.clone()).into()))
+        .and_then(|list| manager.update_list(&list, name, color, sort_order))
+        .map(|_| ());
+    Box::into_raw(Box::new(result.into()))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn toodle_delete_list(manager: *mut Store, uuid: *const c_char) -> *mut ExternResult {
+    let manager = &mut*manager;
+    let uuid_string = c_char_to_string(uuid).to_string();
+    let result = Uuid::parse_str(&uuid_string)
+        .map_err(|e| e.into())
+        .and_then(|uuid| manager.delete_list(&uuid));
+    Box::into_raw(Box::new(result.into()))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn toodle_move_item_to_list(manager: *mut Store, uuid: *const c_char, list: *const c_char) -> *mut ExternResult {
+    let manager = &mut*manager;
+    let uuid_string = c_char_to_string(uuid).to_string();
+    let list_string = c_char_to_string(list).to_string();
+    let result = Uuid::parse_str(&uuid_string)
+        .and_then(|uuid| Uuid::parse_str(&list_string).map(|list| (uuid, list)))
+        .map_err(|e| e.into())
+        .and_then(|(uuid, list)| manager.move_item_to_list(&uuid, &list));
+    Box::into_raw(Box::new(result.into()))
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn label_destroy(label: *mut Label) {
     let _ = Box::from_raw(label);
@@ -274,20 +1219,30 @@ pub unsafe extern "C" fn item_set_name(item: *mut Item, name: *const c_char) {
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn item_set_due_date(item: *mut Item, due_date: *const size_t) {
+pub unsafe extern "C" fn item_set_notes(item: *mut Item, notes: *const c_char) {
+    let item = &mut*item;
+    if !notes.is_null() {
+        item.notes = Some(c_char_to_string(notes).to_string());
+    } else {
+        item.notes = None;
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn item_set_due_date(item: *mut Item, due_date: *const i64) {
     let item = &mut*item;
     if !due_date.is_null() {
-        item.due_date = Some(Timespec::new(due_date as i64, 0));
+        item.due_date = Some(from_micros(*due_date));
     } else {
         item.due_date = None;
     }
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn item_set_completion_date(item: *mut Item, completion_date: *const size_t) {
+pub unsafe extern "C" fn item_set_completion_date(item: *mut Item, completion_date: *const i64) {
     let item = &mut*item;
     if !completion_date.is_null() {
-        item.completion_date = Some(Timespec::new(completion_date as i64, 0));
+        item.completion_date = Some(from_micros(*completion_date));
     } else {
         item.completion_date = None;
     }
@@ -9,12 +9,26 @@
 // specific language governing permissions and limitations under the License.
 pub mod time {
     use time::Timespec;
-    use libc::time_t;
 
-    pub fn optional_timespec(timestamp: *const time_t) -> Option<Timespec> {
+    /// Timestamps cross the FFI boundary as microseconds since the epoch, the precision Mentat
+    /// stores them at.
+    pub fn from_micros(micros: i64) -> Timespec {
+        let (sec, micros) = (micros / 1_000_000, micros % 1_000_000);
+        if micros < 0 {
+            Timespec::new(sec - 1, ((micros + 1_000_000) * 1_000) as i32)
+        } else {
+            Timespec::new(sec, (micros * 1_000) as i32)
+        }
+    }
+
+    pub fn to_micros(timespec: &Timespec) -> i64 {
+        timespec.sec * 1_000_000 + i64::from(timespec.nsec / 1_000)
+    }
+
+    pub fn optional_timespec(timestamp: *const i64) -> Option<Timespec> {
         match timestamp.is_null() {
             true => None,
-            false => Some(Timespec::new(unsafe { *timestamp as i64 }, 0))
+            false => Some(from_micros(unsafe { *timestamp }))
         }
     }
 }
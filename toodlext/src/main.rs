@@ -11,9 +11,13 @@ use std::io::{self, Read, StdinLock, StdoutLock, Write};
 use std::iter::FromIterator;
 
 use byteorder::{NativeEndian, ReadBytesExt, WriteBytesExt};
-use toodle::{Store, Timespec, Toodle, Uuid};
-use toodle::items::Item;
+use toodle::{Duration, ItemOrder, ItemQuery, SearchResult, Store, Timespec, SortDirection, Toodle, TypedValue, Uuid};
+use toodle::errors::ErrorKind;
+use toodle::history::{ChangeOrigin, ItemChange};
+use toodle::json::{from_millis, to_millis, ItemInfo, LabelInfo, ListInfo};
 use toodle::labels::Label;
+use toodle::recurrence::{Occurrence, Recurrence};
+use toodle::reminders::{PendingReminder, Reminder, ReminderTrigger};
 
 #[derive(Serialize, Debug)]
 enum Error {
@@ -24,89 +28,243 @@ enum Error {
     ItemNotFound,
     UpdateItemFailed,
     UpdateLabelsFailed,
+    RemoveItemFailed,
+    LabelAlreadyExists,
+    UpdateLabelFailed,
+    RemoveLabelFailed,
+    ItemCycle,
+    ListNotFound,
+    InvalidRecurrence,
+    UpdateListFailed,
+    RemoveListFailed,
+    ReminderNotFound,
+    UpdateReminderFailed,
+    UndoFailed,
+    ExportFailed,
+    ImportFailed,
     NotImplemented,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-struct LabelInfo {
-    name: String,
+#[derive(Serialize, Debug)]
+struct OccurrenceInfo {
+    todo: ItemInfo,
+
+    #[serde(rename = "dueDate")]
+    due_date: f64,
+}
+
+impl From<Occurrence> for OccurrenceInfo {
+    fn from(occurrence: Occurrence) -> Self {
+        OccurrenceInfo {
+            due_date: to_millis(&occurrence.due_date),
+            todo: occurrence.item.into(),
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+struct ReminderInfo {
+    uuid: String,
+    todo: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    at: Option<f64>,
+
+    /// How long before the todo is due the reminder goes off, in milliseconds.
     #[serde(skip_serializing_if = "Option::is_none")]
-    color: Option<String>,
+    offset: Option<i64>,
+
+    #[serde(rename = "snoozedUntil", skip_serializing_if = "Option::is_none")]
+    snoozed_until: Option<f64>,
 }
 
-impl From<Label> for LabelInfo {
-    fn from(label: Label) -> Self {
-        LabelInfo {
-            name: label.name.clone(),
-            color: Some(label.color.clone()),
+impl From<Reminder> for ReminderInfo {
+    fn from(reminder: Reminder) -> Self {
+        let (at, offset) = match reminder.trigger {
+            ReminderTrigger::At(date) => (Some(to_millis(&date)), None),
+            ReminderTrigger::BeforeDue(offset) => (None, Some(offset.num_milliseconds())),
+        };
+        ReminderInfo {
+            uuid: reminder.uuid.hyphenated().to_string(),
+            todo: reminder.item.hyphenated().to_string(),
+            at,
+            offset,
+            snoozed_until: reminder.snoozed_until.as_ref().map(to_millis),
         }
     }
 }
 
-impl Into<Label> for LabelInfo {
-    fn into(self) -> Label {
-        Label {
-            id: None,
-            name: self.name.clone(),
-            color: self.color.clone().unwrap_or_default(),
+#[derive(Serialize, Debug)]
+struct PendingReminderInfo {
+    reminder: ReminderInfo,
+    todo: ItemInfo,
+
+    #[serde(rename = "fireAt")]
+    fire_at: f64,
+}
+
+impl From<PendingReminder> for PendingReminderInfo {
+    fn from(pending: PendingReminder) -> Self {
+        PendingReminderInfo {
+            fire_at: to_millis(&pending.fire_at),
+            todo: pending.item.into(),
+            reminder: pending.reminder.into(),
         }
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-struct ItemInfo {
-    #[serde(skip_serializing_if = "Option::is_none")]
-    uuid: Option<String>,
-
-    name: String,
-
-    #[serde(rename = "dueDate", skip_serializing_if = "Option::is_none")]
-    due_date: Option<i64>,
-
-    #[serde(rename = "completionDate", skip_serializing_if = "Option::is_none")]
-    completion_date: Option<i64>,
-
-    labels: Option<Vec<LabelInfo>>,
-}
-
-impl From<Item> for ItemInfo {
-    fn from(item: Item) -> Self {
-        let due_date = item.due_date.as_ref().map_or(0, to_millis);
-        let completion_date = item.completion_date.as_ref().map_or(0, to_millis);
-        let label_infos = Some(item.labels
-                                   .clone()
-                                   .into_iter()
-                                   .map(|label| label.into())
-                                   .collect());
-        ItemInfo {
-            uuid: Some(item.uuid.hyphenated().to_string()),
-            name: item.name.clone(),
-            due_date: Some(due_date),
-            completion_date: Some(completion_date),
-            labels: label_infos,
+#[derive(Serialize, Debug)]
+enum OriginInfo {
+    #[serde(rename = "local")]
+    Local,
+    #[serde(rename = "sync")]
+    Sync,
+    #[serde(rename = "unknown")]
+    Unknown,
+}
+
+#[derive(Serialize, Debug)]
+struct ItemChangeInfo {
+    attribute: String,
+
+    #[serde(rename = "oldValue", skip_serializing_if = "Option::is_none")]
+    old_value: Option<serde_json::Value>,
+
+    #[serde(rename = "newValue", skip_serializing_if = "Option::is_none")]
+    new_value: Option<serde_json::Value>,
+
+    #[serde(rename = "txInstant")]
+    tx_instant: f64,
+
+    origin: OriginInfo,
+}
+
+impl From<ItemChange> for ItemChangeInfo {
+    fn from(change: ItemChange) -> Self {
+        ItemChangeInfo {
+            attribute: change.attribute.to_string(),
+            old_value: change.old_value.map(value_to_json),
+            new_value: change.new_value.map(value_to_json),
+            tx_instant: to_millis(&change.tx_instant),
+            origin: match change.origin {
+                ChangeOrigin::Local => OriginInfo::Local,
+                ChangeOrigin::Sync => OriginInfo::Sync,
+                ChangeOrigin::Unknown => OriginInfo::Unknown,
+            },
         }
     }
 }
 
-impl Into<Item> for ItemInfo {
-    fn into(self) -> Item {
-        let due_date = self.due_date.map(from_millis);
-        let completion_date = self.completion_date.map(from_millis);
-        let labels = match self.labels {
-            Some(labels) => labels.into_iter().map(|label| label.into()).collect(),
-            None => Vec::new(),
-        };
-        Item {
-            id: None,
-            uuid: self.uuid
-                .clone()
-                .and_then(|uuid| Uuid::parse_str(&uuid).ok())
-                .unwrap_or_else(|| Uuid::nil()),
-            name: self.name.clone(),
-            due_date,
-            completion_date,
-            labels,
+#[derive(Serialize, Debug)]
+struct SearchResultInfo {
+    todo: ItemInfo,
+    score: f64,
+    snippet: String,
+}
+
+impl From<SearchResult> for SearchResultInfo {
+    fn from(result: SearchResult) -> Self {
+        SearchResultInfo {
+            todo: result.item.into(),
+            score: result.score,
+            snippet: result.snippet,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+enum OrderInfo {
+    #[serde(rename = "dueDate")]
+    DueDate,
+    #[serde(rename = "completionDate")]
+    CompletionDate,
+    #[serde(rename = "name")]
+    Name,
+    #[serde(rename = "creation")]
+    Creation,
+    #[serde(rename = "modification")]
+    Modification,
+}
+
+#[derive(Deserialize, Debug)]
+struct QueryInfo {
+    completed: Option<bool>,
+
+    #[serde(rename = "dueAfter")]
+    due_after: Option<f64>,
+
+    #[serde(rename = "dueBefore")]
+    due_before: Option<f64>,
+
+    labels: Option<Vec<String>>,
+
+    #[serde(rename = "matchAllLabels", default)]
+    match_all_labels: bool,
+
+    #[serde(rename = "nameContains")]
+    name_contains: Option<String>,
+
+    list: Option<String>,
+
+    #[serde(rename = "topLevelOnly", default)]
+    top_level_only: bool,
+
+    #[serde(rename = "orderBy")]
+    order_by: Option<OrderInfo>,
+
+    #[serde(default)]
+    descending: bool,
+
+    limit: Option<u64>,
+}
+
+impl Into<ItemQuery> for QueryInfo {
+    fn into(self) -> ItemQuery {
+        let mut query = ItemQuery::new();
+        if let Some(completed) = self.completed {
+            query = query.completed(completed);
+        }
+        if let Some(due_after) = self.due_after {
+            query = query.due_after(from_millis(due_after));
+        }
+        if let Some(due_before) = self.due_before {
+            query = query.due_before(from_millis(due_before));
+        }
+        if let Some(labels) = self.labels {
+            query = if self.match_all_labels {
+                query.with_all_labels(labels)
+            } else {
+                query.with_any_label(labels)
+            };
+        }
+        if let Some(text) = self.name_contains {
+            query = query.name_contains(text);
         }
+        if let Some(list) = self.list.and_then(|list| Uuid::parse_str(&list).ok()) {
+            query = query.in_list(list);
+        }
+        if self.top_level_only {
+            query = query.top_level_only();
+        }
+        if let Some(order_by) = self.order_by {
+            let order = match order_by {
+                OrderInfo::DueDate => ItemOrder::DueDate,
+                OrderInfo::CompletionDate => ItemOrder::CompletionDate,
+                OrderInfo::Name => ItemOrder::Name,
+                OrderInfo::Creation => ItemOrder::Creation,
+                OrderInfo::Modification => ItemOrder::Modification,
+            };
+            let direction = if self.descending {
+                SortDirection::Descending
+            } else {
+                SortDirection::Ascending
+            };
+            query = query.order_by(order, direction);
+        }
+        if let Some(limit) = self.limit {
+            query = query.limit(limit);
+        }
+        query
     }
 }
 
@@ -115,25 +273,93 @@ impl Into<Item> for ItemInfo {
 enum RequestBody {
     CreateTodo(ItemInfo),
     GetTodos,
+    QueryTodos(QueryInfo),
+    SearchTodos { text: String },
     TodoChangeName { uuid: String, name: String },
+    TodoChangeNotes { uuid: String, notes: Option<String> },
+    TodoChangeRecurrence { uuid: String, recurrence: Option<String> },
+    GetOccurrences { start: f64, end: f64 },
     TodoChangeDueDate {
         uuid: String,
 
         #[serde(rename = "dueDate")]
-        due_date: i64,
+        due_date: f64,
     },
     TodoChangeCompletionDate {
         uuid: String,
 
         #[serde(rename = "completionDate")]
-        completion_date: i64,
+        completion_date: f64,
     },
     TodoAddLabel { uuid: String, name: String },
     TodoRemoveLabel { uuid: String, name: String },
+    GetTodoChildren { uuid: String },
+    GetTodoHistory { uuid: String },
+    MoveTodo { uuid: String, parent: Option<String> },
+    ReorderTodo { uuid: String, previous: Option<String>, next: Option<String> },
+    CompleteTodo {
+        uuid: String,
+
+        #[serde(rename = "completionDate")]
+        completion_date: f64,
+
+        #[serde(default)]
+        cascade: bool,
+    },
     RemoveTodo { uuid: String },
+    CompleteTodos {
+        uuids: Vec<String>,
+
+        #[serde(rename = "completionDate")]
+        completion_date: f64,
+    },
+    UncompleteTodos { uuids: Vec<String> },
+    TodosChangeDueDate {
+        uuids: Vec<String>,
+
+        #[serde(rename = "dueDate")]
+        due_date: Option<f64>,
+    },
+    TodosAddLabel { uuids: Vec<String>, name: String },
+    TodosRemoveLabel { uuids: Vec<String>, name: String },
+    RemoveTodos { uuids: Vec<String> },
     AddLabel(LabelInfo),
     RemoveLabel { name: String },
+    LabelChangeName {
+        name: String,
+
+        #[serde(rename = "newName")]
+        new_name: String,
+    },
+    LabelChangeColor { name: String, color: String },
     GetLabels,
+    GetLists,
+    AddList(ListInfo),
+    ListChange {
+        uuid: String,
+        name: Option<String>,
+        color: Option<String>,
+
+        #[serde(rename = "sortOrder")]
+        sort_order: Option<i64>,
+    },
+    RemoveList { uuid: String },
+    TodoChangeList { uuid: String, list: String },
+    AddReminder {
+        todo: String,
+        at: Option<f64>,
+        offset: Option<i64>,
+    },
+    GetReminders { todo: String },
+    PendingReminders { now: f64, window: i64 },
+    AcknowledgeReminder { uuid: String },
+    SnoozeReminder { uuid: String, until: f64 },
+    RemoveReminder { uuid: String },
+    Undo,
+    Redo,
+    GetUndoState,
+    Export,
+    Import { document: serde_json::Value },
 }
 
 #[derive(Deserialize, Debug)]
@@ -186,10 +412,34 @@ enum ResponseBody {
     CreateTodo(ItemInfo),
     UpdateTodo(ItemInfo),
     GetTodos(Vec<ItemInfo>),
+    SearchTodos(Vec<SearchResultInfo>),
+    GetTodoHistory(Vec<ItemChangeInfo>),
+    GetOccurrences(Vec<OccurrenceInfo>),
     GetLabels(Vec<LabelInfo>),
     RemoveTodo { uuid: String },
+    UpdateTodos(Vec<ItemInfo>),
+    RemoveTodos { uuids: Vec<String> },
     AddLabel(LabelInfo),
+    UpdateLabel(LabelInfo),
     RemoveLabel { name: String },
+    GetLists(Vec<ListInfo>),
+    AddList(ListInfo),
+    UpdateList(ListInfo),
+    RemoveList { uuid: String },
+    AddReminder(ReminderInfo),
+    GetReminders(Vec<ReminderInfo>),
+    PendingReminders(Vec<PendingReminderInfo>),
+    UpdateReminder(ReminderInfo),
+    RemoveReminder { uuid: String },
+    UndoState {
+        #[serde(rename = "canUndo")]
+        can_undo: bool,
+
+        #[serde(rename = "canRedo")]
+        can_redo: bool,
+    },
+    Export(serde_json::Value),
+    Import,
 }
 
 fn update_item_labels(toodle: &mut Store,
@@ -198,55 +448,336 @@ fn update_item_labels(toodle: &mut Store,
                       to_remove: Vec<String>)
                       -> Result<ResponseBody, Error> {
     let uuid = Uuid::parse_str(&uuid).map_err(|_err| Error::ItemNotFound)?;
+    // The item and labels are read in the same transaction as the update, so that a label
+    // added or removed in the meantime can't be lost.
+    let item = toodle
+        .transact(|tx| {
+            let item = tx.fetch_item(&uuid)?
+                .ok_or_else(|| ErrorKind::ItemNotFound(uuid.hyphenated().to_string()))?;
+            let existing_labels = tx.fetch_labels()?;
+            if let Some(name) = to_add.iter().find(|name| {
+                                                     existing_labels
+                                                         .iter()
+                                                         .find(|label| &label.name == *name)
+                                                         .is_none()
+                                                 }) {
+                return Err(ErrorKind::LabelNotFound(name.clone()).into());
+            }
+            let existing_item_label_names =
+                HashSet::<&String>::from_iter(item.labels.iter().map(|label| &label.name));
+            let mut new_labels = item.labels.clone();
+            let mut labels_to_add = to_add
+                .iter()
+                .filter_map(|name| if existing_item_label_names.contains(name) {
+                                None
+                            } else {
+                                existing_labels
+                                    .iter()
+                                    .find(|label| &label.name == name)
+                                    .cloned()
+                            })
+                .collect::<Vec<Label>>();
+            new_labels.append(&mut labels_to_add);
+
+            let item_label_names_to_remove = HashSet::<&String>::from_iter(to_remove.iter());
+            new_labels.retain(|label| !item_label_names_to_remove.contains(&label.name));
+
+            tx.update_item(&item, None, None, None, Some(&new_labels))?;
+            tx.fetch_item(&uuid)
+        })
+        .map_err(|err| match *err.kind() {
+                     ErrorKind::ItemNotFound(_) => Error::ItemNotFound,
+                     ErrorKind::LabelNotFound(_) => Error::LabelNotFound,
+                     _ => Error::UpdateLabelsFailed,
+                 })?;
+    item.map(|item| ResponseBody::UpdateTodo(item.into()))
+        .ok_or(Error::UpdateLabelsFailed)
+}
+
+fn update_item_notes(toodle: &mut Store,
+                     uuid: &str,
+                     notes: Option<String>)
+                     -> Result<ResponseBody, Error> {
+    let uuid = Uuid::parse_str(&uuid).map_err(|_err| Error::ItemNotFound)?;
+    let item = toodle
+        .fetch_item(&uuid)
+        .ok()
+        .unwrap_or_default()
+        .ok_or(Error::ItemNotFound)?;
+    toodle
+        .update_item_notes(&item, notes)
+        .and_then(|_| toodle.fetch_item(&uuid))
+        .unwrap_or_default()
+        .map(|item| ResponseBody::UpdateTodo(item.into()))
+        .ok_or(Error::UpdateItemFailed)
+}
+
+fn update_item_recurrence(toodle: &mut Store,
+                          uuid: &str,
+                          recurrence: Option<String>)
+                          -> Result<ResponseBody, Error> {
+    let recurrence = match recurrence {
+        Some(rule) => Some(rule.parse::<Recurrence>().map_err(|_err| Error::InvalidRecurrence)?),
+        None => None,
+    };
+    let uuid = Uuid::parse_str(&uuid).map_err(|_err| Error::ItemNotFound)?;
     let item = toodle
         .fetch_item(&uuid)
         .ok()
         .unwrap_or_default()
         .ok_or(Error::ItemNotFound)?;
-    let existing_labels = toodle.fetch_labels().unwrap_or(vec![]);
-    if to_add
-           .iter()
-           .any(|name| {
-                    existing_labels
-                        .iter()
-                        .find(|label| &label.name == name)
-                        .is_none()
-                }) {
-        return Err(Error::LabelNotFound);
+    toodle
+        .update_item_recurrence(&item, recurrence)
+        .and_then(|_| toodle.fetch_item(&uuid))
+        .unwrap_or_default()
+        .map(|item| ResponseBody::UpdateTodo(item.into()))
+        .ok_or(Error::UpdateItemFailed)
+}
+
+fn move_item(toodle: &mut Store, uuid: &str, parent: Option<String>) -> Result<ResponseBody, Error> {
+    let uuid = Uuid::parse_str(&uuid).map_err(|_err| Error::ItemNotFound)?;
+    let parent = match parent {
+        Some(parent) => Some(Uuid::parse_str(&parent).map_err(|_err| Error::ItemNotFound)?),
+        None => None,
+    };
+    toodle
+        .move_item(&uuid, parent.as_ref())
+        .map_err(|err| match *err.kind() {
+                     ErrorKind::ItemCycle(_) => Error::ItemCycle,
+                     ErrorKind::ItemNotFound(_) => Error::ItemNotFound,
+                     _ => Error::UpdateItemFailed,
+                 })?;
+    toodle
+        .fetch_item(&uuid)
+        .unwrap_or_default()
+        .map(|item| ResponseBody::UpdateTodo(item.into()))
+        .ok_or(Error::ItemNotFound)
+}
+
+fn reorder_item(toodle: &mut Store,
+                uuid: &str,
+                previous: Option<String>,
+                next: Option<String>)
+                -> Result<ResponseBody, Error> {
+    let uuid = Uuid::parse_str(&uuid).map_err(|_err| Error::ItemNotFound)?;
+    let parse = |uuid: Option<String>| match uuid {
+        Some(uuid) => Uuid::parse_str(&uuid).map(Some).map_err(|_err| Error::ItemNotFound),
+        None => Ok(None),
+    };
+    let previous = parse(previous)?;
+    let next = parse(next)?;
+    toodle
+        .reorder_item(&uuid, previous.as_ref(), next.as_ref())
+        .map_err(|err| match *err.kind() {
+                     ErrorKind::ItemNotFound(_) => Error::ItemNotFound,
+                     _ => Error::UpdateItemFailed,
+                 })?;
+    toodle
+        .fetch_item(&uuid)
+        .unwrap_or_default()
+        .map(|item| ResponseBody::UpdateTodo(item.into()))
+        .ok_or(Error::ItemNotFound)
+}
+
+fn complete_item(toodle: &mut Store,
+                 uuid: &str,
+                 completion_date: f64,
+                 cascade: bool)
+                 -> Result<ResponseBody, Error> {
+    let uuid = Uuid::parse_str(&uuid).map_err(|_err| Error::ItemNotFound)?;
+    toodle
+        .complete_item(&uuid, from_millis(completion_date), cascade)
+        .and_then(|_| toodle.fetch_item(&uuid))
+        .unwrap_or_default()
+        .map(|item| ResponseBody::UpdateTodo(item.into()))
+        .ok_or(Error::UpdateItemFailed)
+}
+
+fn parse_uuids(uuids: &[String]) -> Result<Vec<Uuid>, Error> {
+    uuids
+        .iter()
+        .map(|uuid| Uuid::parse_str(uuid).map_err(|_err| Error::ItemNotFound))
+        .collect()
+}
+
+/// Changes all the todos in `uuids` at once with `update`, and responds with them as they are
+/// afterwards.
+fn update_items<F>(toodle: &mut Store, uuids: &[String], update: F) -> Result<ResponseBody, Error>
+    where F: FnOnce(&mut Store, &[Uuid]) -> toodle::errors::Result<()>
+{
+    let uuids = parse_uuids(uuids)?;
+    update(toodle, &uuids).map_err(|err| match *err.kind() {
+                                       ErrorKind::ItemNotFound(_) => Error::ItemNotFound,
+                                       ErrorKind::LabelNotFound(_) => Error::LabelNotFound,
+                                       _ => Error::UpdateItemFailed,
+                                   })?;
+    let mut infos = vec![];
+    for uuid in uuids.iter() {
+        let item = toodle
+            .fetch_item(uuid)
+            .unwrap_or_default()
+            .ok_or(Error::ItemNotFound)?;
+        infos.push(item.into());
     }
-    let existing_item_label_names =
-        HashSet::<&String>::from_iter(item.labels.iter().map(|label| &label.name));
-    let mut new_labels = item.labels.clone();
-    let mut labels_to_add = to_add
+    Ok(ResponseBody::UpdateTodos(infos))
+}
+
+fn find_label(toodle: &mut Store, name: &str) -> Result<Label, Error> {
+    toodle
+        .fetch_labels()
+        .unwrap_or(vec![])
         .into_iter()
-        .filter_map(|name| if existing_item_label_names.contains(&name) {
-                        None
-                    } else {
-                        existing_labels
-                            .iter()
-                            .find(|label| label.name == name)
-                            .cloned()
-                    })
-        .collect::<Vec<Label>>();
-    new_labels.append(&mut labels_to_add);
-
-    let item_label_names_to_remove = HashSet::<&String>::from_iter(to_remove.iter());
-    new_labels.retain(|label| !item_label_names_to_remove.contains(&label.name));
+        .find(|label| label.name == name)
+        .ok_or(Error::LabelNotFound)
+}
+
+fn update_list(toodle: &mut Store,
+               uuid: &str,
+               name: Option<String>,
+               color: Option<String>,
+               sort_order: Option<i64>)
+               -> Result<ResponseBody, Error> {
+    let uuid = Uuid::parse_str(&uuid).map_err(|_err| Error::ListNotFound)?;
+    let list = toodle
+        .fetch_list(&uuid)
+        .ok()
+        .unwrap_or_default()
+        .ok_or(Error::ListNotFound)?;
+    toodle
+        .update_list(&list, name, color, sort_order)
+        .map(|list| ResponseBody::UpdateList(list.into()))
+        .map_err(|_err| Error::UpdateListFailed)
+}
 
+fn move_item_to_list(toodle: &mut Store, uuid: &str, list: &str) -> Result<ResponseBody, Error> {
+    let uuid = Uuid::parse_str(&uuid).map_err(|_err| Error::ItemNotFound)?;
+    let list = Uuid::parse_str(&list).map_err(|_err| Error::ListNotFound)?;
     toodle
-        .update_item(&item, None, None, None, Some(&new_labels))
-        .and_then(|_| toodle.fetch_item(&uuid))
+        .move_item_to_list(&uuid, &list)
+        .map_err(|err| match *err.kind() {
+                     ErrorKind::ListNotFound(_) => Error::ListNotFound,
+                     ErrorKind::ItemNotFound(_) => Error::ItemNotFound,
+                     _ => Error::UpdateItemFailed,
+                 })?;
+    toodle
+        .fetch_item(&uuid)
         .unwrap_or_default()
         .map(|item| ResponseBody::UpdateTodo(item.into()))
-        .ok_or(Error::UpdateLabelsFailed)
+        .ok_or(Error::ItemNotFound)
+}
+
+fn add_reminder(toodle: &mut Store,
+                todo: &str,
+                at: Option<f64>,
+                offset: Option<i64>)
+                -> Result<ResponseBody, Error> {
+    let trigger = match (at, offset) {
+        (Some(at), None) => ReminderTrigger::At(from_millis(at)),
+        (None, Some(offset)) => ReminderTrigger::BeforeDue(Duration::milliseconds(offset)),
+        _ => return Err(Error::BadRequest),
+    };
+    let uuid = Uuid::parse_str(&todo).map_err(|_err| Error::ItemNotFound)?;
+    toodle
+        .add_reminder(&uuid, trigger)
+        .map(|reminder| ResponseBody::AddReminder(reminder.into()))
+        .map_err(|err| match *err.kind() {
+                     ErrorKind::ItemNotFound(_) => Error::ItemNotFound,
+                     _ => Error::BadRequest,
+                 })
+}
+
+fn update_reminder(toodle: &mut Store, uuid: &str, snooze_until: Option<f64>) -> Result<ResponseBody, Error> {
+    let uuid = Uuid::parse_str(&uuid).map_err(|_err| Error::ReminderNotFound)?;
+    match snooze_until {
+        Some(until) => toodle.snooze_reminder(&uuid, from_millis(until)),
+        None => toodle.acknowledge_reminder(&uuid),
+    }.map_err(|err| match *err.kind() {
+                  ErrorKind::ReminderNotFound(_) => Error::ReminderNotFound,
+                  _ => Error::UpdateReminderFailed,
+              })?;
+    toodle
+        .fetch_reminder(&uuid)
+        .unwrap_or_default()
+        .map(|reminder| ResponseBody::UpdateReminder(reminder.into()))
+        .ok_or(Error::ReminderNotFound)
+}
+
+/// Undoes or redoes the most recent change, if there is one, and reports what can be undone
+/// or redone next.
+fn undo(toodle: &mut Store, redo: Option<bool>) -> Result<ResponseBody, Error> {
+    match redo {
+        Some(true) => toodle.redo(),
+        Some(false) => toodle.undo(),
+        None => Ok(false),
+    }.map_err(|_err| Error::UndoFailed)?;
+    let can_undo = toodle.can_undo().map_err(|_err| Error::UndoFailed)?;
+    let can_redo = toodle.can_redo().map_err(|_err| Error::UndoFailed)?;
+    Ok(ResponseBody::UndoState { can_undo, can_redo })
+}
+
+fn export(toodle: &mut Store) -> Result<ResponseBody, Error> {
+    toodle
+        .export_json()
+        .ok()
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .map(ResponseBody::Export)
+        .ok_or(Error::ExportFailed)
 }
 
-fn to_millis(time: &Timespec) -> i64 {
-    time.sec * 1000 + (time.nsec / 1000000) as i64
+fn import(toodle: &mut Store, document: serde_json::Value) -> Result<ResponseBody, Error> {
+    toodle
+        .import_json(&document.to_string())
+        .map(|_| ResponseBody::Import)
+        .map_err(|err| match *err.kind() {
+            ErrorKind::ListNotFound(_) => Error::ListNotFound,
+            ErrorKind::ItemNotFound(_) => Error::ItemNotFound,
+            ErrorKind::InvalidRecurrence(_) => Error::InvalidRecurrence,
+            ErrorKind::InvalidExport(_) => Error::BadJSON,
+            _ => Error::ImportFailed,
+        })
+}
+
+fn update_label(toodle: &mut Store,
+                name: &str,
+                new_name: Option<String>,
+                color: Option<String>)
+                -> Result<ResponseBody, Error> {
+    let mut label = toodle
+        .fetch_label(&name.to_string())
+        .ok()
+        .unwrap_or_default()
+        .ok_or(Error::LabelNotFound)?;
+    if let Some(color) = color {
+        label = toodle
+            .update_label_color(&label, color)
+            .map_err(|_err| Error::UpdateLabelFailed)?
+            .ok_or(Error::LabelNotFound)?;
+    }
+    if let Some(new_name) = new_name {
+        label = toodle
+            .rename_label(&label, new_name)
+            .map_err(|err| match *err.kind() {
+                         ErrorKind::LabelAlreadyExists(_) => Error::LabelAlreadyExists,
+                         _ => Error::UpdateLabelFailed,
+                     })?
+            .ok_or(Error::LabelNotFound)?;
+    }
+    Ok(ResponseBody::UpdateLabel(label.into()))
 }
 
-fn from_millis(millis: i64) -> Timespec {
-    Timespec::new(millis / 1000, (millis % 1000 * 1000000) as i32)
+/// Converts a value from a todo's history to JSON, with instants in milliseconds like every
+/// other date we send.
+fn value_to_json(value: TypedValue) -> serde_json::Value {
+    match value {
+        TypedValue::String(s) => s.to_string().into(),
+        TypedValue::Uuid(u) => u.hyphenated().to_string().into(),
+        TypedValue::Instant(instant) => (instant.timestamp() as f64 * 1000.0 + f64::from(instant.timestamp_subsec_micros()) / 1000.0).into(),
+        TypedValue::Long(l) => l.into(),
+        TypedValue::Ref(r) => r.into(),
+        TypedValue::Boolean(b) => b.into(),
+        TypedValue::Double(d) => d.0.into(),
+        TypedValue::Keyword(k) => k.to_string().into(),
+    }
 }
 
 fn main() {
@@ -271,7 +802,17 @@ fn main() {
                     }
                     RequestBody::GetTodos => {
                         toodle
-                            .fetch_items()
+                            .fetch_items(&ItemQuery::new())
+                            .map(|items| {
+                                     let infos =
+                                         items.vec.into_iter().map(|item| item.into()).collect();
+                                     ResponseBody::GetTodos(infos)
+                                 })
+                            .map_err(|_err| Error::BadRequest)
+                    }
+                    RequestBody::QueryTodos(info) => {
+                        toodle
+                            .fetch_items(&info.into())
                             .map(|items| {
                                      let infos =
                                          items.vec.into_iter().map(|item| item.into()).collect();
@@ -279,12 +820,39 @@ fn main() {
                                  })
                             .map_err(|_err| Error::BadRequest)
                     }
+                    RequestBody::SearchTodos { text } => {
+                        toodle
+                            .search_items(&text)
+                            .map(|results| {
+                                     let infos =
+                                         results.into_iter().map(|result| result.into()).collect();
+                                     ResponseBody::SearchTodos(infos)
+                                 })
+                            .map_err(|_err| Error::BadRequest)
+                    }
                     RequestBody::TodoChangeName { uuid, name } => {
                         toodle
                             .update_item_by_uuid(&uuid, Some(name), None, None)
                             .map(|item| ResponseBody::UpdateTodo(item.into()))
                             .map_err(|_err| Error::UpdateItemFailed)
                     }
+                    RequestBody::TodoChangeNotes { uuid, notes } => {
+                        update_item_notes(&mut toodle, &uuid, notes)
+                    }
+                    RequestBody::TodoChangeRecurrence { uuid, recurrence } => {
+                        update_item_recurrence(&mut toodle, &uuid, recurrence)
+                    }
+                    RequestBody::GetOccurrences { start, end } => {
+                        toodle
+                            .fetch_occurrences(from_millis(start), from_millis(end))
+                            .map(|occurrences| {
+                                     let infos = occurrences.into_iter()
+                                                            .map(|occurrence| occurrence.into())
+                                                            .collect();
+                                     ResponseBody::GetOccurrences(infos)
+                                 })
+                            .map_err(|_err| Error::BadRequest)
+                    }
                     RequestBody::TodoChangeDueDate { uuid, due_date } => {
                         toodle
                             .update_item_by_uuid(&uuid, None, Some(from_millis(due_date)), None)
@@ -309,7 +877,95 @@ fn main() {
                     RequestBody::TodoRemoveLabel { uuid, name } => {
                         update_item_labels(&mut toodle, &uuid, vec![], vec![name])
                     }
-                    RequestBody::RemoveTodo { uuid } => Err(Error::NotImplemented),
+                    RequestBody::GetTodoChildren { uuid } => {
+                        Uuid::parse_str(&uuid)
+                            .map_err(|_err| Error::ItemNotFound)
+                            .and_then(|item_uuid| {
+                                toodle
+                                    .fetch_children(&item_uuid)
+                                    .map_err(|_err| Error::BadRequest)
+                            })
+                            .map(|items| {
+                                     let infos = items.into_iter().map(|item| item.into()).collect();
+                                     ResponseBody::GetTodos(infos)
+                                 })
+                    }
+                    RequestBody::GetTodoHistory { uuid } => {
+                        Uuid::parse_str(&uuid)
+                            .map_err(|_err| Error::ItemNotFound)
+                            .and_then(|item_uuid| {
+                                toodle
+                                    .fetch_item_history(&item_uuid)
+                                    .map_err(|err| match *err.kind() {
+                                                 ErrorKind::ItemNotFound(_) => Error::ItemNotFound,
+                                                 _ => Error::BadRequest,
+                                             })
+                            })
+                            .map(|changes| {
+                                     let infos = changes.into_iter().map(|change| change.into()).collect();
+                                     ResponseBody::GetTodoHistory(infos)
+                                 })
+                    }
+                    RequestBody::MoveTodo { uuid, parent } => {
+                        move_item(&mut toodle, &uuid, parent)
+                    }
+                    RequestBody::ReorderTodo { uuid, previous, next } => {
+                        reorder_item(&mut toodle, &uuid, previous, next)
+                    }
+                    RequestBody::CompleteTodo {
+                        uuid,
+                        completion_date,
+                        cascade,
+                    } => complete_item(&mut toodle, &uuid, completion_date, cascade),
+                    RequestBody::RemoveTodo { uuid } => {
+                        Uuid::parse_str(&uuid)
+                            .map_err(|_err| Error::ItemNotFound)
+                            .and_then(|item_uuid| {
+                                toodle
+                                    .delete_item(&item_uuid)
+                                    .map_err(|_err| Error::RemoveItemFailed)
+                            })
+                            .map(|_| ResponseBody::RemoveTodo { uuid })
+                    }
+                    RequestBody::CompleteTodos { uuids, completion_date } => {
+                        update_items(&mut toodle, &uuids, |toodle, uuids| {
+                            toodle.complete_items(uuids, from_millis(completion_date))
+                        })
+                    }
+                    RequestBody::UncompleteTodos { uuids } => {
+                        update_items(&mut toodle, &uuids, |toodle, uuids| toodle.uncomplete_items(uuids))
+                    }
+                    RequestBody::TodosChangeDueDate { uuids, due_date } => {
+                        update_items(&mut toodle, &uuids, |toodle, uuids| {
+                            toodle.set_due_date_for_items(uuids, due_date.map(from_millis))
+                        })
+                    }
+                    RequestBody::TodosAddLabel { uuids, name } => {
+                        find_label(&mut toodle, &name).and_then(|label| {
+                            update_items(&mut toodle, &uuids, |toodle, uuids| {
+                                toodle.add_label_to_items(uuids, &label)
+                            })
+                        })
+                    }
+                    RequestBody::TodosRemoveLabel { uuids, name } => {
+                        find_label(&mut toodle, &name).and_then(|label| {
+                            update_items(&mut toodle, &uuids, |toodle, uuids| {
+                                toodle.remove_label_from_items(uuids, &label)
+                            })
+                        })
+                    }
+                    RequestBody::RemoveTodos { uuids } => {
+                        parse_uuids(&uuids)
+                            .and_then(|item_uuids| {
+                                toodle
+                                    .delete_items(&item_uuids)
+                                    .map_err(|err| match *err.kind() {
+                                                 ErrorKind::ItemNotFound(_) => Error::ItemNotFound,
+                                                 _ => Error::RemoveItemFailed,
+                                             })
+                            })
+                            .map(|_| ResponseBody::RemoveTodos { uuids })
+                    }
                     RequestBody::AddLabel(info) => {
                         toodle
                             .create_label(info.name, info.color.unwrap_or_default())
@@ -317,7 +973,21 @@ fn main() {
                             .map(|label| ResponseBody::AddLabel(label.into()))
                             .ok_or(Error::BadRequest)
                     }
-                    RequestBody::RemoveLabel { name } => Err(Error::NotImplemented),
+                    RequestBody::RemoveLabel { name } => {
+                        toodle
+                            .delete_label(&name)
+                            .map(|_| ResponseBody::RemoveLabel { name })
+                            .map_err(|err| match *err.kind() {
+                                         ErrorKind::LabelNotFound(_) => Error::LabelNotFound,
+                                         _ => Error::RemoveLabelFailed,
+                                     })
+                    }
+                    RequestBody::LabelChangeName { name, new_name } => {
+                        update_label(&mut toodle, &name, Some(new_name), None)
+                    }
+                    RequestBody::LabelChangeColor { name, color } => {
+                        update_label(&mut toodle, &name, None, Some(color))
+                    }
                     RequestBody::GetLabels => {
                         toodle
                             .fetch_labels()
@@ -328,6 +998,93 @@ fn main() {
                                  })
                             .map_err(|_err| Error::BadRequest)
                     }
+                    RequestBody::GetLists => {
+                        toodle
+                            .fetch_lists()
+                            .map(|lists| {
+                                     let infos =
+                                         lists.into_iter().map(|list| list.into()).collect();
+                                     ResponseBody::GetLists(infos)
+                                 })
+                            .map_err(|_err| Error::BadRequest)
+                    }
+                    RequestBody::AddList(info) => {
+                        toodle
+                            .create_list(info.name, info.color.unwrap_or_default())
+                            .map(|list| ResponseBody::AddList(list.into()))
+                            .map_err(|_err| Error::BadRequest)
+                    }
+                    RequestBody::ListChange {
+                        uuid,
+                        name,
+                        color,
+                        sort_order,
+                    } => update_list(&mut toodle, &uuid, name, color, sort_order),
+                    RequestBody::RemoveList { uuid } => {
+                        Uuid::parse_str(&uuid)
+                            .map_err(|_err| Error::ListNotFound)
+                            .and_then(|list_uuid| {
+                                toodle
+                                    .delete_list(&list_uuid)
+                                    .map_err(|err| match *err.kind() {
+                                                 ErrorKind::ListNotFound(_) => Error::ListNotFound,
+                                                 _ => Error::RemoveListFailed,
+                                             })
+                            })
+                            .map(|_| ResponseBody::RemoveList { uuid })
+                    }
+                    RequestBody::TodoChangeList { uuid, list } => {
+                        move_item_to_list(&mut toodle, &uuid, &list)
+                    }
+                    RequestBody::AddReminder { todo, at, offset } => {
+                        add_reminder(&mut toodle, &todo, at, offset)
+                    }
+                    RequestBody::GetReminders { todo } => {
+                        Uuid::parse_str(&todo)
+                            .map_err(|_err| Error::ItemNotFound)
+                            .and_then(|item_uuid| {
+                                toodle
+                                    .fetch_reminders(&item_uuid)
+                                    .map_err(|_err| Error::BadRequest)
+                            })
+                            .map(|reminders| {
+                                     let infos = reminders.into_iter().map(|reminder| reminder.into()).collect();
+                                     ResponseBody::GetReminders(infos)
+                                 })
+                    }
+                    RequestBody::PendingReminders { now, window } => {
+                        toodle
+                            .pending_reminders(from_millis(now), Duration::milliseconds(window))
+                            .map(|reminders| {
+                                     let infos = reminders.into_iter().map(|reminder| reminder.into()).collect();
+                                     ResponseBody::PendingReminders(infos)
+                                 })
+                            .map_err(|_err| Error::BadRequest)
+                    }
+                    RequestBody::AcknowledgeReminder { uuid } => {
+                        update_reminder(&mut toodle, &uuid, None)
+                    }
+                    RequestBody::SnoozeReminder { uuid, until } => {
+                        update_reminder(&mut toodle, &uuid, Some(until))
+                    }
+                    RequestBody::RemoveReminder { uuid } => {
+                        Uuid::parse_str(&uuid)
+                            .map_err(|_err| Error::ReminderNotFound)
+                            .and_then(|reminder_uuid| {
+                                toodle
+                                    .delete_reminder(&reminder_uuid)
+                                    .map_err(|err| match *err.kind() {
+                                                 ErrorKind::ReminderNotFound(_) => Error::ReminderNotFound,
+                                                 _ => Error::BadRequest,
+                                             })
+                            })
+                            .map(|_| ResponseBody::RemoveReminder { uuid })
+                    }
+                    RequestBody::Undo => undo(&mut toodle, Some(false)),
+                    RequestBody::Redo => undo(&mut toodle, Some(true)),
+                    RequestBody::GetUndoState => undo(&mut toodle, None),
+                    RequestBody::Export => export(&mut toodle),
+                    RequestBody::Import { document } => import(&mut toodle, document),
                 };
                 match result {
                     Ok(body) => {